@@ -0,0 +1,24 @@
+use std::env;
+use std::process::Command;
+
+/// Sets `cfg(nightly)` when the `nightly` feature is enabled and the
+/// compiler can build the unstable APIs it uses.
+fn main() {
+    println!("cargo:rustc-check-cfg=cfg(nightly)");
+    println!("cargo:rerun-if-env-changed=RUSTC");
+    if env::var_os("CARGO_FEATURE_NIGHTLY").is_none() {
+        return;
+    }
+
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let version = Command::new(rustc)
+        .arg("--version")
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+        .unwrap_or_default();
+    if version.contains("nightly") || version.contains("-dev") {
+        println!("cargo:rustc-cfg=nightly");
+    } else {
+        println!("cargo:warning=The nightly feature has no effect without a nightly toolchain.");
+    }
+}
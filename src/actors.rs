@@ -0,0 +1,176 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::interpreter::{Interpreter, STACK_SIZE};
+use crate::lox::Lox;
+use crate::lox_error::{LoxError, NativeError};
+use crate::messages;
+use crate::token::{Foreign, Literal};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A value that can cross to another interpreter: plain data only, copied
+/// rather than shared.
+pub enum Message {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    List(Vec<Message>),
+    Map(BTreeMap<String, Message>),
+}
+
+impl Message {
+    pub fn from_literal(value: &Literal) -> Result<Self, NativeError> {
+        Ok(match value {
+            Literal::None => Message::Nil,
+            Literal::Bool(b) => Message::Bool(*b),
+            Literal::Number(n) => Message::Number(*n),
+            Literal::String(s) => Message::String(s.clone()),
+            Literal::List(elements) => Message::List(
+                elements
+                    .borrow()
+                    .iter()
+                    .map(Message::from_literal)
+                    .collect::<Result<_, _>>()?,
+            ),
+            Literal::Map(entries) => Message::Map(
+                entries
+                    .borrow()
+                    .iter()
+                    .map(|(k, v)| Ok((k.clone(), Message::from_literal(v)?)))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Literal::Callable(_) | Literal::Foreign(_) | Literal::Instance(_) => {
+                return Err(NativeError::from_message(messages::ONLY_PLAIN_DATA, &[]))
+            }
+        })
+    }
+
+    pub fn into_literal(self) -> Literal {
+        match self {
+            Message::Nil => Literal::None,
+            Message::Bool(b) => Literal::Bool(b),
+            Message::Number(n) => Literal::Number(n),
+            Message::String(s) => Literal::String(s),
+            Message::List(elements) => Literal::List(Rc::new(RefCell::new(
+                elements.into_iter().map(Message::into_literal).collect(),
+            ))),
+            Message::Map(entries) => Literal::Map(Rc::new(RefCell::new(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (k, v.into_literal()))
+                    .collect(),
+            ))),
+        }
+    }
+}
+
+/// Where messages for another interpreter are sent.
+pub struct Address {
+    sender: Sender<Message>,
+}
+
+impl Foreign for Address {
+    fn type_name(&self) -> &'static str {
+        "worker"
+    }
+
+    fn method_arity(&self, method: &str) -> Option<usize> {
+        match method {
+            "send" => Some(1),
+            _ => None,
+        }
+    }
+
+    fn call_method(
+        &mut self,
+        _interpreter: &mut Interpreter,
+        method: &str,
+        arguments: &[Literal],
+    ) -> Result<Literal, LoxError> {
+        match method {
+            "send" => {
+                let message = Message::from_literal(&arguments[0])?;
+                self.sender
+                    .send(message)
+                    .map_err(|_| NativeError::from_message(messages::WORKER_STOPPED, &[]))?;
+                Ok(Literal::None)
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// An interpreter's connections to the workers it spawned and, for a
+/// worker, to the interpreter that spawned it. Workers receive from their
+/// parent; every other interpreter receives from its workers.
+#[derive(Default)]
+pub struct Mailbox {
+    parent: Option<Sender<Message>>,
+    from_parent: Option<Receiver<Message>>,
+    from_workers: Option<(Sender<Message>, Receiver<Message>)>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl Mailbox {
+    pub fn parent(&self) -> Option<Address> {
+        self.parent.clone().map(|sender| Address { sender })
+    }
+
+    /// Runs `source` in a fresh interpreter on its own thread.
+    pub fn spawn(&mut self, name: &str, source: String) -> Address {
+        let parent = self
+            .from_workers
+            .get_or_insert_with(mpsc::channel)
+            .0
+            .clone();
+        let (sender, receiver) = mpsc::channel();
+
+        let name = name.to_string();
+        let worker = thread::Builder::new()
+            .name(name.clone())
+            .stack_size(STACK_SIZE)
+            .spawn(move || {
+                let mut interpreter = Interpreter::new();
+                interpreter.mailbox = Mailbox {
+                    parent: Some(parent),
+                    from_parent: Some(receiver),
+                    ..Default::default()
+                };
+                if let Err(e) = Lox::with_interpreter(interpreter).run(&source) {
+                    eprintln!("{}: {}", name, e);
+                }
+            })
+            .expect("Failed to spawn worker thread");
+
+        self.workers.push(worker);
+        Address { sender }
+    }
+
+    /// Waits for the next message. Returns `None` once no more can arrive:
+    /// a worker's parent dropped its handle, or all of an interpreter's
+    /// workers finished.
+    pub fn receive(&mut self) -> Option<Message> {
+        if let Some(from_parent) = &self.from_parent {
+            return from_parent.recv().ok();
+        }
+
+        let (_, from_workers) = self.from_workers.as_ref()?;
+        loop {
+            match from_workers.recv_timeout(POLL_INTERVAL) {
+                Ok(message) => return Some(message),
+                Err(RecvTimeoutError::Disconnected) => return None,
+                Err(RecvTimeoutError::Timeout) => {
+                    if self.workers.iter().all(|worker| worker.is_finished()) {
+                        return from_workers.try_recv().ok();
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,10 +1,27 @@
+use std::fmt;
+use std::rc::Rc;
+
+use crate::ast_printer::{print_expr, print_stmt};
+use crate::inline_cache::InlineCache;
 use crate::token::{Literal, Token};
 
+/// The optional type annotations of a function, `fun f(a: number) -> string`.
+/// The interpreter ignores them; only `typecheck` reads them.
+#[derive(Clone, Default)]
+pub struct Signature {
+    /// One entry per parameter.
+    pub params: Vec<Option<Token>>,
+    pub returns: Option<Token>,
+}
+
 #[derive(Clone)]
 pub enum Expr {
     Assign {
         name: Token,
         value: Box<Expr>,
+        /// How many scopes out `name` is declared, once resolved. `None`
+        /// looks it up through every enclosing scope.
+        depth: Option<usize>,
     },
     Binary {
         left: Box<Expr>,
@@ -16,9 +33,29 @@ pub enum Expr {
         paren: Token,
         arguments: Vec<Expr>,
     },
+    Get {
+        object: Box<Expr>,
+        name: Token,
+        /// The method this site last found, for instances of its class.
+        cache: InlineCache,
+    },
     Grouping {
         expression: Box<Expr>,
     },
+    Index {
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+    },
+    Lambda {
+        keyword: Token,
+        params: Vec<Token>,
+        signature: Signature,
+        body: Vec<Stmt>,
+    },
+    List {
+        elements: Vec<Expr>,
+    },
     Literal {
         value: Literal,
     },
@@ -27,12 +64,61 @@ pub enum Expr {
         operator: Token,
         right: Box<Expr>,
     },
+    /// `{key: value, ...}`, whose keys must evaluate to strings.
+    Map {
+        brace: Token,
+        entries: Vec<(Expr, Expr)>,
+    },
+    /// `object.name = value`, or with `operator` a compound assignment like
+    /// `object.name += value`.
+    Set {
+        object: Box<Expr>,
+        name: Token,
+        operator: Option<Token>,
+        value: Box<Expr>,
+    },
+    /// `object[index] = value`, or with `operator` a compound assignment.
+    SetIndex {
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+        operator: Option<Token>,
+        value: Box<Expr>,
+    },
+    /// `target++` or `target--`, which evaluates to the value `target` had
+    /// before. `++target` is parsed as `target += 1` instead.
+    Postfix {
+        operator: Token,
+        /// An `Expr::Variable`, `Expr::Get` or `Expr::Index`.
+        target: Box<Expr>,
+    },
+    Spread {
+        operator: Token,
+        expression: Box<Expr>,
+    },
+    /// `super.method`, the superclass's `method` bound to `this`.
+    Super {
+        keyword: Token,
+        method: Token,
+    },
+    /// `condition ? then_branch : else_branch`, which only evaluates the
+    /// branch it picks.
+    Ternary {
+        condition: Box<Expr>,
+        then_branch: Box<Expr>,
+        else_branch: Box<Expr>,
+    },
+    This {
+        keyword: Token,
+    },
     Unary {
         operator: Token,
         right: Box<Expr>,
     },
     Variable {
         name: Token,
+        /// See `Expr::Assign::depth`.
+        depth: Option<usize>,
     },
 }
 
@@ -41,32 +127,133 @@ pub enum Stmt {
     Block {
         statements: Vec<Stmt>,
     },
+    Class {
+        name: Box<Token>,
+        /// An `Expr::Variable` naming the class after `<`.
+        superclass: Option<Box<Expr>>,
+        /// `Stmt::Function`s, one per method.
+        methods: Vec<Stmt>,
+        /// The `///` comment before the declaration.
+        doc: Option<Rc<str>>,
+    },
+    Break {
+        keyword: Box<Token>,
+        label: Option<Box<Token>>,
+    },
+    Continue {
+        keyword: Box<Token>,
+        label: Option<Box<Token>>,
+    },
     Expression {
         expression: Box<Expr>,
     },
     Function {
         name: Box<Token>,
         params: Vec<Token>,
+        signature: Signature,
         body: Vec<Stmt>,
+        /// The `///` comment before the declaration.
+        doc: Option<Rc<str>>,
     },
     If {
+        keyword: Box<Token>,
         condition: Box<Expr>,
         then_branch: Box<Stmt>,
         else_branch: Option<Box<Stmt>>,
     },
     Print {
+        keyword: Box<Token>,
         expression: Box<Expr>,
     },
     Return {
         keyword: Box<Token>,
         value: Option<Box<Expr>>,
     },
+    Throw {
+        keyword: Box<Token>,
+        value: Box<Expr>,
+    },
+    /// Runs `handler` with the error bound to `name` if `body` fails.
+    Try {
+        body: Vec<Stmt>,
+        name: Box<Token>,
+        handler: Vec<Stmt>,
+    },
     Var {
         name: Box<Token>,
+        /// The type after `name:`, which only `typecheck` reads.
+        annotation: Option<Box<Token>>,
         initializer: Option<Box<Expr>>,
     },
     While {
+        keyword: Box<Token>,
         condition: Box<Expr>,
         body: Box<Stmt>,
+        increment: Option<Box<Expr>>,
+        bindings: Vec<Token>,
+        label: Option<Box<Token>>,
     },
 }
+
+/// Shown in the form `ast_printer` renders it in.
+impl fmt::Debug for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&print_expr(self))
+    }
+}
+
+impl fmt::Debug for Stmt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&print_stmt(self))
+    }
+}
+
+impl Expr {
+    /// The line the expression starts on, if it has a token to tell.
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            Expr::Assign { name, .. } | Expr::Variable { name, .. } => Some(name.line),
+            Expr::Binary { left, .. }
+            | Expr::Call { callee: left, .. }
+            | Expr::Get { object: left, .. }
+            | Expr::Index { object: left, .. }
+            | Expr::Logical { left, .. }
+            | Expr::Set { object: left, .. }
+            | Expr::SetIndex { object: left, .. }
+            | Expr::Ternary {
+                condition: left, ..
+            }
+            | Expr::Grouping { expression: left } => left.line(),
+            Expr::Lambda { keyword, .. }
+            | Expr::Map { brace: keyword, .. }
+            | Expr::Super { keyword, .. }
+            | Expr::This { keyword } => Some(keyword.line),
+            Expr::List { elements } => elements.first().and_then(Expr::line),
+            Expr::Literal { .. } => None,
+            Expr::Postfix { operator, .. }
+            | Expr::Spread { operator, .. }
+            | Expr::Unary { operator, .. } => Some(operator.line),
+        }
+    }
+}
+
+impl Stmt {
+    /// The line the statement starts on, if it has a token to tell.
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            Stmt::Block { statements } => statements.first().and_then(Stmt::line),
+            Stmt::Expression { expression } => expression.line(),
+            Stmt::Class { name, .. } | Stmt::Function { name, .. } | Stmt::Var { name, .. } => {
+                Some(name.line)
+            }
+            Stmt::Try { body, name, .. } => body.first().and_then(Stmt::line).or(Some(name.line)),
+            Stmt::Break { keyword, .. }
+            | Stmt::Continue { keyword, .. }
+            | Stmt::If { keyword, .. }
+            | Stmt::Print { keyword, .. }
+            | Stmt::Return { keyword, .. }
+            | Stmt::Throw { keyword, .. }
+            | Stmt::While { keyword, .. } => Some(keyword.line),
+        }
+    }
+}
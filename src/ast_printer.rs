@@ -0,0 +1,579 @@
+use crate::ast::{Expr, Signature, Stmt};
+use crate::token::{Literal, Token};
+
+/// Renders an expression in a fully parenthesized, Lisp-like form, e.g.
+/// `(* (- 1) (group (+ 2 3)))`.
+pub fn print_expr(expr: &Expr) -> String {
+    match expr {
+        Expr::Assign { name, value, .. } => {
+            parenthesize("=", [name.lexeme.clone(), print_expr(value)])
+        }
+        Expr::Binary {
+            left,
+            operator,
+            right,
+        }
+        | Expr::Logical {
+            left,
+            operator,
+            right,
+        } => parenthesize(&operator.lexeme, [print_expr(left), print_expr(right)]),
+        Expr::Call {
+            callee, arguments, ..
+        } => parenthesize(
+            "call",
+            std::iter::once(print_expr(callee)).chain(arguments.iter().map(print_expr)),
+        ),
+        Expr::Get { object, name, .. } => {
+            parenthesize(".", [print_expr(object), name.lexeme.clone()])
+        }
+        Expr::Grouping { expression } => parenthesize("group", [print_expr(expression)]),
+        Expr::Index { object, index, .. } => {
+            parenthesize("index", [print_expr(object), print_expr(index)])
+        }
+        Expr::Lambda {
+            params,
+            signature,
+            body,
+            ..
+        } => parenthesize(
+            "fun",
+            std::iter::once(print_params(params, signature)).chain(body.iter().map(print_stmt)),
+        ),
+        Expr::List { elements } => parenthesize("list", elements.iter().map(print_expr)),
+        Expr::Literal { value } => print_literal(value),
+        Expr::Map { entries, .. } => parenthesize(
+            "map",
+            entries
+                .iter()
+                .flat_map(|(key, value)| [print_expr(key), print_expr(value)]),
+        ),
+        Expr::Set {
+            object,
+            name,
+            operator,
+            value,
+        } => parenthesize(
+            &assignment_name(operator.as_ref()),
+            [
+                parenthesize(".", [print_expr(object), name.lexeme.clone()]),
+                print_expr(value),
+            ],
+        ),
+        Expr::SetIndex {
+            object,
+            index,
+            operator,
+            value,
+            ..
+        } => parenthesize(
+            &assignment_name(operator.as_ref()),
+            [
+                parenthesize("index", [print_expr(object), print_expr(index)]),
+                print_expr(value),
+            ],
+        ),
+        Expr::Postfix { operator, target } => {
+            parenthesize("postfix", [operator.lexeme.clone(), print_expr(target)])
+        }
+        Expr::Spread {
+            operator,
+            expression,
+        } => parenthesize(&operator.lexeme, [print_expr(expression)]),
+        Expr::Super { method, .. } => parenthesize("super", [method.lexeme.clone()]),
+        Expr::Ternary {
+            condition,
+            then_branch,
+            else_branch,
+        } => parenthesize(
+            "?:",
+            [
+                print_expr(condition),
+                print_expr(then_branch),
+                print_expr(else_branch),
+            ],
+        ),
+        Expr::This { .. } => "this".to_string(),
+        Expr::Unary { operator, right } => parenthesize(&operator.lexeme, [print_expr(right)]),
+        Expr::Variable { name, .. } => name.lexeme.clone(),
+    }
+}
+
+/// Renders a statement in the same form as `print_expr`, e.g.
+/// `(if (< a b) (print a) (print b))`.
+pub fn print_stmt(stmt: &Stmt) -> String {
+    match stmt {
+        Stmt::Block { statements } => parenthesize("block", statements.iter().map(print_stmt)),
+        Stmt::Class {
+            name,
+            superclass,
+            methods,
+            ..
+        } => parenthesize(
+            "class",
+            std::iter::once(name.lexeme.clone())
+                .chain(
+                    superclass
+                        .as_deref()
+                        .map(|s| format!("< {}", print_expr(s))),
+                )
+                .chain(methods.iter().map(print_stmt)),
+        ),
+        Stmt::Break { label, .. } => parenthesize("break", label.iter().map(|l| l.lexeme.clone())),
+        Stmt::Continue { label, .. } => {
+            parenthesize("continue", label.iter().map(|l| l.lexeme.clone()))
+        }
+        Stmt::Expression { expression } => parenthesize(";", [print_expr(expression)]),
+        Stmt::Function {
+            name,
+            params,
+            signature,
+            body,
+            ..
+        } => parenthesize(
+            "fun",
+            [name.lexeme.clone(), print_params(params, signature)]
+                .into_iter()
+                .chain(body.iter().map(print_stmt)),
+        ),
+        Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+            ..
+        } => parenthesize(
+            "if",
+            [print_expr(condition), print_stmt(then_branch)]
+                .into_iter()
+                .chain(else_branch.as_deref().map(print_stmt)),
+        ),
+        Stmt::Print { expression, .. } => parenthesize("print", [print_expr(expression)]),
+        Stmt::Return { value, .. } => parenthesize("return", value.as_deref().map(print_expr)),
+        Stmt::Throw { value, .. } => parenthesize("throw", [print_expr(value)]),
+        Stmt::Try {
+            body,
+            name,
+            handler,
+        } => parenthesize(
+            "try",
+            [
+                parenthesize("block", body.iter().map(print_stmt)),
+                parenthesize(
+                    &format!("catch {}", name.lexeme),
+                    handler.iter().map(print_stmt),
+                ),
+            ],
+        ),
+        Stmt::Var {
+            name,
+            annotation,
+            initializer,
+        } => parenthesize(
+            "var",
+            std::iter::once(annotated(name, annotation.as_deref()))
+                .chain(initializer.as_deref().map(print_expr)),
+        ),
+        Stmt::While {
+            condition,
+            body,
+            increment,
+            label,
+            ..
+        } => {
+            let name = match label {
+                Some(label) => format!("while {}:", label.lexeme),
+                None => "while".to_string(),
+            };
+            parenthesize(
+                &name,
+                [print_expr(condition), print_stmt(body)]
+                    .into_iter()
+                    .chain(increment.as_deref().map(print_expr)),
+            )
+        }
+    }
+}
+
+/// Renders an expression back into Lox source that parses to the same
+/// tree. Only groupings are parenthesized, so operands are printed as the
+/// tree nests them.
+pub fn source_expr(expr: &Expr) -> String {
+    match expr {
+        Expr::Assign { name, value, .. } => format!("{} = {}", name.lexeme, source_expr(value)),
+        Expr::Binary {
+            left,
+            operator,
+            right,
+        }
+        | Expr::Logical {
+            left,
+            operator,
+            right,
+        } => format!(
+            "{} {} {}",
+            source_expr(left),
+            operator.lexeme,
+            source_expr(right)
+        ),
+        Expr::Call {
+            callee, arguments, ..
+        } => format!("{}({})", source_expr(callee), source_list(arguments)),
+        Expr::Get { object, name, .. } => format!("{}.{}", source_expr(object), name.lexeme),
+        Expr::Grouping { expression } => format!("({})", source_expr(expression)),
+        Expr::Index { object, index, .. } => {
+            format!("{}[{}]", source_expr(object), source_expr(index))
+        }
+        Expr::Lambda {
+            params,
+            signature,
+            body,
+            ..
+        } => format!(
+            "fun {} {}",
+            source_params(params, signature),
+            source_block(body)
+        ),
+        Expr::List { elements } => format!("[{}]", source_list(elements)),
+        Expr::Literal { value } => match value {
+            Literal::String(s) => format!("\"{}\"", s),
+            value => value.to_string(),
+        },
+        Expr::Map { entries, .. } => {
+            let entries: Vec<String> = entries
+                .iter()
+                .map(|(key, value)| format!("{}: {}", source_expr(key), source_expr(value)))
+                .collect();
+            format!("{{{}}}", entries.join(", "))
+        }
+        Expr::Set {
+            object,
+            name,
+            operator,
+            value,
+        } => format!(
+            "{}.{} {} {}",
+            source_expr(object),
+            name.lexeme,
+            assignment_name(operator.as_ref()),
+            source_expr(value)
+        ),
+        Expr::SetIndex {
+            object,
+            index,
+            operator,
+            value,
+            ..
+        } => format!(
+            "{}[{}] {} {}",
+            source_expr(object),
+            source_expr(index),
+            assignment_name(operator.as_ref()),
+            source_expr(value)
+        ),
+        Expr::Postfix { operator, target } => {
+            format!("{}{}", source_expr(target), operator.lexeme)
+        }
+        Expr::Spread {
+            operator,
+            expression,
+        } => format!("{}{}", operator.lexeme, source_expr(expression)),
+        Expr::Super { method, .. } => format!("super.{}", method.lexeme),
+        Expr::Ternary {
+            condition,
+            then_branch,
+            else_branch,
+        } => format!(
+            "{} ? {} : {}",
+            source_expr(condition),
+            source_expr(then_branch),
+            source_expr(else_branch)
+        ),
+        Expr::This { .. } => "this".to_string(),
+        Expr::Unary { operator, right } => {
+            let right = source_expr(right);
+            // `- -x` mustn't run together into a decrement
+            if right.starts_with(&operator.lexeme) {
+                format!("{} {}", operator.lexeme, right)
+            } else {
+                format!("{}{}", operator.lexeme, right)
+            }
+        }
+        Expr::Variable { name, .. } => name.lexeme.clone(),
+    }
+}
+
+/// Renders a statement back into Lox source on a single line, like
+/// `source_expr`. A `for` loop comes back as the block and `while` loop it
+/// is parsed into, except that its increment stays in a `for` header.
+pub fn source_stmt(stmt: &Stmt) -> String {
+    match stmt {
+        Stmt::Block { statements } => source_block(statements),
+        Stmt::Class {
+            name,
+            superclass,
+            methods,
+            ..
+        } => {
+            let superclass = superclass
+                .as_deref()
+                .map_or(String::new(), |s| format!(" < {}", source_expr(s)));
+            let methods: Vec<String> = methods
+                .iter()
+                .map(|method| match method {
+                    Stmt::Function {
+                        name,
+                        params,
+                        signature,
+                        body,
+                        ..
+                    } => format!(
+                        "{}{} {}",
+                        name.lexeme,
+                        source_params(params, signature),
+                        source_block(body)
+                    ),
+                    method => source_stmt(method),
+                })
+                .collect();
+            format!(
+                "class {}{} {{ {} }}",
+                name.lexeme,
+                superclass,
+                methods.join(" ")
+            )
+        }
+        Stmt::Break { label, .. } => match label {
+            Some(label) => format!("break {};", label.lexeme),
+            None => "break;".to_string(),
+        },
+        Stmt::Continue { label, .. } => match label {
+            Some(label) => format!("continue {};", label.lexeme),
+            None => "continue;".to_string(),
+        },
+        Stmt::Expression { expression } => format!("{};", source_expr(expression)),
+        Stmt::Function {
+            name,
+            params,
+            signature,
+            body,
+            ..
+        } => format!(
+            "fun {}{} {}",
+            name.lexeme,
+            source_params(params, signature),
+            source_block(body)
+        ),
+        Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+            ..
+        } => match else_branch {
+            Some(else_branch) => format!(
+                "if ({}) {} else {}",
+                source_expr(condition),
+                source_stmt(then_branch),
+                source_stmt(else_branch)
+            ),
+            None => format!(
+                "if ({}) {}",
+                source_expr(condition),
+                source_stmt(then_branch)
+            ),
+        },
+        Stmt::Print { expression, .. } => format!("print {};", source_expr(expression)),
+        Stmt::Return { value, .. } => match value {
+            Some(value) => format!("return {};", source_expr(value)),
+            None => "return;".to_string(),
+        },
+        Stmt::Throw { value, .. } => format!("throw {};", source_expr(value)),
+        Stmt::Try {
+            body,
+            name,
+            handler,
+        } => format!(
+            "try {} catch ({}) {}",
+            source_block(body),
+            name.lexeme,
+            source_block(handler)
+        ),
+        Stmt::Var {
+            name,
+            annotation,
+            initializer,
+        } => {
+            let name = match annotation {
+                Some(annotation) => format!("{}: {}", name.lexeme, annotation.lexeme),
+                None => name.lexeme.clone(),
+            };
+            match initializer {
+                Some(initializer) => format!("var {} = {};", name, source_expr(initializer)),
+                None => format!("var {};", name),
+            }
+        }
+        Stmt::While {
+            condition,
+            body,
+            increment,
+            label,
+            ..
+        } => {
+            let label = label
+                .as_deref()
+                .map_or(String::new(), |label| format!("{}: ", label.lexeme));
+            match increment {
+                Some(increment) => format!(
+                    "{}for (; {}; {}) {}",
+                    label,
+                    source_expr(condition),
+                    source_expr(increment),
+                    source_stmt(body)
+                ),
+                None => format!(
+                    "{}while ({}) {}",
+                    label,
+                    source_expr(condition),
+                    source_stmt(body)
+                ),
+            }
+        }
+    }
+}
+
+fn source_list(exprs: &[Expr]) -> String {
+    let exprs: Vec<String> = exprs.iter().map(source_expr).collect();
+    exprs.join(", ")
+}
+
+fn source_block(statements: &[Stmt]) -> String {
+    if statements.is_empty() {
+        return "{}".to_string();
+    }
+    let statements: Vec<String> = statements.iter().map(source_stmt).collect();
+    format!("{{ {} }}", statements.join(" "))
+}
+
+fn source_params(params: &[Token], signature: &Signature) -> String {
+    let names: Vec<String> = params
+        .iter()
+        .enumerate()
+        .map(
+            |(i, p)| match signature.params.get(i).and_then(Option::as_ref) {
+                Some(annotation) => format!("{}: {}", p.lexeme, annotation.lexeme),
+                None => p.lexeme.clone(),
+            },
+        )
+        .collect();
+    match &signature.returns {
+        Some(returns) => format!("({}) -> {}", names.join(", "), returns.lexeme),
+        None => format!("({})", names.join(", ")),
+    }
+}
+
+/// `=` for a plain assignment, or e.g. `+=` for a compound one.
+fn assignment_name(operator: Option<&Token>) -> String {
+    match operator {
+        Some(operator) => format!("{}=", operator.lexeme),
+        None => "=".to_string(),
+    }
+}
+
+fn print_literal(value: &Literal) -> String {
+    match value {
+        Literal::String(s) => format!("{:?}", s),
+        value => value.to_string(),
+    }
+}
+
+/// `name`, or `name:type` if it has a type annotation.
+fn annotated(name: &Token, annotation: Option<&Token>) -> String {
+    match annotation {
+        Some(annotation) => format!("{}:{}", name.lexeme, annotation.lexeme),
+        None => name.lexeme.clone(),
+    }
+}
+
+fn print_params(params: &[Token], signature: &Signature) -> String {
+    let names: Vec<String> = params
+        .iter()
+        .enumerate()
+        .map(|(i, p)| annotated(p, signature.params.get(i).and_then(Option::as_ref)))
+        .collect();
+    match &signature.returns {
+        Some(returns) => format!("({})->{}", names.join(" "), returns.lexeme),
+        None => format!("({})", names.join(" ")),
+    }
+}
+
+fn parenthesize(name: &str, parts: impl IntoIterator<Item = String>) -> String {
+    let mut out = format!("({}", name);
+    for part in parts {
+        out.push(' ');
+        out.push_str(&part);
+    }
+    out.push(')');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn print(source: &str) -> Vec<String> {
+        let mut scanner = Scanner::new(source);
+        let tokens = scanner.scan_tokens().unwrap();
+        let statements = Parser::new(tokens).parse().unwrap();
+        statements.iter().map(print_stmt).collect()
+    }
+
+    #[test]
+    fn test_print_expressions() {
+        assert_eq!(
+            print("-1 * (2 + 3); a = b or !c; f(x, ...ys).name; xs[0][i] *= 2; p.x = 1;"),
+            vec![
+                "(; (* (- 1) (group (+ 2 3))))",
+                "(; (= a (or b (! c))))",
+                "(; (. (call f x (... ys)) name))",
+                "(; (*= (index (index xs 0) i) 2))",
+                "(; (= (. p x) 1))",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_print_statements() {
+        assert_eq!(
+            print(
+                "fun add(a, b) { return a + b; }
+                 if (x) print \"yes\"; else { var y = nil; }
+                 outer: for (var i = 0; i < 2; i = i + 1) break outer;"
+            ),
+            vec![
+                "(fun add (a b) (return (+ a b)))",
+                "(if x (print \"yes\") (block (var y nil)))",
+                "(block (var i 0) (while outer: (< i 2) (break outer) (= i (+ i 1))))",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_source_reparses_to_the_same_tree() {
+        let source = "var a: number = -(-1) * (2 + 3); a = b or !c ? [1, \"s\"] : ({\"k\": nil});
+             f(x, ...ys).name; xs[0][i] *= 2; p.x = 1; i++; - -i;
+             var g = fun (a: number, b) -> number { return a - b; };
+             fun add(a, b) { return a + b; }
+             class B < A { init() { super.init(); this.x = 1; } m() {} }
+             if (x) print \"yes\"; else { var y; }
+             outer: for (var i = 0; i < 2; i = i + 1) { if (i) continue outer; break; }
+             while (true) try { throw 1; } catch (e) { return; }";
+        let trees = print(source);
+        let mut scanner = Scanner::new(source);
+        let tokens = scanner.scan_tokens().unwrap();
+        let statements = Parser::new(tokens).parse().unwrap();
+        let printed: Vec<String> = statements.iter().map(source_stmt).collect();
+        assert_eq!(print(&printed.join("\n")), trees);
+        assert_eq!(printed[0], "var a: number = -(-1) * (2 + 3);");
+    }
+}
@@ -0,0 +1,79 @@
+//! Single-file executables: `lox bundle` appends a script to a copy of this
+//! interpreter, and on startup the interpreter checks whether a script was
+//! appended to it and runs that instead of parsing its arguments.
+//!
+//! The script is followed by a trailer of its length in bytes, as a little
+//! endian u64, and `MAGIC`.
+
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"LOXBUNDL";
+const TRAILER_LEN: u64 = 16;
+
+/// The script appended to `binary`, if any.
+fn read_embedded(binary: &mut (impl Read + Seek)) -> io::Result<Option<String>> {
+    let len = binary.seek(SeekFrom::End(0))?;
+    if len < TRAILER_LEN {
+        return Ok(None);
+    }
+    binary.seek(SeekFrom::End(-(TRAILER_LEN as i64)))?;
+    let mut trailer = [0; TRAILER_LEN as usize];
+    binary.read_exact(&mut trailer)?;
+    if &trailer[8..] != MAGIC {
+        return Ok(None);
+    }
+
+    let script_len = u64::from_le_bytes(trailer[..8].try_into().expect("8 bytes"));
+    let Some(start) = (len - TRAILER_LEN).checked_sub(script_len) else {
+        return Ok(None);
+    };
+    binary.seek(SeekFrom::Start(start))?;
+    let mut source = String::new();
+    binary.take(script_len).read_to_string(&mut source)?;
+    Ok(Some(source))
+}
+
+/// The script bundled into the running executable, if it is a bundle.
+pub fn embedded_script() -> Option<String> {
+    let mut binary = File::open(std::env::current_exe().ok()?).ok()?;
+    read_embedded(&mut binary).ok()?
+}
+
+/// Appends `source` and its trailer to `binary`.
+fn append(binary: &mut Vec<u8>, source: &str) {
+    binary.extend_from_slice(source.as_bytes());
+    binary.extend_from_slice(&(source.len() as u64).to_le_bytes());
+    binary.extend_from_slice(MAGIC);
+}
+
+/// Writes a copy of the running interpreter to `output` that runs `source`.
+pub fn bundle(source: &str, output: &Path) -> io::Result<()> {
+    let mut binary = fs::read(std::env::current_exe()?)?;
+    append(&mut binary, source);
+    fs::write(output, binary)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(output, fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_embedded_script_round_trips() {
+        let mut binary = b"\x7fELF interpreter".to_vec();
+        assert!(read_embedded(&mut Cursor::new(&binary)).unwrap().is_none());
+
+        append(&mut binary, "print \"hi\";");
+        let source = read_embedded(&mut Cursor::new(&binary)).unwrap();
+        assert_eq!(source.as_deref(), Some("print \"hi\";"));
+    }
+}
@@ -0,0 +1,195 @@
+//! Conversions between Rust values and Lox values, for embedders and
+//! natives: `Literal::from` to hand a value to Lox, `FromLox` to take one
+//! back, and `IntoNative` to turn a closure with typed arguments into a
+//! native.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::interpreter::Interpreter;
+use crate::lox_error::{LoxError, NativeError};
+use crate::messages;
+use crate::token::{Literal, NativeClosure};
+use crate::value;
+
+impl From<f64> for Literal {
+    fn from(n: f64) -> Self {
+        Literal::Number(n)
+    }
+}
+
+impl From<bool> for Literal {
+    fn from(b: bool) -> Self {
+        Literal::Bool(b)
+    }
+}
+
+impl From<String> for Literal {
+    fn from(text: String) -> Self {
+        Literal::String(text)
+    }
+}
+
+impl From<&str> for Literal {
+    fn from(text: &str) -> Self {
+        Literal::String(text.to_string())
+    }
+}
+
+impl From<()> for Literal {
+    fn from(_: ()) -> Self {
+        Literal::None
+    }
+}
+
+impl<T: Into<Literal>> From<Vec<T>> for Literal {
+    fn from(values: Vec<T>) -> Self {
+        let values = values.into_iter().map(Into::into).collect();
+        Literal::List(Rc::new(RefCell::new(values)))
+    }
+}
+
+/// `None` becomes nil.
+impl<T: Into<Literal>> From<Option<T>> for Literal {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Literal::None, Into::into)
+    }
+}
+
+/// A Rust value a Lox value can be converted to.
+pub trait FromLox: Sized {
+    /// Fails with an error naming what was expected if `value` has the
+    /// wrong type.
+    fn from_lox(value: &Literal) -> Result<Self, NativeError>;
+}
+
+fn expected(what: &str, value: &Literal) -> NativeError {
+    NativeError::from_message(messages::WRONG_TYPE, &[&what, &value::type_name(value)])
+}
+
+impl FromLox for Literal {
+    fn from_lox(value: &Literal) -> Result<Self, NativeError> {
+        Ok(value.clone())
+    }
+}
+
+impl FromLox for f64 {
+    fn from_lox(value: &Literal) -> Result<Self, NativeError> {
+        match value {
+            Literal::Number(n) => Ok(*n),
+            _ => Err(expected("number", value)),
+        }
+    }
+}
+
+impl FromLox for bool {
+    fn from_lox(value: &Literal) -> Result<Self, NativeError> {
+        match value {
+            Literal::Bool(b) => Ok(*b),
+            _ => Err(expected("bool", value)),
+        }
+    }
+}
+
+impl FromLox for String {
+    fn from_lox(value: &Literal) -> Result<Self, NativeError> {
+        match value {
+            Literal::String(text) => Ok(text.clone()),
+            _ => Err(expected("string", value)),
+        }
+    }
+}
+
+impl<T: FromLox> FromLox for Vec<T> {
+    fn from_lox(value: &Literal) -> Result<Self, NativeError> {
+        match value {
+            Literal::List(values) => values.borrow().iter().map(T::from_lox).collect(),
+            _ => Err(expected("list", value)),
+        }
+    }
+}
+
+/// nil becomes `None`.
+impl<T: FromLox> FromLox for Option<T> {
+    fn from_lox(value: &Literal) -> Result<Self, NativeError> {
+        match value {
+            Literal::None => Ok(None),
+            value => T::from_lox(value).map(Some),
+        }
+    }
+}
+
+/// A closure that can be registered as a native with
+/// `Interpreter::register_fn`. `Args` are its argument types, which each
+/// implement `FromLox`; its result converts into a `Literal`.
+pub trait IntoNative<Args> {
+    fn arity(&self) -> usize;
+    fn into_native(self) -> NativeClosure;
+}
+
+macro_rules! into_native {
+    ($($arg:ident),*) => {
+        impl<F, R, $($arg),*> IntoNative<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R + 'static,
+            R: Into<Literal>,
+            $($arg: FromLox),*
+        {
+            fn arity(&self) -> usize {
+                let names: &[&str] = &[$(stringify!($arg)),*];
+                names.len()
+            }
+
+            #[allow(non_snake_case, unused_variables, unused_mut)]
+            fn into_native(self) -> NativeClosure {
+                Rc::new(move |_: &mut Interpreter, arguments: &[Literal]| {
+                    let mut arguments = arguments.iter();
+                    $(let $arg = $arg::from_lox(arguments.next().expect("Arity was checked"))?;)*
+                    Ok::<_, LoxError>(self($($arg),*).into())
+                })
+            }
+        }
+    };
+}
+
+into_native!();
+into_native!(A);
+into_native!(A, B);
+into_native!(A, B, C);
+into_native!(A, B, C, D);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let list = Literal::from(vec![1.0, 2.0]);
+        assert_eq!(Vec::<f64>::from_lox(&list).unwrap(), vec![1.0, 2.0]);
+        assert_eq!(Option::<String>::from_lox(&Literal::None).unwrap(), None);
+        assert_eq!(
+            String::from_lox(&Literal::from("text")).unwrap(),
+            "text".to_string()
+        );
+        assert!(matches!(Literal::from(None::<f64>), Literal::None));
+
+        let error = f64::from_lox(&Literal::from(true)).unwrap_err();
+        assert_eq!(error.to_string(), "Expected number but got bool.");
+    }
+
+    #[test]
+    fn test_register_fn() {
+        let mut interpreter = Interpreter::new();
+        interpreter.register_fn("max", |x: f64, y: f64| x.max(y));
+        let greeting = String::from("Hello");
+        interpreter.register_fn("greet", move |name: String| {
+            format!("{}, {}!", greeting, name)
+        });
+
+        let value = interpreter.run("max(3, 4);").unwrap();
+        assert!(value::lox_equals(&Literal::Number(4.0), &value));
+        let value = interpreter.run("greet(\"Lox\");").unwrap();
+        assert!(value::lox_equals(&Literal::from("Hello, Lox!"), &value));
+        assert!(interpreter.run("max(3, \"4\");").is_err());
+    }
+}
@@ -0,0 +1,63 @@
+//! Reports internal panics as bugs in the interpreter, pointing at the part
+//! of the script that was being parsed or run, instead of a bare backtrace
+//! that reads as if the script were at fault.
+
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::cell::{Cell, RefCell};
+use std::panic::{self, PanicHookInfo};
+
+thread_local! {
+    static SOURCE: RefCell<String> = const { RefCell::new(String::new()) };
+    static LINE: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// Remembers the script being compiled, so a crash can quote it.
+pub fn set_source(source: &str) {
+    SOURCE.with_borrow_mut(|current| {
+        current.clear();
+        current.push_str(source);
+    });
+    LINE.set(None);
+}
+
+/// Records the line of the script currently being parsed or executed.
+pub fn set_line(line: Option<usize>) {
+    LINE.set(line);
+}
+
+fn report(info: &PanicHookInfo) {
+    let message = match info.payload().downcast_ref::<&str>() {
+        Some(message) => message.to_string(),
+        None => match info.payload().downcast_ref::<String>() {
+            Some(message) => message.clone(),
+            None => "unknown panic".to_string(),
+        },
+    };
+    let location = info
+        .location()
+        .map(|l| format!(" at {}:{}", l.file(), l.line()))
+        .unwrap_or_default();
+
+    eprintln!("Internal error{}: {}", location, message);
+    if let Some(line) = LINE.get() {
+        eprintln!("while processing line {} of the script:", line);
+        SOURCE.with_borrow(|source| {
+            if let Some(text) = source.lines().nth(line.saturating_sub(1)) {
+                eprintln!("    | {}", text);
+            }
+        });
+    }
+    // Still honors RUST_BACKTRACE, for whoever debugs the report
+    let backtrace = Backtrace::capture();
+    if backtrace.status() == BacktraceStatus::Captured {
+        eprintln!("{}", backtrace);
+    }
+    eprintln!(
+        "This is a bug in lox, not in your script. Please report it along with the script that triggered it."
+    );
+}
+
+/// Replaces the default panic message with `report`.
+pub fn install_hook() {
+    panic::set_hook(Box::new(report));
+}
@@ -0,0 +1,137 @@
+//! Errors and warnings in a form tools can read, for `--error-format=json`.
+//! Each is printed to stderr as one line of JSON.
+
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use serde::Serialize;
+
+use crate::lint::{Level, Warning};
+use crate::lox_error::{LoxError, ParserError, RuntimeError, ScannerError};
+use crate::token::Span;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ErrorFormat {
+    /// Messages for people, with the source they point at.
+    #[default]
+    Human,
+    /// A JSON `Diagnostic` per line.
+    Json,
+}
+
+impl FromStr for ErrorFormat {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "human" => Ok(ErrorFormat::Human),
+            "json" => Ok(ErrorFormat::Json),
+            _ => Err(format!(
+                "Unknown error format '{}'. Available: human, json.",
+                name
+            )),
+        }
+    }
+}
+
+static FORMAT: OnceLock<ErrorFormat> = OnceLock::new();
+
+/// Sets how errors and warnings are printed. Only the first call has an
+/// effect.
+pub fn set_format(format: ErrorFormat) {
+    let _ = FORMAT.set(format);
+}
+
+pub fn format() -> ErrorFormat {
+    FORMAT.get().copied().unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// Where in the source it is, if anywhere.
+    pub span: Option<Span>,
+    /// The message's code, like `R001`, or a lint's name.
+    pub code: Option<String>,
+}
+
+impl Diagnostic {
+    fn error(message: String, span: Option<Span>, code: Option<&str>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message,
+            span,
+            code: code.map(str::to_string),
+        }
+    }
+
+    /// Every diagnostic in `error`: one per syntax error, or just one.
+    pub fn all(error: &LoxError) -> Vec<Diagnostic> {
+        match error {
+            LoxError::Parser(errors) => errors.iter().map(Diagnostic::from).collect(),
+            LoxError::Runtime(error) => vec![error.into()],
+            LoxError::Scanner(error) => vec![error.into()],
+            LoxError::Io(errors) => errors
+                .iter()
+                .map(|error| Diagnostic::error(error.to_string(), None, Some(error.code())))
+                .collect(),
+            _ => vec![Diagnostic::error(error.to_string(), None, None)],
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Diagnostics are always valid JSON")
+    }
+}
+
+impl From<&ScannerError> for Diagnostic {
+    fn from(error: &ScannerError) -> Self {
+        Diagnostic::error(
+            error.message().to_string(),
+            Some(error.span()),
+            Some(error.code()),
+        )
+    }
+}
+
+impl From<&ParserError> for Diagnostic {
+    fn from(error: &ParserError) -> Self {
+        Diagnostic::error(
+            error.message().to_string(),
+            Some(error.span()),
+            Some(error.code()),
+        )
+    }
+}
+
+impl From<&RuntimeError> for Diagnostic {
+    fn from(error: &RuntimeError) -> Self {
+        Diagnostic::error(
+            error.message().to_string(),
+            Some(error.span()),
+            error.code(),
+        )
+    }
+}
+
+impl From<&Warning> for Diagnostic {
+    fn from(warning: &Warning) -> Self {
+        Self {
+            severity: match warning.level {
+                Level::Deny => Severity::Error,
+                _ => Severity::Warning,
+            },
+            message: warning.message().to_string(),
+            span: Some(Span::line(warning.line)),
+            code: Some(warning.lint.name().to_string()),
+        }
+    }
+}
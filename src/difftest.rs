@@ -0,0 +1,136 @@
+use std::error::Error;
+use std::fs;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+/// What one implementation did with a program.
+struct Outcome {
+    stdout: String,
+    exit: Option<i32>,
+}
+
+impl Outcome {
+    fn of(output: Output) -> Self {
+        Self {
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            exit: output.status.code(),
+        }
+    }
+}
+
+fn collect_programs(path: &Path, programs: &mut Vec<PathBuf>) -> Result<(), Box<dyn Error>> {
+    if !path.is_dir() {
+        programs.push(path.to_path_buf());
+        return Ok(());
+    }
+
+    let mut entries = fs::read_dir(path)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() || entry.extension().is_some_and(|ext| ext == "lox") {
+            collect_programs(&entry, programs)?;
+        }
+    }
+    Ok(())
+}
+
+fn run(binary: &Path, program: &Path) -> Result<Outcome, String> {
+    let output = Command::new(binary)
+        .arg(program)
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", binary.display(), e))?;
+    Ok(Outcome::of(output))
+}
+
+/// Describes how `ours` differs from `reference`, if it does.
+fn divergence(ours: &Outcome, reference: &Outcome) -> Option<String> {
+    let ours_lines: Vec<_> = ours.stdout.lines().collect();
+    let reference_lines: Vec<_> = reference.stdout.lines().collect();
+    let line = (0..ours_lines.len().max(reference_lines.len()))
+        .find(|&i| ours_lines.get(i) != reference_lines.get(i));
+
+    if let Some(i) = line {
+        let show =
+            |line: Option<&&str>| line.map_or("<end of output>".to_string(), |l| l.to_string());
+        return Some(format!(
+            "stdout differs at line {}\n  lox:       {}\n  reference: {}",
+            i + 1,
+            show(ours_lines.get(i)),
+            show(reference_lines.get(i))
+        ));
+    }
+
+    if ours.exit != reference.exit {
+        let show = |code: Option<i32>| code.map_or("signal".to_string(), |c| c.to_string());
+        return Some(format!(
+            "exit code differs\n  lox:       {}\n  reference: {}",
+            show(ours.exit),
+            show(reference.exit)
+        ));
+    }
+    None
+}
+
+/// Runs `program` through both implementations and describes how they
+/// differ, if they do.
+fn compare(lox: &Path, reference: &Path, program: &Path) -> Result<Option<String>, String> {
+    let ours = run(lox, program)?;
+    let theirs = run(reference, program)?;
+    Ok(divergence(&ours, &theirs))
+}
+
+/// Runs every `.lox` program under `paths` through this interpreter and
+/// `reference`, printing each program whose stdout or exit code differs.
+/// Programs run on `jobs` threads at once, but are reported in order.
+/// Returns whether all of them agreed.
+pub fn run_corpus(
+    reference: &Path,
+    paths: &[PathBuf],
+    jobs: NonZeroUsize,
+) -> Result<bool, Box<dyn Error>> {
+    let lox = std::env::current_exe()?;
+    let cwd = std::env::current_dir()?;
+
+    let mut programs = Vec::new();
+    for path in paths {
+        collect_programs(path, &mut programs)?;
+    }
+
+    // Each worker takes the next program nobody has started on yet
+    let next = AtomicUsize::new(0);
+    let mut results = thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs.get().min(programs.len()))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+                    while let Some(program) = programs.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        results.push((program, compare(&lox, reference, program)));
+                    }
+                    results
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("Difftest worker panicked"))
+            .collect::<Vec<_>>()
+    });
+    results.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut diverged = 0;
+    for (program, result) in results {
+        if let Some(description) = result? {
+            diverged += 1;
+            let shown = program.strip_prefix(&cwd).unwrap_or(program);
+            println!("{}: {}", shown.display(), description);
+        }
+    }
+
+    println!("{} of {} programs diverged.", diverged, programs.len());
+    Ok(diverged == 0)
+}
@@ -0,0 +1,82 @@
+//! `--emit`: writes what the scanner and parser made of a script to files
+//! next to it, so every phase of a failing program can be inspected after
+//! a single run.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::token::Token;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Artifact {
+    Tokens,
+    Ast,
+}
+
+impl FromStr for Artifact {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "tokens" => Ok(Artifact::Tokens),
+            "ast" => Ok(Artifact::Ast),
+            "bytecode" => Err(
+                "There is no bytecode to emit: the syntax tree is interpreted \
+                               directly."
+                    .to_string(),
+            ),
+            _ => Err(format!(
+                "Unknown artifact '{}'. Available: tokens, ast.",
+                name
+            )),
+        }
+    }
+}
+
+impl Artifact {
+    /// Where the artifact of `script` is written, e.g. `main.tokens` for
+    /// `main.lox`.
+    pub fn path(self, script: &Path) -> PathBuf {
+        let extension = match self {
+            Artifact::Tokens => "tokens",
+            Artifact::Ast => "ast",
+        };
+        script.with_extension(extension)
+    }
+}
+
+/// One token per line, with the line it was scanned from.
+pub fn tokens(tokens: &[Token]) -> String {
+    tokens
+        .iter()
+        .map(|token| format!("{:>4} {:?}\n", token.line, token))
+        .collect()
+}
+
+/// Writes `contents` to `path`, reporting rather than failing the run if
+/// it can't.
+pub fn write(path: &Path, contents: &str) {
+    if let Err(e) = fs::write(path, contents) {
+        eprintln!("Failed to write {}: {}", path.display(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::Scanner;
+
+    #[test]
+    fn test_tokens_are_listed_by_line() {
+        let mut scanner = Scanner::new("var x = 1;\nprint x;");
+        let dump = tokens(scanner.scan_tokens().unwrap());
+        let lines: Vec<_> = dump.lines().collect();
+        assert_eq!(lines[0], "   1 Var var None");
+        assert_eq!(lines[5], "   2 Print print None");
+        assert_eq!(
+            Artifact::Tokens.path(Path::new("src/main.lox")),
+            PathBuf::from("src/main.tokens")
+        );
+    }
+}
@@ -1,4 +1,6 @@
 use crate::lox_error::{LoxError, RuntimeError};
+use crate::messages;
+use crate::native_functions::{lookup_native, native_names};
 use crate::token::{Literal, Token};
 use std::cell::RefCell;
 use std::collections::HashMap;
@@ -23,33 +25,38 @@ impl EnvironmentValues {
         self.values.insert(name.lexeme.clone(), value.clone());
     }
 
-    pub fn assign(&mut self, name: &Token, value: &Literal) -> Result<(), LoxError> {
+    /// Returns false if `name` isn't defined below the outermost scope,
+    /// which holds the natives.
+    pub fn assign(&mut self, name: &Token, value: &Literal) -> bool {
+        let Some(enclosing) = &self.enclosing else {
+            return false;
+        };
         if self.values.contains_key(&name.lexeme) {
             self.values.insert(name.lexeme.clone(), value.clone());
-            Ok(())
+            true
         } else {
-            match &mut self.enclosing {
-                Some(enclosing) => enclosing.borrow_mut().assign(name, value),
-                _ => {
-                    let error_msg = format!("Undefined variable '{}'.", name.lexeme);
-                    Err(RuntimeError::new(name, &error_msg).into())
-                }
-            }
+            enclosing.borrow_mut().assign(name, value)
         }
     }
 
-    pub fn get(&self, name: &Token) -> Result<Literal, LoxError> {
+    pub fn get(&self, name: &Token) -> Option<Literal> {
         match self.values.get(&name.lexeme) {
-            Some(literal) => Ok(literal.clone()),
+            Some(literal) => Some(literal.clone()),
             None => match &self.enclosing {
                 Some(enclosing) => enclosing.borrow().get(name),
-                _ => {
-                    let error_msg = format!("Undefined variable '{}'.", name.lexeme);
-                    Err(RuntimeError::new(name, &error_msg).into())
-                }
+                None => lookup_native(&name.lexeme),
             },
         }
     }
+
+    /// Calls `f` with every name visible from this scope.
+    fn for_each_name(&self, f: &mut impl FnMut(&str)) {
+        self.values.keys().for_each(|name| f(name));
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().for_each_name(f),
+            None => native_names().for_each(f),
+        }
+    }
 }
 
 #[derive(Default)]
@@ -82,17 +89,209 @@ impl Environment {
         self.head.borrow_mut().define(name, value)
     }
 
+    /// Assigns `name` in the nearest scope defining it. A name only the
+    /// outermost scope or the built-in natives define is shadowed in this
+    /// scope instead, so the natives stay as they were.
     pub fn assign(&mut self, name: &Token, value: &Literal) -> Result<(), LoxError> {
-        self.head.borrow_mut().assign(name, value)
+        if self.head.borrow_mut().assign(name, value) {
+            return Ok(());
+        }
+        if self.head.borrow().get(name).is_none() {
+            return Err(self.undefined(name));
+        }
+        self.define(name, value);
+        Ok(())
     }
 
     pub fn get(&self, name: &Token) -> Result<Literal, LoxError> {
-        self.head.borrow().get(name)
+        let value = self.head.borrow().get(name);
+        value.ok_or_else(|| self.undefined(name))
+    }
+
+    /// The scope `distance` enclosing links out from this one.
+    fn ancestor(&self, distance: usize) -> Rc<RefCell<EnvironmentValues>> {
+        let mut values = self.head();
+        for _ in 0..distance {
+            let enclosing = values.borrow().enclosing.clone();
+            values = enclosing.expect("Resolved scope exists");
+        }
+        values
+    }
+
+    /// Reads `name` from exactly the scope `distance` links out, without
+    /// looking further.
+    pub fn get_at(&self, distance: usize, name: &Token) -> Result<Literal, LoxError> {
+        let value = self
+            .ancestor(distance)
+            .borrow()
+            .values
+            .get(&name.lexeme)
+            .cloned();
+        value.ok_or_else(|| self.undefined(name))
+    }
+
+    /// Assigns `name` in exactly the scope `distance` links out.
+    pub fn assign_at(
+        &mut self,
+        distance: usize,
+        name: &Token,
+        value: &Literal,
+    ) -> Result<(), LoxError> {
+        let scope = self.ancestor(distance);
+        let mut scope = scope.borrow_mut();
+        match scope.values.get_mut(&name.lexeme) {
+            Some(slot) => {
+                *slot = value.clone();
+                Ok(())
+            }
+            None => {
+                drop(scope);
+                Err(self.undefined(name))
+            }
+        }
+    }
+
+    /// An "Undefined variable" error, suggesting the closest visible name.
+    pub fn undefined(&self, name: &Token) -> LoxError {
+        let mut best: Option<(usize, String)> = None;
+        self.head.borrow().for_each_name(&mut |candidate| {
+            let distance = edit_distance(&name.lexeme, candidate);
+            let close = distance <= (name.lexeme.chars().count() / 3).max(1);
+            let better = match &best {
+                Some((best_distance, best_name)) => {
+                    (distance, candidate) < (*best_distance, best_name.as_str())
+                }
+                None => true,
+            };
+            if close && better {
+                best = Some((distance, candidate.to_string()));
+            }
+        });
+
+        match best {
+            Some((_, suggestion)) => RuntimeError::from_message(
+                name,
+                messages::UNDEFINED_VARIABLE_SUGGESTION,
+                &[&name.lexeme, &suggestion],
+            ),
+            None => RuntimeError::from_message(name, messages::UNDEFINED_VARIABLE, &[&name.lexeme]),
+        }
+        .into()
+    }
+
+    /// The names bound in this scope itself and their values, by name.
+    pub fn locals(&self) -> Vec<(String, Literal)> {
+        let mut locals: Vec<_> = self
+            .head
+            .borrow()
+            .values
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        locals.sort_by(|a, b| a.0.cmp(&b.0));
+        locals
+    }
+
+    /// Every name visible from this scope, natives included, sorted.
+    pub fn names(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        self.head
+            .borrow()
+            .for_each_name(&mut |name| names.push(name.to_string()));
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// The scope this one is nested in, or `None` for the outermost one.
+    pub fn enclosing(&self) -> Option<Environment> {
+        let enclosing = self.head.borrow().enclosing.clone();
+        enclosing.map(|head| Environment { head })
+    }
+
+    /// This scope and every scope enclosing it, innermost first.
+    pub fn scopes(&self) -> impl Iterator<Item = Environment> {
+        std::iter::successors(Some(self.clone()), Environment::enclosing)
+    }
+
+    /// Identifies this scope: clones of an `Environment` share an address.
+    pub fn address(&self) -> usize {
+        Rc::as_ptr(&self.head) as usize
+    }
+
+    /// Whether `name` is bound in this scope itself, ignoring enclosing ones.
+    pub fn defines(&self, name: &Token) -> bool {
+        self.head.borrow().values.contains_key(&name.lexeme)
     }
 }
 
+/// The edit distance between `a` and `b`, counting insertions, deletions,
+/// substitutions and swaps of adjacent characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    d[0] = (0..=b.len()).collect();
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
 impl Clone for Environment {
     fn clone(&self) -> Environment {
         Environment { head: self.head() }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::token_type::TokenType;
+
+    fn name(lexeme: &str) -> Token {
+        Token::new(TokenType::Identifier, lexeme, None, 1)
+    }
+
+    #[test]
+    fn test_get_at_and_assign_at_skip_other_scopes() {
+        let mut outer = Environment::new();
+        outer.define(&name("x"), &Literal::Number(1.0));
+        let mut inner = Environment::from_env(&outer);
+        inner.define(&name("x"), &Literal::Number(2.0));
+
+        inner
+            .assign_at(1, &name("x"), &Literal::Number(3.0))
+            .unwrap();
+        assert!(matches!(inner.get_at(0, &name("x")), Ok(Literal::Number(n)) if n == 2.0));
+        assert!(matches!(inner.get_at(1, &name("x")), Ok(Literal::Number(n)) if n == 3.0));
+        assert!(inner.get_at(0, &name("clock")).is_err());
+        assert!(inner.assign_at(0, &name("y"), &Literal::None).is_err());
+    }
+
+    #[test]
+    fn test_scopes_lists_innermost_first() {
+        let mut outer = Environment::new();
+        outer.define(&name("x"), &Literal::Number(1.0));
+        let mut inner = Environment::from_env(&outer);
+        inner.define(&name("y"), &Literal::Number(2.0));
+
+        let names: Vec<Vec<String>> = inner
+            .scopes()
+            .map(|scope| scope.locals().into_iter().map(|(n, _)| n).collect())
+            .collect();
+        assert_eq!(names, [vec!["y"], vec!["x"]]);
+    }
+}
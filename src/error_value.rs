@@ -0,0 +1,55 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::interpreter::Interpreter;
+use crate::lox_error::{LoxError, RuntimeError};
+use crate::token::{Foreign, Literal};
+
+/// A runtime error caught by `try`/`catch`. Scripts read its `message`,
+/// `line`, `stackTrace` and `payload` fields, and can `throw` it again.
+pub struct ErrorValue {
+    error: RuntimeError,
+}
+
+impl ErrorValue {
+    pub fn new(error: RuntimeError) -> Self {
+        Self { error }
+    }
+}
+
+impl Foreign for ErrorValue {
+    fn type_name(&self) -> &'static str {
+        "error"
+    }
+
+    fn method_arity(&self, _method: &str) -> Option<usize> {
+        None
+    }
+
+    fn call_method(
+        &mut self,
+        _interpreter: &mut Interpreter,
+        method: &str,
+        _arguments: &[Literal],
+    ) -> Result<Literal, LoxError> {
+        unreachable!("Errors have no method '{}'", method)
+    }
+
+    fn field(&self, name: &str) -> Option<Literal> {
+        match name {
+            "message" => Some(Literal::String(self.error.message().to_string())),
+            "line" => Some(Literal::Number(self.error.line() as f64)),
+            "stackTrace" => {
+                let frames = self.error.trace().iter();
+                let frames = frames.map(|frame| Literal::String(frame.to_string()));
+                Some(Literal::List(Rc::new(RefCell::new(frames.collect()))))
+            }
+            "payload" => Some(self.error.payload().clone()),
+            _ => None,
+        }
+    }
+
+    fn as_error(&self) -> Option<&RuntimeError> {
+        Some(&self.error)
+    }
+}
@@ -0,0 +1,136 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+
+use crate::interpreter::Interpreter;
+use crate::lox_error::{LoxError, NativeError};
+use crate::messages::{self, Message};
+use crate::token::{Foreign, Literal};
+
+enum State {
+    Reading(BufReader<File>),
+    Writing(BufWriter<File>),
+    /// Stands in for a file while replaying, where reads come from the
+    /// trace and writes are dropped.
+    Replayed,
+    Closed,
+}
+
+/// A file opened by the `open` native, read line by line or written to
+/// through a buffer.
+pub struct FileHandle {
+    state: State,
+}
+
+fn io_error(message: Message, error: std::io::Error) -> NativeError {
+    NativeError::from_message(message, &[&error])
+}
+
+impl FileHandle {
+    pub fn open(path: &str, mode: &str) -> Result<Self, NativeError> {
+        let mut options = OpenOptions::new();
+        match mode {
+            "r" => options.read(true),
+            "w" => options.write(true).create(true).truncate(true),
+            "a" => options.append(true).create(true),
+            _ => {
+                return Err(NativeError::from_message(
+                    messages::UNKNOWN_FILE_MODE,
+                    &[&mode],
+                ))
+            }
+        };
+
+        let file = options
+            .open(path)
+            .map_err(|e| NativeError::from_message(messages::CANNOT_OPEN_FILE, &[&path, &e]))?;
+        let state = match mode {
+            "r" => State::Reading(BufReader::new(file)),
+            _ => State::Writing(BufWriter::new(file)),
+        };
+        Ok(Self { state })
+    }
+
+    pub fn replayed() -> Self {
+        Self {
+            state: State::Replayed,
+        }
+    }
+
+    fn read_line(&mut self) -> Result<Literal, NativeError> {
+        let State::Reading(reader) = &mut self.state else {
+            return Err(NativeError::from_message(messages::FILE_NOT_READABLE, &[]));
+        };
+
+        let mut line = String::new();
+        let read = reader
+            .read_line(&mut line)
+            .map_err(|e| io_error(messages::CANNOT_READ_OPEN_FILE, e))?;
+        if read == 0 {
+            return Ok(Literal::None);
+        }
+
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        Ok(Literal::String(line))
+    }
+
+    fn write(&mut self, text: &Literal) -> Result<Literal, NativeError> {
+        let writer = match &mut self.state {
+            State::Writing(writer) => writer,
+            State::Replayed => return Ok(Literal::None),
+            _ => return Err(NativeError::from_message(messages::FILE_NOT_WRITABLE, &[])),
+        };
+
+        writer
+            .write_all(text.to_string().as_bytes())
+            .map_err(|e| io_error(messages::CANNOT_WRITE_FILE, e))?;
+        Ok(Literal::None)
+    }
+
+    pub fn close(&mut self) -> Result<Literal, NativeError> {
+        if let State::Writing(writer) = &mut self.state {
+            writer
+                .flush()
+                .map_err(|e| io_error(messages::CANNOT_WRITE_FILE, e))?;
+        }
+        self.state = State::Closed;
+        Ok(Literal::None)
+    }
+}
+
+impl Foreign for FileHandle {
+    fn type_name(&self) -> &'static str {
+        "file"
+    }
+
+    fn method_arity(&self, method: &str) -> Option<usize> {
+        match method {
+            "readLine" | "close" => Some(0),
+            "write" => Some(1),
+            _ => None,
+        }
+    }
+
+    fn call_method(
+        &mut self,
+        interpreter: &mut Interpreter,
+        method: &str,
+        arguments: &[Literal],
+    ) -> Result<Literal, LoxError> {
+        let result = match method {
+            "readLine" => {
+                return interpreter
+                    .tape
+                    .observe("readLine", || Ok(self.read_line()?))
+            }
+            "write" => self.write(&arguments[0]),
+            "close" => self.close(),
+            _ => unreachable!(),
+        };
+        Ok(result?)
+    }
+}
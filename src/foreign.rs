@@ -0,0 +1,145 @@
+//! How long foreign values live: weak references that don't keep a value
+//! alive, and finalizers that run as soon as the last strong reference to a
+//! value is dropped, so the host resource behind it is released right away.
+
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+use crate::interpreter::Interpreter;
+use crate::lox_error::{LoxError, RuntimeError};
+use crate::token::{Foreign, Literal};
+
+/// A reference to a foreign value that doesn't keep it alive, created by
+/// the `weak` native. `get()` returns the value, or `nil` once it is gone.
+pub struct WeakRef {
+    target: Weak<RefCell<dyn Foreign>>,
+}
+
+impl WeakRef {
+    pub fn new(target: &Rc<RefCell<dyn Foreign>>) -> Self {
+        Self {
+            target: Rc::downgrade(target),
+        }
+    }
+
+    /// The value, if something still holds a strong reference to it.
+    pub fn upgrade(&self) -> Option<Literal> {
+        self.target.upgrade().map(Literal::Foreign)
+    }
+}
+
+impl Foreign for WeakRef {
+    fn type_name(&self) -> &'static str {
+        "weak"
+    }
+
+    fn method_arity(&self, method: &str) -> Option<usize> {
+        match method {
+            "get" => Some(0),
+            _ => None,
+        }
+    }
+
+    fn call_method(
+        &mut self,
+        _interpreter: &mut Interpreter,
+        method: &str,
+        _arguments: &[Literal],
+    ) -> Result<Literal, LoxError> {
+        match method {
+            "get" => Ok(self.upgrade().unwrap_or(Literal::None)),
+            _ => unreachable!(),
+        }
+    }
+
+    fn field(&self, name: &str) -> Option<Literal> {
+        match name {
+            "alive" => Some(Literal::Bool(self.target.strong_count() > 0)),
+            _ => None,
+        }
+    }
+}
+
+type Finalizer<T> = Box<dyn FnOnce(&mut T)>;
+
+/// A foreign value that runs `finalizer` on itself when the interpreter
+/// drops the last strong reference to it. It behaves exactly like `T`
+/// otherwise.
+pub struct Finalized<T: Foreign> {
+    value: T,
+    finalizer: Option<Finalizer<T>>,
+}
+
+impl<T: Foreign> Finalized<T> {
+    pub fn new(value: T, finalizer: impl FnOnce(&mut T) + 'static) -> Self {
+        Self {
+            value,
+            finalizer: Some(Box::new(finalizer)),
+        }
+    }
+}
+
+impl<T: Foreign> Drop for Finalized<T> {
+    fn drop(&mut self) {
+        if let Some(finalizer) = self.finalizer.take() {
+            finalizer(&mut self.value);
+        }
+    }
+}
+
+impl<T: Foreign> Foreign for Finalized<T> {
+    fn type_name(&self) -> &'static str {
+        self.value.type_name()
+    }
+
+    fn method_arity(&self, method: &str) -> Option<usize> {
+        self.value.method_arity(method)
+    }
+
+    fn call_method(
+        &mut self,
+        interpreter: &mut Interpreter,
+        method: &str,
+        arguments: &[Literal],
+    ) -> Result<Literal, LoxError> {
+        self.value.call_method(interpreter, method, arguments)
+    }
+
+    fn field(&self, name: &str) -> Option<Literal> {
+        self.value.field(name)
+    }
+
+    fn as_error(&self) -> Option<&RuntimeError> {
+        self.value.as_error()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error_value::ErrorValue;
+    use crate::token::Token;
+    use crate::token_type::TokenType;
+
+    #[test]
+    fn test_finalizer_runs_when_last_reference_drops() {
+        let finalized = Rc::new(RefCell::new(false));
+        let flag = Rc::clone(&finalized);
+        let token = Token::new(TokenType::Throw, "throw", None, 1);
+        let error = ErrorValue::new(RuntimeError::new(&token, "boom"));
+        let value: Rc<RefCell<dyn Foreign>> =
+            Rc::new(RefCell::new(Finalized::new(error, move |_| {
+                *flag.borrow_mut() = true
+            })));
+
+        let weak = WeakRef::new(&value);
+        let copy = Literal::Foreign(Rc::clone(&value));
+        drop(value);
+        assert!(weak.upgrade().is_some());
+        assert!(!*finalized.borrow());
+
+        drop(copy);
+        assert!(weak.upgrade().is_none());
+        assert!(*finalized.borrow());
+    }
+}
@@ -0,0 +1,244 @@
+//! A snapshot of every value a script can still reach, for `--heap-dump`
+//! and the `heapDump` native. It lists scopes, classes, instances, lists,
+//! maps and foreign values with the references between them, and gives each
+//! one the shortest path that keeps it alive, like `globals.cache[3]`.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::rc::Rc;
+
+use serde::Serialize;
+
+use crate::environment::Environment;
+use crate::interpreter::Interpreter;
+use crate::token::{Callable, Foreign, Literal, LoxClass, LoxInstance};
+
+#[derive(Serialize)]
+pub struct Snapshot {
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+}
+
+#[derive(Serialize)]
+struct Node {
+    id: usize,
+    /// `scope`, `class`, `instance`, `list`, `map` or a foreign type's name.
+    kind: &'static str,
+    /// The name of a class, or of an instance's class.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    /// How many variables, fields, elements or entries it holds.
+    size: usize,
+    /// The shortest path from a root that keeps it alive.
+    path: String,
+}
+
+#[derive(Serialize)]
+struct Edge {
+    from: usize,
+    to: usize,
+    name: String,
+}
+
+/// A value that lives on the heap, shared by everything that refers to it.
+enum Object {
+    Scope(Environment),
+    Class(Rc<LoxClass>),
+    Instance(Rc<RefCell<LoxInstance>>),
+    List(Rc<RefCell<Vec<Literal>>>),
+    Map(Rc<RefCell<BTreeMap<String, Literal>>>),
+    Foreign(Rc<RefCell<dyn Foreign>>),
+}
+
+impl Object {
+    /// Identifies the object, so one reached along several paths is only
+    /// listed once.
+    fn address(&self) -> usize {
+        match self {
+            Object::Scope(scope) => scope.address(),
+            Object::Class(class) => Rc::as_ptr(class) as usize,
+            Object::Instance(instance) => Rc::as_ptr(instance) as usize,
+            Object::List(list) => Rc::as_ptr(list) as usize,
+            Object::Map(map) => Rc::as_ptr(map) as usize,
+            Object::Foreign(value) => Rc::as_ptr(value) as *const () as usize,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Object::Scope(_) => "scope",
+            Object::Class(_) => "class",
+            Object::Instance(_) => "instance",
+            Object::List(_) => "list",
+            Object::Map(_) => "map",
+            Object::Foreign(value) => value.borrow().type_name(),
+        }
+    }
+
+    fn name(&self) -> Option<String> {
+        match self {
+            Object::Class(class) => Some(class.name.clone()),
+            Object::Instance(instance) => Some(instance.borrow().class.name.clone()),
+            _ => None,
+        }
+    }
+
+    /// The objects this one refers to, each with the step of the path that
+    /// leads to it.
+    fn references(&self) -> (usize, Vec<(String, Object)>) {
+        let mut references = Vec::new();
+        let size = match self {
+            Object::Scope(scope) => {
+                let locals = scope.locals();
+                for (name, value) in &locals {
+                    follow(&format!(".{}", name), value, &mut references);
+                }
+                if let Some(enclosing) = scope.enclosing() {
+                    references.push((".<enclosing>".to_string(), Object::Scope(enclosing)));
+                }
+                locals.len()
+            }
+            Object::Class(class) => {
+                let mut methods: Vec<_> = class.methods.iter().collect();
+                methods.sort_by(|a, b| a.0.cmp(b.0));
+                for (name, method) in &methods {
+                    let step = format!(".{}.<closure>", name);
+                    references.push((step, Object::Scope(method.closure.clone())));
+                }
+                if let Some(superclass) = &class.superclass {
+                    let superclass = Object::Class(Rc::clone(superclass));
+                    references.push((".<superclass>".to_string(), superclass));
+                }
+                methods.len()
+            }
+            Object::Instance(instance) => {
+                let instance = instance.borrow();
+                let mut fields: Vec<_> = instance.fields().collect();
+                fields.sort_by(|a, b| a.0.cmp(b.0));
+                for (name, value) in &fields {
+                    follow(&format!(".{}", name), value, &mut references);
+                }
+                references.push((
+                    ".<class>".to_string(),
+                    Object::Class(Rc::clone(&instance.class)),
+                ));
+                fields.len()
+            }
+            Object::List(list) => {
+                let list = list.borrow();
+                for (i, value) in list.iter().enumerate() {
+                    follow(&format!("[{}]", i), value, &mut references);
+                }
+                list.len()
+            }
+            Object::Map(map) => {
+                let map = map.borrow();
+                for (key, value) in map.iter() {
+                    follow(&format!("[{:?}]", key), value, &mut references);
+                }
+                map.len()
+            }
+            Object::Foreign(_) => 0,
+        };
+        (size, references)
+    }
+}
+
+/// Adds the object `value` refers to, if any, reached by `step`.
+fn follow(step: &str, value: &Literal, references: &mut Vec<(String, Object)>) {
+    let (step, object) = match value {
+        Literal::Foreign(value) => (step.to_string(), Object::Foreign(Rc::clone(value))),
+        Literal::Instance(instance) => (step.to_string(), Object::Instance(Rc::clone(instance))),
+        Literal::List(list) => (step.to_string(), Object::List(Rc::clone(list))),
+        Literal::Map(map) => (step.to_string(), Object::Map(Rc::clone(map))),
+        Literal::Callable(Callable::Class(class)) => {
+            (step.to_string(), Object::Class(Rc::clone(class)))
+        }
+        Literal::Callable(Callable::Function(function)) => (
+            format!("{}.<closure>", step),
+            Object::Scope(function.closure.clone()),
+        ),
+        Literal::Callable(Callable::BuiltinMethod(method)) => {
+            return follow(
+                &format!("{}.<receiver>", step),
+                &method.receiver,
+                references,
+            );
+        }
+        Literal::Callable(Callable::ForeignMethod(method)) => (
+            format!("{}.<receiver>", step),
+            Object::Foreign(Rc::clone(&method.receiver)),
+        ),
+        _ => return,
+    };
+    references.push((step, object));
+}
+
+/// Walks everything reachable from the globals and, inside a call, from the
+/// scope the interpreter is running in.
+pub fn take(interpreter: &Interpreter) -> Snapshot {
+    let mut snapshot = Snapshot {
+        nodes: Vec::new(),
+        edges: Vec::new(),
+    };
+    let mut ids: HashMap<usize, usize> = HashMap::new();
+    let mut queue = VecDeque::new();
+
+    let mut roots = vec![("globals", interpreter.globals.clone())];
+    if interpreter.environment.address() != interpreter.globals.address() {
+        roots.push(("scope", interpreter.environment.clone()));
+    }
+    for (name, scope) in roots {
+        let object = Object::Scope(scope);
+        ids.insert(object.address(), snapshot.nodes.len());
+        queue.push_back((snapshot.nodes.len(), name.to_string(), object));
+        snapshot.nodes.push(placeholder());
+    }
+
+    // Breadth first, so each object's path is the shortest one to it
+    while let Some((id, path, object)) = queue.pop_front() {
+        let (size, references) = object.references();
+        for (step, target) in references {
+            let to = match ids.get(&target.address()) {
+                Some(&to) => to,
+                None => {
+                    let to = snapshot.nodes.len();
+                    ids.insert(target.address(), to);
+                    snapshot.nodes.push(placeholder());
+                    queue.push_back((to, format!("{}{}", path, step), target));
+                    to
+                }
+            };
+            snapshot.edges.push(Edge {
+                from: id,
+                to,
+                name: step,
+            });
+        }
+        snapshot.nodes[id] = Node {
+            id,
+            kind: object.kind(),
+            name: object.name(),
+            size,
+            path,
+        };
+    }
+    snapshot
+}
+
+/// Stands in for a node that has been found but not visited yet.
+fn placeholder() -> Node {
+    Node {
+        id: 0,
+        kind: "",
+        name: None,
+        size: 0,
+        path: String::new(),
+    }
+}
+
+impl Snapshot {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Snapshots are always valid JSON")
+    }
+}
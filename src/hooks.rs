@@ -0,0 +1,54 @@
+//! Hooks that tools register on an interpreter to observe a run without
+//! changing how it executes, e.g. to trace, profile or measure coverage.
+
+use crate::ast::Stmt;
+use crate::ast_printer;
+use crate::interpreter::Interpreter;
+use crate::token::{Callable, Literal};
+
+/// Called as the interpreter runs. Every method does nothing by default, so
+/// a hook only implements the events it cares about.
+pub trait Hook {
+    /// Called just before `statement` runs.
+    fn on_statement(&mut self, _statement: &Stmt, _interpreter: &Interpreter) {}
+
+    /// Called just before `callee` is called from `line`.
+    fn on_call(
+        &mut self,
+        _callee: &Callable,
+        _arguments: &[Literal],
+        _line: usize,
+        _interpreter: &Interpreter,
+    ) {
+    }
+}
+
+/// Prints every statement and call to stderr as it runs, for `--trace`.
+pub struct Tracer;
+
+impl Hook for Tracer {
+    fn on_statement(&mut self, statement: &Stmt, _interpreter: &Interpreter) {
+        // Blocks are traced through the statements in them
+        if let Stmt::Block { .. } = statement {
+            return;
+        }
+        let line = statement.line().map_or("?".to_string(), |l| l.to_string());
+        eprintln!("[line {}] {}", line, ast_printer::print_stmt(statement));
+    }
+
+    fn on_call(
+        &mut self,
+        callee: &Callable,
+        arguments: &[Literal],
+        line: usize,
+        interpreter: &Interpreter,
+    ) {
+        let arguments: Vec<_> = arguments.iter().map(|a| interpreter.stringify(a)).collect();
+        eprintln!(
+            "[line {}] call {}({})",
+            line,
+            callee.name(),
+            arguments.join(", ")
+        );
+    }
+}
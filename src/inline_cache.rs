@@ -0,0 +1,54 @@
+//! Per-site caches for method lookup. Each `object.name` in the syntax
+//! tree remembers the class it last looked the method `name` up for and
+//! the method it found, so that repeated calls on instances of that class,
+//! as in a loop, skip the method tables altogether.
+
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+use crate::stats::Stats;
+use crate::token::{Function, LoxClass};
+
+/// The class a site last looked a method up for, and the method found on
+/// it or its superclasses. Both are held weakly so the cache neither keeps
+/// classes alive nor lets a freed class's address be reused while it's
+/// cached.
+struct Entry {
+    class: Weak<LoxClass>,
+    method: Weak<Function>,
+}
+
+/// Clones share the cache, as a method body is cloned each time the method
+/// is bound but is still the same site.
+#[derive(Clone, Default)]
+pub struct InlineCache {
+    entry: Rc<RefCell<Option<Entry>>>,
+}
+
+impl InlineCache {
+    /// The method `name` of instances of `class`, from the cache if this
+    /// site last saw `class`, or else looked up and cached.
+    pub fn method(
+        &self,
+        class: &Rc<LoxClass>,
+        name: &str,
+        stats: &mut Stats,
+    ) -> Option<Rc<Function>> {
+        if let Some(entry) = &*self.entry.borrow() {
+            if Weak::as_ptr(&entry.class) == Rc::as_ptr(class) {
+                if let Some(method) = entry.method.upgrade() {
+                    stats.method_cache_hits += 1;
+                    return Some(method);
+                }
+            }
+        }
+
+        stats.method_cache_misses += 1;
+        let method = Rc::clone(class.find_method(name)?);
+        *self.entry.borrow_mut() = Some(Entry {
+            class: Rc::downgrade(class),
+            method: Rc::downgrade(&method),
+        });
+        Some(method)
+    }
+}
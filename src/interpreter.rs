@@ -1,51 +1,679 @@
+use crate::actors::Mailbox;
 use crate::ast::{Expr, Stmt};
+use crate::conversions::IntoNative;
+use crate::crash;
 use crate::environment::Environment;
-use crate::lox_error::{LoxError, ReturnError, RuntimeError};
-use crate::native_functions::setup_native_functions;
-use crate::token::{Callable, Function, Literal};
+use crate::error_value::ErrorValue;
+use crate::hooks::Hook;
+use crate::inline_cache::InlineCache;
+use crate::lint::{Lint, LintConfig, Warning};
+use crate::lox_error::{
+    BreakError, ContinueError, LimitError, LoxError, NativeError, ReturnError, RuntimeError,
+};
+use crate::memory_profile::{Allocation, MemoryProfile};
+use crate::messages;
+use crate::methods;
+use crate::number_format::NumberFormat;
+use crate::parser;
+use crate::random::Random;
+use crate::replay::Tape;
+use crate::resolver;
+use crate::runtime_warnings::RuntimeWarnings;
+use crate::scanner::Scanner;
+use crate::stats::Stats;
+use crate::timers::Timers;
+use crate::token::{
+    Arity, Callable, ForeignMethod, Function, Literal, LoxClass, LoxInstance, NativeClosure,
+    NativeFunction, Token,
+};
 use crate::token_type::TokenType;
+use crate::value;
+use smallvec::SmallVec;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, Write};
 use std::mem;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use tracing::debug;
 
-fn is_truthy(val: &Literal) -> bool {
-    match val {
-        Literal::None => false,
-        Literal::Bool(b) => *b,
-        _ => true,
+/// How deeply Lox functions may call each other before a stack overflow
+/// error is raised, unless set otherwise.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1024;
+/// A stack with room for `DEFAULT_MAX_CALL_DEPTH` calls even in a debug
+/// build, for the threads interpreters run on. Lox calls recurse on the
+/// Rust stack.
+pub const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+/// Evaluated arguments, kept inline for the common case of a few of them
+/// to avoid a heap allocation per call.
+type Values = SmallVec<[Literal; 4]>;
+
+fn targets_loop(target: &Option<String>, label: &Option<Box<Token>>) -> bool {
+    match target {
+        Some(target) => label.as_ref().is_some_and(|l| &l.lexeme == target),
+        None => true,
     }
 }
 
-fn is_equal(left: &Literal, right: &Literal) -> bool {
-    match (left, right) {
-        (Literal::None, Literal::None) => true,
-        (Literal::Bool(left), Literal::Bool(right)) => left == right,
-        (Literal::Number(left), Literal::Number(right)) => left == right,
-        (Literal::String(left), Literal::String(right)) => left == right,
-        (_, _) => false,
+/// Applies a binary `operator` that evaluates both of its operands.
+fn binary(operator: &Token, left: Literal, right: Literal) -> Result<Literal, LoxError> {
+    match operator.type_ {
+        TokenType::Minus => match (left, right) {
+            (Literal::Number(left), Literal::Number(right)) => Ok(Literal::Number(left - right)),
+            _ => Err(
+                RuntimeError::from_message(operator, messages::OPERANDS_MUST_BE_NUMBERS, &[])
+                    .into(),
+            ),
+        },
+        TokenType::Slash => match (left, right) {
+            (Literal::Number(left), Literal::Number(right)) => Ok(Literal::Number(left / right)),
+            _ => Err(
+                RuntimeError::from_message(operator, messages::OPERANDS_MUST_BE_NUMBERS, &[])
+                    .into(),
+            ),
+        },
+        TokenType::Star => match (left, right) {
+            (Literal::Number(left), Literal::Number(right)) => Ok(Literal::Number(left * right)),
+            _ => Err(
+                RuntimeError::from_message(operator, messages::OPERANDS_MUST_BE_NUMBERS, &[])
+                    .into(),
+            ),
+        },
+        TokenType::Percent => match (left, right) {
+            (Literal::Number(left), Literal::Number(right)) => Ok(Literal::Number(left % right)),
+            _ => Err(
+                RuntimeError::from_message(operator, messages::OPERANDS_MUST_BE_NUMBERS, &[])
+                    .into(),
+            ),
+        },
+        TokenType::StarStar => match (left, right) {
+            (Literal::Number(left), Literal::Number(right)) => {
+                Ok(Literal::Number(left.powf(right)))
+            }
+            _ => Err(
+                RuntimeError::from_message(operator, messages::OPERANDS_MUST_BE_NUMBERS, &[])
+                    .into(),
+            ),
+        },
+        TokenType::Plus => {
+            value::add(&left, &right).map_err(|e| RuntimeError::from_native(operator, e).into())
+        }
+        TokenType::Greater => match value::compare(&left, &right) {
+            Ok(ordering) => Ok(Literal::Bool(matches!(ordering, Some(Ordering::Greater)))),
+            Err(e) => Err(RuntimeError::from_native(operator, e).into()),
+        },
+        TokenType::GreaterEqual => match value::compare(&left, &right) {
+            Ok(ordering) => Ok(Literal::Bool(matches!(
+                ordering,
+                Some(Ordering::Greater | Ordering::Equal)
+            ))),
+            Err(e) => Err(RuntimeError::from_native(operator, e).into()),
+        },
+        TokenType::Less => match value::compare(&left, &right) {
+            Ok(ordering) => Ok(Literal::Bool(matches!(ordering, Some(Ordering::Less)))),
+            Err(e) => Err(RuntimeError::from_native(operator, e).into()),
+        },
+        TokenType::LessEqual => match value::compare(&left, &right) {
+            Ok(ordering) => Ok(Literal::Bool(matches!(
+                ordering,
+                Some(Ordering::Less | Ordering::Equal)
+            ))),
+            Err(e) => Err(RuntimeError::from_native(operator, e).into()),
+        },
+        TokenType::In => match (left, right) {
+            (Literal::String(left), Literal::String(right)) => {
+                Ok(Literal::Bool(right.contains(&left)))
+            }
+            (left, Literal::List(right)) => Ok(Literal::Bool(
+                right.borrow().iter().any(|e| value::lox_equals(&left, e)),
+            )),
+            (Literal::String(left), Literal::Map(right)) => {
+                Ok(Literal::Bool(right.borrow().contains_key(&left)))
+            }
+            (_, Literal::String(_)) => {
+                Err(
+                    RuntimeError::from_message(operator, messages::SEARCH_STRING_FOR_STRING, &[])
+                        .into(),
+                )
+            }
+            (_, Literal::Map(_)) => {
+                Err(
+                    RuntimeError::from_message(operator, messages::MAP_KEYS_MUST_BE_STRINGS, &[])
+                        .into(),
+                )
+            }
+            _ => Err(RuntimeError::from_message(operator, messages::IN_RIGHT_OPERAND, &[]).into()),
+        },
+        TokenType::BangEqual => Ok(Literal::Bool(!value::lox_equals(&left, &right))),
+        TokenType::EqualEqual => Ok(Literal::Bool(value::lox_equals(&left, &right))),
+        _ => unreachable!(),
     }
 }
 
+/// Whether `statement` binds a name in the scope it appears in.
+pub fn declares(statement: &Stmt) -> bool {
+    matches!(
+        statement,
+        Stmt::Class { .. } | Stmt::Function { .. } | Stmt::Var { .. }
+    )
+}
+
 pub struct Interpreter {
+    /// The scope top-level declarations go in, where variables the
+    /// resolver couldn't find in a local scope are looked up.
     pub globals: Environment,
     pub environment: Environment,
+    pub stats: Stats,
+    call_depth: usize,
+    max_call_depth: Option<usize>,
+    fuel: Option<usize>,
+    /// When the run must stop, and the wall time it was given.
+    deadline: Option<(Instant, Duration)>,
+    capture_by_iteration: bool,
+    output: Box<dyn Write>,
+    /// Where `readLine` reads from, or `None` for stdin.
+    input: Option<Box<dyn BufRead>>,
+    /// What `args` returns: the arguments the script was given.
+    args: Vec<String>,
+    pub stopwatches: Vec<Instant>,
+    pub timers: Timers,
+    pub mailbox: Mailbox,
+    pub tape: Tape,
+    /// Seeded from the clock on first use, unless `randomSeed` was called.
+    pub random: Option<Random>,
+    sandboxed: bool,
+    number_format: NumberFormat,
+    max_output: Option<usize>,
+    output_written: usize,
+    /// The scope the last uncaught runtime error was raised in.
+    error_scope: Option<Environment>,
+    memory_profile: Option<MemoryProfile>,
+    hooks: Vec<Rc<RefCell<dyn Hook>>>,
+    runtime_warnings: Option<RuntimeWarnings>,
+    /// The function the last call returned from, if it returned nil by
+    /// reaching the end of its body. Only tracked for runtime warnings.
+    implicit_return: Option<String>,
+    #[cfg(feature = "async")]
+    runtime: Runtime,
+}
+
+/// Where async natives are driven.
+#[cfg(feature = "async")]
+enum Runtime {
+    /// Not yet needed by a synchronous run.
+    None,
+    /// Created for a synchronous run.
+    Owned(tokio::runtime::Runtime),
+    /// The caller's runtime, inside `run_async`.
+    Handle(tokio::runtime::Handle),
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Interpreter {
     pub fn new() -> Self {
-        let mut globals = Environment::new();
-        setup_native_functions(&mut globals);
+        Self::with_writer(io::stdout())
+    }
 
-        let environment = Environment::from_env(&globals);
+    pub fn with_writer<W: Write + 'static>(writer: W) -> Self {
+        // The outermost scope is left for natives that globals shadow
+        let globals = Environment::from_env(&Environment::new());
         Interpreter {
-            globals,
-            environment,
+            globals: globals.clone(),
+            environment: globals,
+            stats: Stats::default(),
+            call_depth: 0,
+            max_call_depth: Some(DEFAULT_MAX_CALL_DEPTH),
+            fuel: None,
+            deadline: None,
+            capture_by_iteration: false,
+            output: Box::new(writer),
+            input: None,
+            args: Vec::new(),
+            stopwatches: Vec::new(),
+            timers: Timers::default(),
+            mailbox: Mailbox::default(),
+            tape: Tape::default(),
+            random: None,
+            sandboxed: false,
+            number_format: NumberFormat::default(),
+            max_output: None,
+            output_written: 0,
+            error_scope: None,
+            memory_profile: None,
+            hooks: Vec::new(),
+            runtime_warnings: None,
+            implicit_return: None,
+            #[cfg(feature = "async")]
+            runtime: Runtime::None,
+        }
+    }
+
+    /// Assigns to the variable `name` declared `depth` scopes out, or to
+    /// the global if it wasn't resolved.
+    fn assign_variable(
+        &mut self,
+        name: &Token,
+        depth: Option<usize>,
+        value: &Literal,
+    ) -> Result<(), LoxError> {
+        match depth {
+            Some(depth) => self.environment.assign_at(depth, name, value),
+            None => self
+                .globals
+                .assign(name, value)
+                .map_err(|_| self.environment.undefined(name)),
+        }
+    }
+
+    /// Applies `target++` or `target--`, returning the value `target` had.
+    fn postfix(&mut self, operator: &Token, target: &Expr) -> Result<Literal, LoxError> {
+        let step = parser::increment_operator(operator);
+        let one = Literal::Number(1.0);
+        match target {
+            Expr::Variable { name, depth } => {
+                let previous = self.evaluate(target)?;
+                let value = binary(&step, previous.clone(), one)?;
+                self.assign_variable(name, *depth, &value)?;
+                Ok(previous)
+            }
+            Expr::Get { object, name, .. } => {
+                let Literal::Instance(instance) = self.evaluate(object)? else {
+                    return Err(RuntimeError::from_message(
+                        name,
+                        messages::ONLY_INSTANCES_HAVE_FIELDS,
+                        &[],
+                    )
+                    .into());
+                };
+                let previous = LoxInstance::get(&instance, &name.lexeme).ok_or_else(|| {
+                    RuntimeError::from_message(name, messages::UNDEFINED_PROPERTY, &[&name.lexeme])
+                })?;
+                let value = binary(&step, previous.clone(), one)?;
+                instance.borrow_mut().set(&name.lexeme, value);
+                Ok(previous)
+            }
+            Expr::Index {
+                object,
+                bracket,
+                index,
+            } => {
+                let object = self.evaluate(object)?;
+                let index = self.evaluate(index)?;
+                let previous = value::index(&object, &index)
+                    .map_err(|e| RuntimeError::from_native(bracket, e))?;
+                let value = binary(&step, previous.clone(), one)?;
+                value::set_index(&object, &index, value)
+                    .map_err(|e| RuntimeError::from_native(bracket, e))?;
+                Ok(previous)
+            }
+            _ => unreachable!("The parser only allows assignable targets"),
+        }
+    }
+
+    /// Reports suspicious operations as the program runs, at the levels in
+    /// `config`. See `take_warnings`.
+    pub fn set_runtime_warnings(&mut self, config: Option<LintConfig>) {
+        self.runtime_warnings = config.map(RuntimeWarnings::new);
+    }
+
+    /// The runtime warnings reported since this was last called.
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        self.runtime_warnings
+            .as_mut()
+            .map_or_else(Vec::new, RuntimeWarnings::take)
+    }
+
+    /// Notes whether `function` just returned by reaching its end.
+    pub fn note_return(&mut self, function: &Function, implicit: bool) {
+        if self.runtime_warnings.is_some() {
+            self.implicit_return =
+                implicit.then(|| function.name.as_deref().unwrap_or("<lambda>").to_string());
+        }
+    }
+
+    /// Evaluates a condition, warning if it is a function, which is always
+    /// truthy and was most likely meant to be called.
+    fn condition(&mut self, condition: &Expr) -> Result<bool, LoxError> {
+        let value = self.evaluate(condition)?;
+        if let (Some(warnings), Literal::Callable(callable)) = (&mut self.runtime_warnings, &value)
+        {
+            let name = callable.name();
+            warnings.warn(
+                Lint::FunctionCondition,
+                condition.line().unwrap_or_default(),
+                messages::text(messages::FUNCTION_CONDITION, &[&name]),
+                messages::text(messages::FUNCTION_CONDITION_HELP, &[]),
+            )?;
         }
+        Ok(value::is_truthy(&value))
+    }
+
+    /// Warns about a binary operator that is applied to values that make
+    /// it suspicious, before it is applied.
+    fn check_binary(
+        &mut self,
+        operator: &Token,
+        left: (&Expr, &Literal),
+        right: (&Expr, &Literal),
+    ) -> Result<(), LoxError> {
+        let Some(warnings) = &mut self.runtime_warnings else {
+            return Ok(());
+        };
+        match operator.type_ {
+            TokenType::EqualEqual | TokenType::BangEqual => {
+                let (left, right) = (left.1, right.1);
+                let (left_type, right_type) = (value::type_name(left), value::type_name(right));
+                // Comparing with nil is how a missing value is checked for
+                let nil = matches!(left, Literal::None) || matches!(right, Literal::None);
+                if left_type != right_type && !nil {
+                    let always = operator.type_ == TokenType::BangEqual;
+                    let args: [&dyn std::fmt::Display; 4] =
+                        [&left_type, &right_type, &operator.lexeme, &always];
+                    warnings.warn(
+                        Lint::MixedTypeEquality,
+                        operator.line,
+                        messages::text(messages::MIXED_TYPE_EQUALITY, &args),
+                        messages::text(messages::MIXED_TYPE_EQUALITY_HELP, &[]),
+                    )?;
+                }
+            }
+            TokenType::Minus
+            | TokenType::Plus
+            | TokenType::Slash
+            | TokenType::Star
+            | TokenType::Percent
+            | TokenType::StarStar
+            | TokenType::Greater
+            | TokenType::GreaterEqual
+            | TokenType::Less
+            | TokenType::LessEqual => {
+                for (expr, value) in [left, right] {
+                    if !matches!((expr, value), (Expr::Call { .. }, Literal::None)) {
+                        continue;
+                    }
+                    if let Some(function) = &self.implicit_return {
+                        warnings.warn(
+                            Lint::ImplicitNil,
+                            operator.line,
+                            messages::text(messages::IMPLICIT_NIL, &[function]),
+                            messages::text(messages::IMPLICIT_NIL_HELP, &[function]),
+                        )?;
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Forgets every global, as if nothing had run yet.
+    pub fn reset_globals(&mut self) {
+        let root = self.globals.enclosing().unwrap_or_default();
+        self.globals = Environment::from_env(&root);
+        self.environment = self.globals.clone();
+    }
+
+    /// Makes `closure` callable from scripts as `name`, with `arity`
+    /// arguments. Like the built-in natives, globals can shadow it.
+    pub fn register_native(
+        &mut self,
+        name: &str,
+        arity: impl Into<Arity>,
+        closure: impl Fn(&mut Interpreter, &[Literal]) -> Result<Literal, LoxError> + 'static,
+    ) {
+        self.define_native(name, arity.into(), Rc::new(closure));
+    }
+
+    /// Registers a Rust closure as a native, converting its arguments from
+    /// and its result to Lox values, e.g. `|x: f64, y: f64| x.max(y)`.
+    pub fn register_fn<Args>(&mut self, name: &str, function: impl IntoNative<Args>) {
+        let arity = Arity::Exactly(function.arity());
+        self.define_native(name, arity, function.into_native());
+    }
+
+    fn define_native(&mut self, name: &str, arity: Arity, closure: NativeClosure) {
+        let native = Literal::Callable(Callable::NativeFunction(NativeFunction { arity, closure }));
+        let mut natives = self
+            .globals
+            .enclosing()
+            .expect("Globals are nested in the scope for natives");
+        natives.define(&Token::new(TokenType::Identifier, name, None, 0), &native);
+    }
+
+    /// Limits how many more statements may be executed and how long, from
+    /// now, they may take, so untrusted scripts can't run forever. Each
+    /// call replaces both limits; `None` lifts one.
+    pub fn set_limits(&mut self, max_steps: Option<usize>, max_wall_time: Option<Duration>) {
+        self.fuel = max_steps;
+        self.deadline = max_wall_time.map(|time| (Instant::now() + time, time));
+    }
+
+    /// Reading the clock is comparatively slow, so it is only checked every
+    /// so many steps.
+    fn check_deadline(&self, steps: usize) -> Result<(), LoxError> {
+        if let Some((deadline, time)) = self.deadline {
+            if steps.is_multiple_of(1024) && Instant::now() >= deadline {
+                let time = format!("{:?}", time);
+                return Err(LimitError::new(&messages::text(
+                    messages::TIME_LIMIT_EXCEEDED,
+                    &[&time],
+                ))
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the scope the last run's runtime error was raised in, with
+    /// the variables as they were when it failed.
+    pub fn take_error_scope(&mut self) -> Option<Environment> {
+        self.error_scope.take()
+    }
+
+    /// Remembers the current scope if `result` is a runtime error raised
+    /// in it, rather than one passing through from a nested scope.
+    fn note_error_scope(&mut self, result: &Result<(), LoxError>) {
+        if matches!(result, Err(LoxError::Runtime(_))) && self.error_scope.is_none() {
+            self.error_scope = Some(self.environment.clone());
+        }
+    }
+
+    /// Counts the strings, environments, lists and functions the program
+    /// allocates, by source line. See `memory_profile`.
+    pub fn set_memory_profiling(&mut self, enabled: bool) {
+        self.memory_profile = enabled.then(MemoryProfile::new);
+    }
+
+    pub fn memory_profile(&self) -> Option<&MemoryProfile> {
+        self.memory_profile.as_ref()
+    }
+
+    fn allocated(&mut self, allocation: Allocation) {
+        if let Some(profile) = &mut self.memory_profile {
+            profile.record(allocation);
+        }
+    }
+
+    /// Registers `hook` to be told about every statement and call from now
+    /// on. The caller can keep a clone to read what the hook collected.
+    pub fn add_hook(&mut self, hook: Rc<RefCell<dyn Hook>>) {
+        self.hooks.push(hook);
+    }
+
+    /// The field `name` of `instance`, or else the class's method `name`
+    /// bound to it, found through the lookup site's `cache`.
+    fn get_property(
+        &mut self,
+        instance: &Rc<RefCell<LoxInstance>>,
+        name: &str,
+        cache: &InlineCache,
+    ) -> Option<Literal> {
+        let class = {
+            let this = instance.borrow();
+            if let Some(value) = this.field(name) {
+                return Some(value.clone());
+            }
+            Rc::clone(&this.class)
+        };
+        let method = cache.method(&class, name, &mut self.stats)?;
+        Some(Literal::Callable(Callable::Function(method.bind(instance))))
+    }
+
+    /// Counts a new scope in the stats and the memory profile.
+    pub fn count_environment(&mut self) {
+        self.stats.environments += 1;
+        self.allocated(Allocation::Environment);
+    }
+
+    /// Gives each iteration of a `for` loop its own binding of the loop
+    /// variable instead of sharing one across all iterations.
+    pub fn set_capture_by_iteration(&mut self, enabled: bool) {
+        self.capture_by_iteration = enabled;
+    }
+
+    pub fn capture_by_iteration(&self) -> bool {
+        self.capture_by_iteration
+    }
+
+    /// Denies natives access to the file system.
+    pub fn set_sandboxed(&mut self, sandboxed: bool) {
+        self.sandboxed = sandboxed;
+    }
+
+    pub fn is_sandboxed(&self) -> bool {
+        self.sandboxed
+    }
+
+    /// Sets how numbers are printed and converted to strings.
+    pub fn set_number_format(&mut self, number_format: NumberFormat) {
+        self.number_format = number_format;
+    }
+
+    /// Converts `value` to a string using the configured number format.
+    pub fn stringify(&self, value: &Literal) -> String {
+        self.number_format.display(value).to_string()
+    }
+
+    /// Limits the number of bytes a run may print.
+    pub fn set_max_output(&mut self, max_output: Option<usize>) {
+        self.max_output = max_output;
+    }
+
+    /// Gives the script `args`, as returned by the `args` native.
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.args = args;
+    }
+
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    /// Makes `readLine` and `readNumber` read from `input` instead of stdin.
+    pub fn set_input<R: BufRead + 'static>(&mut self, input: R) {
+        self.input = Some(Box::new(input));
+    }
+
+    /// Reads a line of input without its line ending, or `None` at the end
+    /// of the input.
+    pub fn read_line(&mut self) -> io::Result<Option<String>> {
+        let mut line = String::new();
+        let read = match &mut self.input {
+            Some(input) => input.read_line(&mut line)?,
+            None => io::stdin().lock().read_line(&mut line)?,
+        };
+        if read == 0 {
+            return Ok(None);
+        }
+        let end = line.trim_end_matches(['\n', '\r']).len();
+        line.truncate(end);
+        Ok(Some(line))
+    }
+
+    /// Prints `values` on one line, separated by spaces.
+    pub fn print(&mut self, values: &[Literal]) -> Result<(), NativeError> {
+        let values: Vec<String> = values.iter().map(|value| self.stringify(value)).collect();
+        let text = values.join(" ") + "\n";
+        self.count_output(&text)?;
+        self.output
+            .write_all(text.as_bytes())
+            .map_err(|e| NativeError::from_message(messages::OUTPUT_FAILED, &[&e]))
+    }
+
+    /// Writes `text` as is, flushing so control sequences take effect
+    /// immediately.
+    pub fn write(&mut self, text: &str) -> Result<(), NativeError> {
+        self.count_output(text)?;
+        self.output
+            .write_all(text.as_bytes())
+            .and_then(|_| self.output.flush())
+            .map_err(|e| NativeError::from_message(messages::OUTPUT_FAILED, &[&e]))
+    }
+
+    /// Fails without counting `text` if printing it would go over the limit.
+    fn count_output(&mut self, text: &str) -> Result<(), NativeError> {
+        let written = self.output_written + text.len();
+        if let Some(max) = self.max_output.filter(|max| written > *max) {
+            return Err(NativeError::from_message(
+                messages::OUTPUT_LIMIT_EXCEEDED,
+                &[&max],
+            ));
+        }
+        self.output_written = written;
+        Ok(())
+    }
+
+    /// Runs `statements` on the current multi-threaded tokio runtime, so
+    /// async natives are driven by the caller's executor. Evaluation itself
+    /// is synchronous: the script keeps a worker thread for as long as it
+    /// runs, and tokio moves other tasks off that worker meanwhile.
+    #[cfg(feature = "async")]
+    pub async fn run_async(&mut self, statements: Vec<Stmt>) -> Result<(), LoxError> {
+        let previous = mem::replace(
+            &mut self.runtime,
+            Runtime::Handle(tokio::runtime::Handle::current()),
+        );
+        let result = tokio::task::block_in_place(|| self.interpret(statements));
+        self.runtime = previous;
+        result
+    }
+
+    /// Waits for an async native's result.
+    #[cfg(feature = "async")]
+    pub fn block_on(&mut self, future: crate::token::NativeFuture) -> Result<Literal, LoxError> {
+        if let Runtime::None = self.runtime {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_time()
+                .build()
+                .expect("Failed to start async runtime");
+            self.runtime = Runtime::Owned(runtime);
+        }
+
+        match &self.runtime {
+            Runtime::Owned(runtime) => runtime.block_on(future),
+            Runtime::Handle(handle) => handle.block_on(future),
+            Runtime::None => unreachable!(),
+        }
+    }
+
+    pub fn set_max_call_depth(&mut self, max_call_depth: Option<usize>) {
+        self.max_call_depth = max_call_depth;
     }
 
     pub fn evaluate(&mut self, expression: &Expr) -> Result<Literal, LoxError> {
+        self.stats.expressions += 1;
+        self.check_deadline(self.stats.expressions)?;
+
         match expression {
-            Expr::Assign { name, value } => {
+            Expr::Assign { name, value, depth } => {
                 let value = self.evaluate(value)?;
-                self.environment.assign(name, &value)?;
+                self.assign_variable(name, *depth, &value)?;
                 Ok(value)
             }
             Expr::Binary {
@@ -53,69 +681,17 @@ impl Interpreter {
                 operator,
                 right,
             } => {
-                let left = self.evaluate(left)?;
-                let right = self.evaluate(right)?;
+                let left_value = self.evaluate(left)?;
+                let right_value = self.evaluate(right)?;
+                if self.runtime_warnings.is_some() {
+                    self.check_binary(operator, (left, &left_value), (right, &right_value))?;
+                }
 
-                match operator.type_ {
-                    TokenType::Minus => match (left, right) {
-                        (Literal::Number(left), Literal::Number(right)) => {
-                            Ok(Literal::Number(left - right))
-                        }
-                        _ => Err(RuntimeError::new(operator, "Operands must be numbers.").into()),
-                    },
-                    TokenType::Slash => match (left, right) {
-                        (Literal::Number(left), Literal::Number(right)) => {
-                            Ok(Literal::Number(left / right))
-                        }
-                        _ => Err(RuntimeError::new(operator, "Operands must be numbers.").into()),
-                    },
-                    TokenType::Star => match (left, right) {
-                        (Literal::Number(left), Literal::Number(right)) => {
-                            Ok(Literal::Number(left * right))
-                        }
-                        _ => Err(RuntimeError::new(operator, "Operands must be numbers.").into()),
-                    },
-                    TokenType::Plus => match (left, right) {
-                        (Literal::Number(left), Literal::Number(right)) => {
-                            Ok(Literal::Number(left + right))
-                        }
-                        (Literal::String(left), Literal::String(right)) => {
-                            Ok(Literal::String(left + &right))
-                        }
-                        _ => Err(RuntimeError::new(
-                            operator,
-                            "Operands must be two numbers or two strings.",
-                        )
-                        .into()),
-                    },
-                    TokenType::Greater => match (left, right) {
-                        (Literal::Number(left), Literal::Number(right)) => {
-                            Ok(Literal::Bool(left > right))
-                        }
-                        _ => Err(RuntimeError::new(operator, "Operands must be numbers.").into()),
-                    },
-                    TokenType::GreaterEqual => match (left, right) {
-                        (Literal::Number(left), Literal::Number(right)) => {
-                            Ok(Literal::Bool(left >= right))
-                        }
-                        _ => Err(RuntimeError::new(operator, "Operands must be numbers.").into()),
-                    },
-                    TokenType::Less => match (left, right) {
-                        (Literal::Number(left), Literal::Number(right)) => {
-                            Ok(Literal::Bool(left < right))
-                        }
-                        _ => Err(RuntimeError::new(operator, "Operands must be numbers.").into()),
-                    },
-                    TokenType::LessEqual => match (left, right) {
-                        (Literal::Number(left), Literal::Number(right)) => {
-                            Ok(Literal::Bool(left <= right))
-                        }
-                        _ => Err(RuntimeError::new(operator, "Operands must be numbers.").into()),
-                    },
-                    TokenType::BangEqual => Ok(Literal::Bool(!is_equal(&left, &right))),
-                    TokenType::EqualEqual => Ok(Literal::Bool(is_equal(&left, &right))),
-                    _ => unreachable!(),
+                let result = binary(operator, left_value, right_value)?;
+                if let Literal::String(_) = result {
+                    self.allocated(Allocation::String);
                 }
+                Ok(result)
             }
             Expr::Call {
                 callee,
@@ -123,31 +699,163 @@ impl Interpreter {
                 arguments,
             } => {
                 let callee = self.evaluate(callee)?;
-                let mut values = Vec::new();
-                for argument in arguments {
-                    values.push(self.evaluate(argument)?);
-                }
+                let values = self.evaluate_spreadable(arguments)?;
 
                 match callee {
                     Literal::Callable(c) => {
-                        if arguments.len() == c.arity() {
-                            c.call(self, &values)
+                        if c.arity().accepts(values.len()) {
+                            if self
+                                .max_call_depth
+                                .is_some_and(|max| self.call_depth >= max)
+                            {
+                                return Err(RuntimeError::from_message(
+                                    paren,
+                                    messages::STACK_OVERFLOW,
+                                    &[],
+                                )
+                                .into());
+                            }
+
+                            self.stats.calls += 1;
+                            self.call_depth += 1;
+                            self.stats.max_call_depth =
+                                self.stats.max_call_depth.max(self.call_depth);
+
+                            self.implicit_return = None;
+                            debug!(line = paren.line, arguments = values.len(), "call enter");
+                            for hook in self.hooks.clone() {
+                                hook.borrow_mut().on_call(&c, &values, paren.line, self);
+                            }
+                            let result = c.call(self, &values).map_err(|e| match (e, &c) {
+                                (LoxError::Native(e), _) => {
+                                    RuntimeError::from_native(paren, e).into()
+                                }
+                                (
+                                    LoxError::Runtime(mut e),
+                                    Callable::Function(_) | Callable::Class(_),
+                                ) => {
+                                    e.push_frame(c.name(), paren.line);
+                                    e.into()
+                                }
+                                (e, _) => e,
+                            });
+                            debug!(line = paren.line, ok = result.is_ok(), "call exit");
+
+                            self.call_depth -= 1;
+                            result
                         } else {
-                            let error_msg = format!(
-                                "Expected {} arguments but got {}.",
-                                c.arity(),
-                                arguments.len()
-                            );
-                            Err(RuntimeError::new(paren, &error_msg).into())
+                            Err(RuntimeError::from_message(
+                                paren,
+                                messages::WRONG_ARGUMENT_COUNT,
+                                &[&c.arity().describe(), &values.len()],
+                            )
+                            .into())
                         }
                     }
-                    _ => {
-                        Err(RuntimeError::new(paren, "Can only call functions and classes.").into())
-                    }
+                    _ => Err(RuntimeError::from_message(paren, messages::NOT_CALLABLE, &[]).into()),
                 }
             }
+            Expr::Get {
+                object,
+                name,
+                cache,
+            } => match self.evaluate(object)? {
+                Literal::Instance(instance) => self
+                    .get_property(&instance, &name.lexeme, cache)
+                    .ok_or_else(|| {
+                        RuntimeError::from_message(
+                            name,
+                            messages::UNDEFINED_PROPERTY,
+                            &[&name.lexeme],
+                        )
+                        .into()
+                    }),
+                Literal::Foreign(receiver) => {
+                    if let Some(value) = receiver.borrow().field(&name.lexeme) {
+                        return Ok(value);
+                    }
+                    let arity = receiver.borrow().method_arity(&name.lexeme);
+                    match arity {
+                        Some(arity) => {
+                            Ok(Literal::Callable(Callable::ForeignMethod(ForeignMethod {
+                                receiver,
+                                name: name.lexeme.clone(),
+                                arity,
+                            })))
+                        }
+                        None => Err(RuntimeError::from_message(
+                            name,
+                            messages::UNDEFINED_PROPERTY,
+                            &[&name.lexeme],
+                        )
+                        .into()),
+                    }
+                }
+                value => match methods::lookup_method(&value, &name.lexeme) {
+                    Some(method) => Ok(Literal::Callable(method)),
+                    None if methods::has_methods(&value) => Err(RuntimeError::from_message(
+                        name,
+                        messages::UNDEFINED_PROPERTY,
+                        &[&name.lexeme],
+                    )
+                    .into()),
+                    None => Err(RuntimeError::from_message(
+                        name,
+                        messages::ONLY_FOREIGN_PROPERTIES,
+                        &[],
+                    )
+                    .into()),
+                },
+            },
             Expr::Grouping { expression } => self.evaluate(expression),
-            Expr::Literal { value } => Ok(value.clone()),
+            Expr::Index {
+                object,
+                bracket,
+                index,
+            } => {
+                let object = self.evaluate(object)?;
+                let index = self.evaluate(index)?;
+                value::index(&object, &index)
+                    .map_err(|e| RuntimeError::from_native(bracket, e).into())
+            }
+            Expr::Lambda { params, body, .. } => {
+                self.allocated(Allocation::Function);
+                Ok(Literal::Callable(Callable::Function(Function {
+                    name: None,
+                    doc: None,
+                    closure: self.environment.clone(),
+                    params: params.clone(),
+                    body: body.clone(),
+                    initializer: false,
+                })))
+            }
+            Expr::List { elements } => {
+                let elements = self.evaluate_spreadable(elements)?;
+                self.allocated(Allocation::List);
+                Ok(Literal::List(Rc::new(RefCell::new(elements.into_vec()))))
+            }
+            Expr::Map { brace, entries } => {
+                let mut map = BTreeMap::new();
+                for (key, value) in entries {
+                    let Literal::String(key) = self.evaluate(key)? else {
+                        return Err(RuntimeError::from_message(
+                            brace,
+                            messages::MAP_KEYS_MUST_BE_STRINGS,
+                            &[],
+                        )
+                        .into());
+                    };
+                    map.insert(key, self.evaluate(value)?);
+                }
+                self.allocated(Allocation::Map);
+                Ok(Literal::Map(Rc::new(RefCell::new(map))))
+            }
+            Expr::Literal { value } => {
+                if let Literal::String(_) = value {
+                    self.allocated(Allocation::String);
+                }
+                Ok(value.clone())
+            }
             Expr::Logical {
                 left,
                 operator,
@@ -156,14 +864,14 @@ impl Interpreter {
                 let left = self.evaluate(left)?;
                 Ok(match operator.type_ {
                     TokenType::Or => {
-                        if is_truthy(&left) {
+                        if value::is_truthy(&left) {
                             left
                         } else {
                             self.evaluate(right)?
                         }
                     }
                     TokenType::And => {
-                        if !is_truthy(&left) {
+                        if !value::is_truthy(&left) {
                             left
                         } else {
                             self.evaluate(right)?
@@ -172,6 +880,99 @@ impl Interpreter {
                     _ => unreachable!(),
                 })
             }
+            Expr::Ternary {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                if self.condition(condition)? {
+                    self.evaluate(then_branch)
+                } else {
+                    self.evaluate(else_branch)
+                }
+            }
+            Expr::Set {
+                object,
+                name,
+                operator,
+                value,
+            } => {
+                let Literal::Instance(instance) = self.evaluate(object)? else {
+                    return Err(RuntimeError::from_message(
+                        name,
+                        messages::ONLY_INSTANCES_HAVE_FIELDS,
+                        &[],
+                    )
+                    .into());
+                };
+                let current = match operator {
+                    Some(_) => {
+                        Some(LoxInstance::get(&instance, &name.lexeme).ok_or_else(|| {
+                            RuntimeError::from_message(
+                                name,
+                                messages::UNDEFINED_PROPERTY,
+                                &[&name.lexeme],
+                            )
+                        })?)
+                    }
+                    None => None,
+                };
+                let mut value = self.evaluate(value)?;
+                if let (Some(operator), Some(current)) = (operator, current) {
+                    value = binary(operator, current, value)?;
+                }
+                instance.borrow_mut().set(&name.lexeme, value.clone());
+                Ok(value)
+            }
+            Expr::SetIndex {
+                object,
+                bracket,
+                index,
+                operator,
+                value,
+            } => {
+                let object = self.evaluate(object)?;
+                let index = self.evaluate(index)?;
+                let current = match operator {
+                    Some(_) => Some(
+                        value::index(&object, &index)
+                            .map_err(|e| RuntimeError::from_native(bracket, e))?,
+                    ),
+                    None => None,
+                };
+                let mut value = self.evaluate(value)?;
+                if let (Some(operator), Some(current)) = (operator, current) {
+                    value = binary(operator, current, value)?;
+                }
+                value::set_index(&object, &index, value.clone())
+                    .map_err(|e| RuntimeError::from_native(bracket, e))?;
+                Ok(value)
+            }
+            Expr::Postfix { operator, target } => self.postfix(operator, target),
+            Expr::Spread { .. } => unreachable!(),
+            Expr::Super { keyword, method } => {
+                let Literal::Callable(Callable::Class(superclass)) =
+                    self.environment.get(keyword)?
+                else {
+                    unreachable!("'super' is bound to the superclass");
+                };
+                let this = Token::new(TokenType::This, "this", None, keyword.line);
+                let Literal::Instance(instance) = self.environment.get(&this)? else {
+                    unreachable!("'this' is bound to an instance");
+                };
+                match superclass.find_method(&method.lexeme) {
+                    Some(function) => Ok(Literal::Callable(Callable::Function(
+                        function.bind(&instance),
+                    ))),
+                    None => Err(RuntimeError::from_message(
+                        method,
+                        messages::UNDEFINED_PROPERTY,
+                        &[&method.lexeme],
+                    )
+                    .into()),
+                }
+            }
+            Expr::This { keyword } => self.environment.get(keyword),
             Expr::Unary { operator, right } => {
                 let right = self.evaluate(right)?;
                 match operator.type_ {
@@ -179,49 +980,210 @@ impl Interpreter {
                         if let Literal::Number(right) = right {
                             Ok(Literal::Number(-right))
                         } else {
-                            Err(RuntimeError::new(operator, "Operand must be a number.").into())
+                            Err(RuntimeError::from_message(
+                                operator,
+                                messages::OPERAND_MUST_BE_NUMBER,
+                                &[],
+                            )
+                            .into())
                         }
                     }
-                    TokenType::Bang => Ok(Literal::Bool(!is_truthy(&right))),
+                    TokenType::Bang => Ok(Literal::Bool(!value::is_truthy(&right))),
                     _ => unreachable!(),
                 }
             }
-            Expr::Variable { name } => Ok(self.environment.get(name)?),
+            Expr::Variable { name, depth } => match depth {
+                Some(depth) => self.environment.get_at(*depth, name),
+                // Suggest names from every scope, not just the globals
+                None => self
+                    .globals
+                    .get(name)
+                    .map_err(|_| self.environment.undefined(name)),
+            },
+        }
+    }
+
+    /// Evaluates call arguments or list elements, splicing in the elements
+    /// of every `...list` expression.
+    fn evaluate_spreadable(&mut self, expressions: &[Expr]) -> Result<Values, LoxError> {
+        let mut values = Values::new();
+        for expression in expressions {
+            match expression {
+                Expr::Spread {
+                    operator,
+                    expression,
+                } => match self.evaluate(expression)? {
+                    Literal::List(list) => values.extend(list.borrow().iter().cloned()),
+                    _ => {
+                        return Err(RuntimeError::from_message(
+                            operator,
+                            messages::SPREAD_NON_LIST,
+                            &[],
+                        )
+                        .into())
+                    }
+                },
+                _ => values.push(self.evaluate(expression)?),
+            }
         }
+        Ok(values)
     }
 
     pub fn execute(&mut self, statement: &Stmt) -> Result<(), LoxError> {
+        self.stats.statements += 1;
+        crash::set_line(statement.line());
+        if let Some(profile) = &mut self.memory_profile {
+            profile.set_line(statement.line());
+        }
+
+        let step = self.stats.statements;
+        if let Some(fuel) = self.fuel.as_mut() {
+            if *fuel == 0 {
+                return Err(
+                    LimitError::new(&messages::text(messages::STEP_LIMIT_EXCEEDED, &[])).into(),
+                );
+            }
+            *fuel -= 1;
+        }
+        self.check_deadline(step)?;
+
+        for hook in self.hooks.clone() {
+            hook.borrow_mut().on_statement(statement, self);
+        }
+
         match statement {
             Stmt::Block { statements } => {
-                self.execute_block(statements, Environment::from_env(&self.environment))?;
+                if statements.iter().any(declares) {
+                    self.count_environment();
+                    self.execute_block(statements, Environment::from_env(&self.environment))?;
+                } else {
+                    // Nothing can be bound in this scope, so share the
+                    // enclosing one.
+                    for statement in statements {
+                        self.execute(statement)?;
+                    }
+                }
+            }
+            Stmt::Break { keyword: _, label } => {
+                let label = label.as_ref().map(|l| l.lexeme.clone());
+                return Err(BreakError { label }.into());
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                doc,
+            } => {
+                self.allocated(Allocation::Function);
+                let superclass = match superclass {
+                    Some(expression) => match self.evaluate(expression)? {
+                        Literal::Callable(Callable::Class(class)) => Some(class),
+                        _ => {
+                            let token = match &**expression {
+                                Expr::Variable { name, .. } => name,
+                                _ => unreachable!("Superclasses are named by a variable"),
+                            };
+                            return Err(RuntimeError::from_message(
+                                token,
+                                messages::SUPERCLASS_NOT_CLASS,
+                                &[],
+                            )
+                            .into());
+                        }
+                    },
+                    None => None,
+                };
+                // Methods of a subclass see `super` in a scope of their own
+                let mut closure = self.environment.clone();
+                if let Some(superclass) = &superclass {
+                    self.count_environment();
+                    closure = Environment::from_env(&closure);
+                    let token = Token::new(TokenType::Super, "super", None, name.line);
+                    closure.define(
+                        &token,
+                        &Literal::Callable(Callable::Class(Rc::clone(superclass))),
+                    );
+                }
+
+                let methods = methods
+                    .iter()
+                    .map(|method| {
+                        let Stmt::Function {
+                            name,
+                            params,
+                            body,
+                            doc,
+                            ..
+                        } = method
+                        else {
+                            unreachable!("Class bodies only hold methods");
+                        };
+                        let function = Function {
+                            name: Some(name.lexeme.clone()),
+                            doc: doc.clone(),
+                            closure: closure.clone(),
+                            params: params.clone(),
+                            body: body.clone(),
+                            initializer: name.lexeme == "init",
+                        };
+                        (name.lexeme.clone(), Rc::new(function))
+                    })
+                    .collect();
+                let class = LoxClass {
+                    name: name.lexeme.clone(),
+                    doc: doc.clone(),
+                    superclass,
+                    methods,
+                };
+                self.environment
+                    .define(name, &Literal::Callable(Callable::Class(Rc::new(class))));
+            }
+            Stmt::Continue { keyword: _, label } => {
+                let label = label.as_ref().map(|l| l.lexeme.clone());
+                return Err(ContinueError { label }.into());
             }
             Stmt::Expression { expression } => {
                 self.evaluate(expression)?;
             }
-            Stmt::Function { name, params, body } => {
+            Stmt::Function {
+                name,
+                params,
+                body,
+                doc,
+                ..
+            } => {
+                self.allocated(Allocation::Function);
                 self.environment.define(
                     name,
                     &Literal::Callable(Callable::Function(Function {
+                        name: Some(name.lexeme.clone()),
+                        doc: doc.clone(),
                         closure: self.environment.clone(),
                         params: params.clone(),
                         body: body.clone(),
+                        initializer: false,
                     })),
                 );
             }
             Stmt::If {
+                keyword: _,
                 condition,
                 then_branch,
                 else_branch,
             } => {
-                if is_truthy(&self.evaluate(condition)?) {
+                if self.condition(condition)? {
                     self.execute(then_branch)?
                 } else if let Some(else_branch) = else_branch {
                     self.execute(else_branch)?
                 }
             }
-            Stmt::Print { expression } => {
+            Stmt::Print {
+                keyword,
+                expression,
+            } => {
                 let value = self.evaluate(expression)?;
-                println!("{}", value);
+                self.print(&[value])
+                    .map_err(|e| RuntimeError::from_native(keyword, e))?;
             }
             Stmt::Return { keyword: _, value } => {
                 let value = match value {
@@ -230,28 +1192,137 @@ impl Interpreter {
                 };
                 return Err(ReturnError { value }.into());
             }
-            Stmt::Var { name, initializer } => {
+            Stmt::Throw { keyword, value } => {
+                let value = self.evaluate(value)?;
+                if let Literal::Foreign(foreign) = &value {
+                    if let Some(error) = foreign.borrow().as_error() {
+                        return Err(error.clone().into());
+                    }
+                }
+                let message = self.stringify(&value);
+                return Err(RuntimeError::thrown(keyword, &message, value).into());
+            }
+            Stmt::Try {
+                body,
+                name,
+                handler,
+            } => {
+                self.count_environment();
+                let scope = Environment::from_env(&self.environment);
+                match self.execute_block(body, scope) {
+                    Err(LoxError::Runtime(error)) => {
+                        self.error_scope = None;
+                        self.count_environment();
+                        let mut scope = Environment::from_env(&self.environment);
+                        let error = Rc::new(RefCell::new(ErrorValue::new(error)));
+                        scope.define(name, &Literal::Foreign(error));
+                        self.execute_block(handler, scope)?;
+                    }
+                    result => result?,
+                }
+            }
+            Stmt::Var {
+                name, initializer, ..
+            } => {
                 let value = match initializer {
                     Some(expression) => self.evaluate(expression)?,
                     None => Literal::None,
                 };
                 self.environment.define(name, &value);
             }
-            Stmt::While { condition, body } => {
-                while is_truthy(&self.evaluate(condition)?) {
-                    self.execute(body)?;
+            Stmt::While {
+                keyword: _,
+                condition,
+                body,
+                increment,
+                bindings,
+                label,
+            } => {
+                if self.capture_by_iteration && !bindings.is_empty() {
+                    self.execute_loop_per_iteration(condition, body, increment, bindings, label)?;
+                } else {
+                    while self.condition(condition)? {
+                        if !self.execute_loop_body(body, label)? {
+                            break;
+                        }
+                        if let Some(increment) = increment {
+                            self.evaluate(increment)?;
+                        }
+                    }
                 }
             }
         }
         Ok(())
     }
 
+    /// Runs a loop where every iteration gets fresh copies of `bindings`, so
+    /// closures created in the body capture that iteration's values. The
+    /// copy is made before the increment, like JavaScript's `for (let ...)`.
+    fn execute_loop_per_iteration(
+        &mut self,
+        condition: &Expr,
+        body: &Stmt,
+        increment: &Option<Box<Expr>>,
+        bindings: &[Token],
+        label: &Option<Box<Token>>,
+    ) -> Result<(), LoxError> {
+        let outer = self.environment.clone();
+
+        let r = || -> Result<(), LoxError> {
+            self.environment = self.iteration_environment(&outer, &outer, bindings)?;
+            while self.condition(condition)? {
+                if !self.execute_loop_body(body, label)? {
+                    break;
+                }
+
+                let previous = self.environment.clone();
+                self.environment = self.iteration_environment(&outer, &previous, bindings)?;
+                if let Some(increment) = increment {
+                    self.evaluate(increment)?;
+                }
+            }
+            Ok(())
+        }();
+
+        self.environment = outer;
+        r
+    }
+
+    /// Executes one iteration of a loop body, returning whether the loop
+    /// should keep going.
+    fn execute_loop_body(
+        &mut self,
+        body: &Stmt,
+        label: &Option<Box<Token>>,
+    ) -> Result<bool, LoxError> {
+        match self.execute(body) {
+            Err(LoxError::Break(b)) if targets_loop(&b.label, label) => Ok(false),
+            Err(LoxError::Continue(c)) if targets_loop(&c.label, label) => Ok(true),
+            Err(e) => Err(e),
+            Ok(()) => Ok(true),
+        }
+    }
+
+    fn iteration_environment(
+        &mut self,
+        outer: &Environment,
+        previous: &Environment,
+        bindings: &[Token],
+    ) -> Result<Environment, LoxError> {
+        self.count_environment();
+        let mut env = Environment::from_env(outer);
+        for name in bindings {
+            env.define(name, &previous.get_at(0, name)?);
+        }
+        Ok(env)
+    }
+
     pub fn execute_block(
         &mut self,
         statements: &Vec<Stmt>,
         environment: Environment,
     ) -> Result<(), LoxError> {
-        let mut env = Environment::from_env(&environment);
+        let mut env = environment;
         mem::swap(&mut self.environment, &mut env);
 
         let r = || -> Result<(), LoxError> {
@@ -261,18 +1332,63 @@ impl Interpreter {
             Ok(())
         }();
 
+        self.note_error_scope(&r);
         mem::swap(&mut self.environment, &mut env);
 
         r
     }
 
+    /// Executes the declarations of a changed program: functions and classes
+    /// are redefined, variables only defined if they don't exist yet, and every
+    /// other statement is skipped, so the program's state survives.
+    pub fn reload(&mut self, statements: Vec<Stmt>) -> Result<(), LoxError> {
+        for statement in statements {
+            let keep = match &statement {
+                Stmt::Class { .. } | Stmt::Function { .. } => true,
+                Stmt::Var { name, .. } => !self.environment.defines(name),
+                _ => false,
+            };
+            if keep {
+                self.execute(&statement)?;
+            }
+        }
+        Ok(())
+    }
+
     pub fn interpret(&mut self, statements: Vec<Stmt>) -> Result<(), LoxError> {
+        debug!(statements = statements.len(), "executing program");
+        self.error_scope = None;
         for statement in statements {
-            self.execute(&statement)?;
+            let result = self.execute(&statement);
+            self.note_error_scope(&result);
+            result?;
         }
 
         Ok(())
     }
+
+    /// Runs `source` as a program, returning the value of its last
+    /// statement if that is an expression, and nil otherwise.
+    pub fn run(&mut self, source: &str) -> Result<Literal, LoxError> {
+        self.run_statements(source)
+            .map_err(|e| e.with_source(|line| source.lines().nth(line.wrapping_sub(1))))
+    }
+
+    fn run_statements(&mut self, source: &str) -> Result<Literal, LoxError> {
+        let mut scanner = Scanner::new(source);
+        let mut statements = parser::Parser::new(scanner.scan_tokens()?).parse()?;
+        resolver::resolve(&mut statements, self.capture_by_iteration);
+
+        let last = match statements.last() {
+            Some(Stmt::Expression { .. }) => statements.pop(),
+            _ => None,
+        };
+        self.interpret(statements)?;
+        match last {
+            Some(Stmt::Expression { expression }) => self.evaluate(&expression),
+            _ => Ok(Literal::None),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -301,9 +1417,80 @@ mod tests {
         };
 
         let mut interpreter = Interpreter::new();
-        assert!(is_equal(
+        assert!(value::lox_equals(
             &Literal::Number(-123.0 * 45.67),
             &interpreter.evaluate(&Box::new(expression)).unwrap()
         ));
     }
+
+    #[test]
+    fn test_run() {
+        let mut interpreter = Interpreter::new();
+        let value = interpreter.run("var x = 6; x * 7;").unwrap();
+        assert!(value::lox_equals(&Literal::Number(42.0), &value));
+
+        // Globals persist between runs
+        let value = interpreter.run("var y = x;").unwrap();
+        assert!(matches!(value, Literal::None));
+        assert!(matches!(interpreter.run("y = ;"), Err(LoxError::Parser(_))));
+    }
+
+    #[test]
+    fn test_read_input() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_input(io::Cursor::new("first line\r\n3.5\nthree\n"));
+
+        let value = interpreter.run("readLine();").unwrap();
+        assert!(value::lox_equals(
+            &Literal::String("first line".into()),
+            &value
+        ));
+        let value = interpreter.run("readNumber();").unwrap();
+        assert!(value::lox_equals(&Literal::Number(3.5), &value));
+        assert!(interpreter.run("readNumber();").is_err());
+        assert!(matches!(interpreter.run("readLine();"), Ok(Literal::None)));
+    }
+
+    #[test]
+    fn test_register_native() {
+        fn double(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, LoxError> {
+            match arguments[0] {
+                Literal::Number(n) => Ok(Literal::Number(n * 2.0)),
+                _ => Err(NativeError::new("Can only double numbers.").into()),
+            }
+        }
+
+        let mut interpreter = Interpreter::new();
+        interpreter.register_native("double", 1, double);
+        let value = interpreter.run("double(21);").unwrap();
+        assert!(value::lox_equals(&Literal::Number(42.0), &value));
+        assert!(interpreter.run("double(\"a\");").is_err());
+
+        // Natives survive forgetting the globals
+        interpreter.reset_globals();
+        assert!(interpreter.run("double(1);").is_ok());
+    }
+
+    #[test]
+    fn test_failing_output_is_a_runtime_error() {
+        struct BrokenPipe;
+
+        impl Write for BrokenPipe {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::BrokenPipe.into())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut interpreter = Interpreter::with_writer(BrokenPipe);
+        for source in ["print 1;", "print(1, 2);", "clearScreen();"] {
+            let Err(LoxError::Runtime(e)) = interpreter.run(source) else {
+                panic!("Writing to a broken pipe should fail: {}", source);
+            };
+            assert!(e.message().starts_with("Failed to write output"));
+        }
+    }
 }
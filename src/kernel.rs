@@ -0,0 +1,533 @@
+use std::cell::RefCell;
+use std::error::Error;
+use std::io::{self, BufRead, Read};
+use std::path::Path;
+use std::rc::Rc;
+use std::thread;
+
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use tracing::{debug, warn};
+
+use crate::interpreter::Interpreter;
+use crate::lox_error::LoxError;
+use crate::output::SharedBuffer;
+use crate::token::Literal;
+
+const DELIMITER: &[u8] = b"<IDS|MSG>";
+const PROTOCOL_VERSION: &str = "5.3";
+
+#[derive(Deserialize)]
+struct ConnectionInfo {
+    transport: String,
+    ip: String,
+    key: String,
+    shell_port: u16,
+    iopub_port: u16,
+    stdin_port: u16,
+    control_port: u16,
+    hb_port: u16,
+}
+
+impl ConnectionInfo {
+    fn endpoint(&self, port: u16) -> String {
+        format!("{}://{}:{}", self.transport, self.ip, port)
+    }
+}
+
+#[derive(Clone)]
+struct Message {
+    identities: Vec<Vec<u8>>,
+    header: Value,
+    content: Value,
+}
+
+impl Message {
+    fn msg_type(&self) -> &str {
+        self.header["msg_type"].as_str().unwrap_or_default()
+    }
+}
+
+enum Outcome {
+    Continue,
+    Shutdown,
+}
+
+/// Signs, checks and frames the messages of one kernel session.
+#[derive(Clone)]
+struct Session {
+    key: Vec<u8>,
+    id: String,
+}
+
+impl Session {
+    fn mac(&self, parts: &[&[u8]]) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts any key");
+        for part in parts {
+            mac.update(part);
+        }
+        mac
+    }
+
+    fn sign(&self, parts: &[&[u8]]) -> String {
+        if self.key.is_empty() {
+            return String::new();
+        }
+
+        self.mac(parts)
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    /// Checks a hex `signature` over `parts` in constant time.
+    fn verify(&self, parts: &[&[u8]], signature: &[u8]) -> bool {
+        if self.key.is_empty() {
+            return signature.is_empty();
+        }
+
+        match decode_hex(signature) {
+            Some(bytes) => self.mac(parts).verify_slice(&bytes).is_ok(),
+            None => false,
+        }
+    }
+
+    fn recv(&self, socket: &zmq::Socket) -> Result<Option<Message>, Box<dyn Error>> {
+        let frames = socket.recv_multipart(0)?;
+        let Some(split) = frames.iter().position(|f| f == DELIMITER) else {
+            warn!("dropping message without delimiter");
+            return Ok(None);
+        };
+        if frames.len() < split + 6 {
+            warn!("dropping truncated message");
+            return Ok(None);
+        }
+
+        let parts = &frames[split + 2..split + 6];
+        let signature = &frames[split + 1];
+        if !self.verify(&[&parts[0], &parts[1], &parts[2], &parts[3]], signature) {
+            warn!("dropping message with invalid signature");
+            return Ok(None);
+        }
+
+        let parsed = serde_json::from_slice(&parts[0])
+            .and_then(|header| Ok((header, serde_json::from_slice(&parts[3])?)));
+        match parsed {
+            Ok((header, content)) => Ok(Some(Message {
+                identities: frames[..split].to_vec(),
+                header,
+                content,
+            })),
+            Err(e) => {
+                warn!(error = %e, "dropping message with malformed JSON");
+                Ok(None)
+            }
+        }
+    }
+
+    fn send(
+        &self,
+        socket: &zmq::Socket,
+        identities: &[Vec<u8>],
+        msg_type: &str,
+        parent: &Message,
+        content: Value,
+    ) -> Result<(), Box<dyn Error>> {
+        let header = json!({
+            "msg_id": uuid::Uuid::new_v4().to_string(),
+            "session": self.id,
+            "username": "lox",
+            "date": chrono::Utc::now().to_rfc3339(),
+            "msg_type": msg_type,
+            "version": PROTOCOL_VERSION,
+        });
+
+        let parts = [
+            serde_json::to_vec(&header)?,
+            serde_json::to_vec(&parent.header)?,
+            b"{}".to_vec(),
+            serde_json::to_vec(&content)?,
+        ];
+        let signature = self.sign(&[&parts[0], &parts[1], &parts[2], &parts[3]]);
+
+        let mut frames: Vec<Vec<u8>> = identities.to_vec();
+        frames.push(DELIMITER.to_vec());
+        frames.push(signature.into_bytes());
+        frames.extend(parts);
+        socket.send_multipart(frames, 0)?;
+        Ok(())
+    }
+}
+
+struct Kernel {
+    session: Session,
+    iopub: zmq::Socket,
+    interpreter: Interpreter,
+    output: SharedBuffer,
+    /// The execute request being run, while it allows input.
+    input_request: Rc<RefCell<Option<Message>>>,
+    execution_count: u64,
+}
+
+impl Kernel {
+    fn publish(
+        &self,
+        msg_type: &str,
+        parent: &Message,
+        content: Value,
+    ) -> Result<(), Box<dyn Error>> {
+        let topic = vec![msg_type.as_bytes().to_vec()];
+        self.session
+            .send(&self.iopub, &topic, msg_type, parent, content)
+    }
+
+    fn reply(
+        &self,
+        socket: &zmq::Socket,
+        request: &Message,
+        content: Value,
+    ) -> Result<(), Box<dyn Error>> {
+        let msg_type = request.msg_type().replace("_request", "_reply");
+        self.session
+            .send(socket, &request.identities, &msg_type, request, content)
+    }
+
+    fn handle(
+        &mut self,
+        socket: &zmq::Socket,
+        request: Message,
+    ) -> Result<Outcome, Box<dyn Error>> {
+        debug!(msg_type = request.msg_type(), "kernel request");
+        self.publish("status", &request, json!({ "execution_state": "busy" }))?;
+
+        let mut outcome = Outcome::Continue;
+        match request.msg_type() {
+            "kernel_info_request" => self.reply(socket, &request, kernel_info())?,
+            "execute_request" => self.execute(socket, &request)?,
+            "is_complete_request" => {
+                self.reply(socket, &request, json!({ "status": "complete" }))?
+            }
+            "comm_info_request" => {
+                self.reply(socket, &request, json!({ "status": "ok", "comms": {} }))?
+            }
+            "shutdown_request" => {
+                let restart = request.content["restart"].as_bool().unwrap_or(false);
+                self.reply(
+                    socket,
+                    &request,
+                    json!({ "status": "ok", "restart": restart }),
+                )?;
+                outcome = Outcome::Shutdown;
+            }
+            other => debug!(msg_type = other, "ignoring unsupported request"),
+        }
+
+        self.publish("status", &request, json!({ "execution_state": "idle" }))?;
+        Ok(outcome)
+    }
+
+    fn execute(&mut self, socket: &zmq::Socket, request: &Message) -> Result<(), Box<dyn Error>> {
+        let code = request.content["code"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        self.execution_count += 1;
+        let count = self.execution_count;
+
+        self.publish(
+            "execute_input",
+            request,
+            json!({ "code": code, "execution_count": count }),
+        )?;
+
+        if request.content["allow_stdin"].as_bool().unwrap_or(false) {
+            *self.input_request.borrow_mut() = Some(request.clone());
+        }
+        let result = self.run_cell(&code);
+        self.input_request.borrow_mut().take();
+
+        let text = self.output.take();
+        if !text.is_empty() {
+            self.publish("stream", request, json!({ "name": "stdout", "text": text }))?;
+        }
+
+        match result {
+            Ok(value) => {
+                if let Some(value) = value {
+                    self.publish(
+                        "execute_result",
+                        request,
+                        json!({
+                            "execution_count": count,
+                            "data": { "text/plain": self.interpreter.stringify(&value) },
+                            "metadata": {},
+                        }),
+                    )?;
+                }
+                self.reply(
+                    socket,
+                    request,
+                    json!({
+                        "status": "ok",
+                        "execution_count": count,
+                        "payload": [],
+                        "user_expressions": {},
+                    }),
+                )
+            }
+            Err(e) => {
+                let ename = match e {
+                    LoxError::Scanner(_) => "ScannerError",
+                    LoxError::Parser(_) => "ParserError",
+                    _ => "RuntimeError",
+                };
+                let error = json!({
+                    "ename": ename,
+                    "evalue": e.to_string(),
+                    "traceback": [e.to_string()],
+                });
+                self.publish("error", request, error.clone())?;
+
+                let mut reply = error;
+                reply["status"] = json!("error");
+                reply["execution_count"] = json!(count);
+                self.reply(socket, request, reply)
+            }
+        }
+    }
+
+    /// Runs a cell, giving the value of a trailing expression statement so
+    /// it can be shown as the cell result.
+    fn run_cell(&mut self, code: &str) -> Result<Option<Literal>, LoxError> {
+        match self.interpreter.run(code) {
+            Ok(Literal::None) | Err(LoxError::Return(_)) => Ok(None),
+            Ok(value) => Ok(Some(value)),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Feeds `readLine` from the frontend, asking for each line with an
+/// `input_request` on the stdin channel. Requests that don't allow input
+/// read nothing.
+struct Input {
+    session: Session,
+    socket: zmq::Socket,
+    request: Rc<RefCell<Option<Message>>>,
+    line: Vec<u8>,
+    position: usize,
+}
+
+impl Input {
+    fn request_line(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        let Some(request) = &*self.request.borrow() else {
+            return Ok(Vec::new());
+        };
+
+        let prompt = json!({ "prompt": "", "password": false });
+        self.session.send(
+            &self.socket,
+            &request.identities,
+            "input_request",
+            request,
+            prompt,
+        )?;
+        loop {
+            match self.session.recv(&self.socket)? {
+                Some(reply) if reply.msg_type() == "input_reply" => {
+                    let value = reply.content["value"].as_str().unwrap_or_default();
+                    return Ok(format!("{}\n", value).into_bytes());
+                }
+                _ => warn!("ignoring unexpected message on the stdin channel"),
+            }
+        }
+    }
+}
+
+impl Read for Input {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let count = available.len().min(buf.len());
+        buf[..count].copy_from_slice(&available[..count]);
+        self.consume(count);
+        Ok(count)
+    }
+}
+
+impl BufRead for Input {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.position == self.line.len() {
+            self.line = self
+                .request_line()
+                .map_err(|e| io::Error::other(e.to_string()))?;
+            self.position = 0;
+        }
+        Ok(&self.line[self.position..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.position += amount;
+    }
+}
+
+fn decode_hex(text: &[u8]) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+
+    text.chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+fn kernel_info() -> Value {
+    json!({
+        "status": "ok",
+        "protocol_version": PROTOCOL_VERSION,
+        "implementation": "lox",
+        "implementation_version": env!("CARGO_PKG_VERSION"),
+        "language_info": {
+            "name": "lox",
+            "version": env!("CARGO_PKG_VERSION"),
+            "mimetype": "text/x-lox",
+            "file_extension": ".lox",
+        },
+        "banner": "Lox",
+        "help_links": [],
+    })
+}
+
+fn heartbeat(context: zmq::Context, endpoint: String) -> Result<(), zmq::Error> {
+    let socket = context.socket(zmq::REP)?;
+    socket.bind(&endpoint)?;
+    loop {
+        let ping = socket.recv_bytes(0)?;
+        socket.send(ping, 0)?;
+    }
+}
+
+/// Serves Jupyter kernel requests described by `connection_file` until a
+/// shutdown request arrives.
+pub fn run(connection_file: &Path) -> Result<(), Box<dyn Error>> {
+    let info: ConnectionInfo = serde_json::from_str(&std::fs::read_to_string(connection_file)?)?;
+    let context = zmq::Context::new();
+
+    let hb_context = context.clone();
+    let hb_endpoint = info.endpoint(info.hb_port);
+    thread::spawn(move || heartbeat(hb_context, hb_endpoint));
+
+    let bind = |kind, port| -> Result<zmq::Socket, zmq::Error> {
+        let socket = context.socket(kind)?;
+        socket.bind(&info.endpoint(port))?;
+        Ok(socket)
+    };
+
+    let shell = bind(zmq::ROUTER, info.shell_port)?;
+    let control = bind(zmq::ROUTER, info.control_port)?;
+    let session = Session {
+        key: info.key.clone().into_bytes(),
+        id: uuid::Uuid::new_v4().to_string(),
+    };
+    let input_request = Rc::new(RefCell::new(None));
+
+    let output = SharedBuffer::default();
+    let mut interpreter = Interpreter::with_writer(output.clone());
+    interpreter.set_input(Input {
+        session: session.clone(),
+        socket: bind(zmq::ROUTER, info.stdin_port)?,
+        request: Rc::clone(&input_request),
+        line: Vec::new(),
+        position: 0,
+    });
+
+    let mut kernel = Kernel {
+        session,
+        iopub: bind(zmq::PUB, info.iopub_port)?,
+        interpreter,
+        output,
+        input_request,
+        execution_count: 0,
+    };
+
+    loop {
+        let mut items = [
+            control.as_poll_item(zmq::POLLIN),
+            shell.as_poll_item(zmq::POLLIN),
+        ];
+        zmq::poll(&mut items, -1)?;
+        let ready = [items[0].is_readable(), items[1].is_readable()];
+
+        for (socket, ready) in [&control, &shell].into_iter().zip(ready) {
+            if !ready {
+                continue;
+            }
+
+            if let Some(request) = kernel.session.recv(socket)? {
+                if let Outcome::Shutdown = kernel.handle(socket, request)? {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(key: &str) -> Session {
+        Session {
+            key: key.as_bytes().to_vec(),
+            id: "test".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_signatures_round_trip() {
+        let session = session("secret");
+        let parts: [&[u8]; 4] = [b"{\"msg_type\":\"x\"}", b"{}", b"{}", b"{}"];
+        let signature = session.sign(&parts);
+        assert_eq!(signature.len(), 64);
+        assert!(session.verify(&parts, signature.as_bytes()));
+
+        let tampered: [&[u8]; 4] = [b"{\"msg_type\":\"y\"}", b"{}", b"{}", b"{}"];
+        assert!(!session.verify(&tampered, signature.as_bytes()));
+        assert!(!session.verify(&parts, b"not hex"));
+        assert!(!session.verify(&parts, b""));
+        assert!(!self::session("other").verify(&parts, signature.as_bytes()));
+    }
+
+    #[test]
+    fn test_unsigned_sessions_accept_only_empty_signatures() {
+        let session = session("");
+        let parts: [&[u8]; 4] = [b"{}", b"{}", b"{}", b"{}"];
+        assert_eq!(session.sign(&parts), "");
+        assert!(session.verify(&parts, b""));
+        assert!(!session.verify(&parts, b"00"));
+    }
+
+    #[test]
+    fn test_malformed_messages_are_dropped() {
+        let session = session("secret");
+        let context = zmq::Context::new();
+        let receiver = context.socket(zmq::PAIR).unwrap();
+        receiver.bind("inproc://kernel-test").unwrap();
+        let sender = context.socket(zmq::PAIR).unwrap();
+        sender.connect("inproc://kernel-test").unwrap();
+
+        for content in [&b"{not json"[..], b"{\"code\":\"1;\"}"] {
+            let parts: [&[u8]; 4] = [b"{}", b"{}", b"{}", content];
+            let mut frames = vec![DELIMITER.to_vec(), session.sign(&parts).into_bytes()];
+            frames.extend(parts.iter().map(|part| part.to_vec()));
+            sender.send_multipart(frames, 0).unwrap();
+        }
+
+        assert!(session.recv(&receiver).unwrap().is_none());
+        let message = session.recv(&receiver).unwrap().unwrap();
+        assert_eq!(message.content["code"], "1;");
+    }
+}
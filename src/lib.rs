@@ -0,0 +1,81 @@
+//! A tree-walking interpreter for Lox. The `lox` binary is a thin command
+//! line around it; other programs can embed it:
+//!
+//! ```
+//! let mut interpreter = lox::Interpreter::new();
+//! let answer = interpreter.run("var x = 6; x * 7;").unwrap();
+//! assert_eq!(answer.to_string(), "42");
+//! ```
+//!
+//! What scripts print goes to stdout unless the interpreter is given a
+//! writer of its own:
+//!
+//! ```
+//! let output = lox::SharedBuffer::default();
+//! let mut interpreter = lox::Interpreter::with_writer(output.clone());
+//! interpreter.run("print \"hello\";").unwrap();
+//! assert_eq!(output.take(), "hello\n");
+//! ```
+
+#![cfg_attr(nightly, feature(likely_unlikely))]
+
+pub mod actors;
+pub mod ast;
+pub mod ast_printer;
+pub mod bundle;
+pub mod conversions;
+pub mod crash;
+pub mod diagnostic;
+pub mod difftest;
+pub mod emit;
+pub mod environment;
+pub mod error_value;
+pub mod file_handle;
+pub mod foreign;
+pub mod heap_snapshot;
+pub mod hooks;
+pub mod inline_cache;
+pub mod interpreter;
+#[cfg(feature = "jupyter")]
+pub mod kernel;
+pub mod line_editor;
+pub mod line_index;
+pub mod lint;
+pub mod lox;
+pub mod lox_error;
+pub mod manifest;
+pub mod memory_profile;
+pub mod messages;
+pub mod methods;
+pub mod native_functions;
+pub mod number_format;
+pub mod output;
+pub mod parser;
+pub mod post_mortem;
+pub mod random;
+pub mod replay;
+pub mod resolver;
+pub mod runtime_warnings;
+pub mod scanner;
+pub mod server;
+pub mod stats;
+#[cfg(any(test, feature = "proptest"))]
+pub mod strategies;
+#[cfg(unix)]
+pub mod time_travel;
+pub mod timers;
+pub mod token;
+pub mod token_type;
+pub mod typecheck;
+pub mod value;
+
+pub use crate::conversions::{FromLox, IntoNative};
+pub use crate::interpreter::Interpreter;
+pub use crate::lox::Lox;
+pub use crate::lox_error::LoxError;
+pub use crate::output::SharedBuffer;
+pub use crate::parser::Parser;
+pub use crate::scanner::Scanner;
+/// A Lox value.
+pub use crate::token::Literal as Value;
+pub use crate::token::{Arity, NativeClosure, NativeFn};
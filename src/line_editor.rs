@@ -0,0 +1,152 @@
+//! Reads the REPL's input. At a terminal, lines can be edited, earlier ones
+//! recalled and Tab completes keywords and the names defined so far. Piped
+//! input is read line by line, as it is.
+
+use std::io::{BufRead, IsTerminal, Write};
+
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+
+/// What reading a line found.
+pub enum Input {
+    Line(String),
+    /// Ctrl-C was pressed, dropping whatever had been typed.
+    Interrupted,
+    End,
+}
+
+pub enum LineEditor {
+    Terminal(Box<Editor<Completions, DefaultHistory>>),
+    Piped,
+}
+
+impl Default for LineEditor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LineEditor {
+    pub fn new() -> Self {
+        if std::io::stdin().is_terminal() {
+            if let Ok(mut editor) = Editor::new() {
+                editor.set_helper(Some(Completions::default()));
+                return LineEditor::Terminal(Box::new(editor));
+            }
+        }
+        LineEditor::Piped
+    }
+
+    /// Shows `prompt` and reads a line, offering `candidates` to complete
+    /// words with.
+    pub fn read_line(&mut self, prompt: &str, candidates: Vec<String>) -> Input {
+        match self {
+            LineEditor::Terminal(editor) => {
+                if let Some(completions) = editor.helper_mut() {
+                    completions.candidates = candidates;
+                }
+                match editor.readline(prompt) {
+                    Ok(line) => {
+                        let _ = editor.add_history_entry(line.as_str());
+                        Input::Line(line)
+                    }
+                    Err(ReadlineError::Interrupted) => Input::Interrupted,
+                    Err(_) => Input::End,
+                }
+            }
+            LineEditor::Piped => {
+                print!("{}", prompt);
+                std::io::stdout().flush().unwrap();
+                let mut line = String::new();
+                match std::io::stdin().lock().read_line(&mut line) {
+                    Ok(1..) => Input::Line(line.trim_end_matches(['\n', '\r']).to_string()),
+                    _ => Input::End,
+                }
+            }
+        }
+    }
+}
+
+/// Completes the word before the cursor.
+#[derive(Default)]
+pub struct Completions {
+    /// Every word that can be completed to, sorted.
+    candidates: Vec<String>,
+}
+
+impl Completer for Completions {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(complete(&self.candidates, line, pos))
+    }
+}
+
+impl Hinter for Completions {
+    type Hint = String;
+}
+
+impl Highlighter for Completions {}
+
+impl Validator for Completions {}
+
+impl Helper for Completions {}
+
+/// Where the identifier ending at `pos` in `line` starts, and the
+/// `candidates` it could be completed to.
+fn complete(candidates: &[String], line: &str, pos: usize) -> (usize, Vec<String>) {
+    let start = line[..pos]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| c.is_alphanumeric() || *c == '_')
+        .last()
+        .map_or(pos, |(i, _)| i);
+    let word = &line[start..pos];
+    if word.is_empty() || word.starts_with(|c: char| c.is_ascii_digit()) {
+        return (pos, Vec::new());
+    }
+    let matches = candidates
+        .iter()
+        .filter(|candidate| candidate.starts_with(word))
+        .cloned()
+        .collect();
+    (start, matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_complete_word_before_cursor() {
+        let candidates: Vec<String> = ["clock", "count", "print", "var"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        assert_eq!(
+            complete(&candidates, "pr", 2),
+            (0, vec!["print".to_string()])
+        );
+        assert_eq!(
+            complete(&candidates, "print c", 7),
+            (6, vec!["clock".to_string(), "count".to_string()])
+        );
+        assert_eq!(
+            complete(&candidates, "x = co + 1", 6),
+            (4, vec!["count".to_string()])
+        );
+        assert_eq!(complete(&candidates, "print ", 6), (6, vec![]));
+        assert_eq!(complete(&candidates, "12", 2), (2, vec![]));
+    }
+}
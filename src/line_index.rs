@@ -0,0 +1,29 @@
+use std::cell::OnceCell;
+use std::iter;
+
+/// Byte offsets of the start of every line in a source, computed the first
+/// time a line is looked up so runs without diagnostics never pay for it.
+#[derive(Default)]
+pub struct LineIndex {
+    starts: OnceCell<Vec<usize>>,
+}
+
+impl LineIndex {
+    pub fn reset(&mut self) {
+        self.starts = OnceCell::new();
+    }
+
+    /// Returns the text of the 1-based `line` of `source`, without its line
+    /// ending.
+    pub fn line<'a>(&self, source: &'a str, line: usize) -> Option<&'a str> {
+        let starts = self.starts.get_or_init(|| {
+            iter::once(0)
+                .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+                .collect()
+        });
+
+        let start = *starts.get(line.checked_sub(1)?)?;
+        let end = starts.get(line).map_or(source.len(), |next| next - 1);
+        Some(source[start..end].trim_end_matches('\r'))
+    }
+}
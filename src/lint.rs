@@ -0,0 +1,495 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::ast::{Expr, Stmt};
+use crate::messages;
+use crate::token::Token;
+use crate::token_type::TokenType;
+use crate::typecheck;
+use crate::value;
+
+/// A named check that `lox check` can run over a program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Lint {
+    UnusedVariable,
+    ShadowedVariable,
+    UnreachableCode,
+    ConstantCondition,
+    SelfAssignment,
+    TypeMismatch,
+    /// The rest are only found while the program runs, with
+    /// `--runtime-warnings`.
+    MixedTypeEquality,
+    FunctionCondition,
+    ImplicitNil,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+pub struct Warning {
+    pub lint: Lint,
+    pub level: Level,
+    pub line: usize,
+    message: String,
+    /// How to fix the code, shown below it.
+    pub help: String,
+}
+
+/// Which lints are reported, and whether they fail the check.
+#[derive(Default)]
+pub struct LintConfig {
+    lints: HashMap<Lint, Level>,
+    deny_warnings: bool,
+}
+
+struct Binding {
+    token: Token,
+    used: bool,
+    is_variable: bool,
+}
+
+struct Linter<'a> {
+    config: &'a LintConfig,
+    scopes: Vec<HashMap<String, Binding>>,
+    warnings: Vec<Warning>,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 9] = [
+        Lint::UnusedVariable,
+        Lint::ShadowedVariable,
+        Lint::UnreachableCode,
+        Lint::ConstantCondition,
+        Lint::SelfAssignment,
+        Lint::TypeMismatch,
+        Lint::MixedTypeEquality,
+        Lint::FunctionCondition,
+        Lint::ImplicitNil,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::UnusedVariable => "unused-variable",
+            Lint::ShadowedVariable => "shadowed-variable",
+            Lint::UnreachableCode => "unreachable-code",
+            Lint::ConstantCondition => "constant-condition",
+            Lint::SelfAssignment => "self-assignment",
+            Lint::TypeMismatch => "type-mismatch",
+            Lint::MixedTypeEquality => "mixed-type-equality",
+            Lint::FunctionCondition => "function-condition",
+            Lint::ImplicitNil => "implicit-nil",
+        }
+    }
+}
+
+impl FromStr for Lint {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Lint::ALL
+            .into_iter()
+            .find(|lint| lint.name() == name)
+            .ok_or_else(|| format!("Unknown lint '{}'.", name))
+    }
+}
+
+impl LintConfig {
+    pub fn new(lints: HashMap<Lint, Level>, deny_warnings: bool) -> Self {
+        Self {
+            lints,
+            deny_warnings,
+        }
+    }
+
+    pub fn set_level(&mut self, lint: Lint, level: Level) {
+        self.lints.insert(lint, level);
+    }
+
+    pub fn set_deny_warnings(&mut self, deny_warnings: bool) {
+        self.deny_warnings = deny_warnings;
+    }
+
+    fn level(&self, lint: Lint) -> Level {
+        match self.lints.get(&lint).copied().unwrap_or(Level::Warn) {
+            Level::Warn if self.deny_warnings => Level::Deny,
+            level => level,
+        }
+    }
+}
+
+/// Runs every enabled lint over a parsed program, including type checking.
+pub fn check(statements: &[Stmt], config: &LintConfig) -> Vec<Warning> {
+    let mut linter = Linter {
+        config,
+        scopes: vec![HashMap::new()],
+        warnings: Vec::new(),
+    };
+    linter.statements(statements);
+    linter.warnings.extend(typecheck::check(statements, config));
+    linter.warnings.sort_by_key(|w| w.line);
+    linter.warnings
+}
+
+impl Warning {
+    /// Creates a warning at `token` if `lint` is enabled in `config`.
+    pub fn new(
+        config: &LintConfig,
+        lint: Lint,
+        token: &Token,
+        message: String,
+        help: String,
+    ) -> Option<Self> {
+        Self::at_line(config, lint, token.line, message, help)
+    }
+
+    /// Like `new`, for code that has no token to point at.
+    pub fn at_line(
+        config: &LintConfig,
+        lint: Lint,
+        line: usize,
+        message: String,
+        help: String,
+    ) -> Option<Self> {
+        let level = config.level(lint);
+        (level != Level::Allow).then_some(Warning {
+            lint,
+            level,
+            line,
+            message,
+            help,
+        })
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl Linter<'_> {
+    fn warn(&mut self, lint: Lint, token: &Token, message: String, help: String) {
+        let warning = Warning::new(self.config, lint, token, message, help);
+        self.warnings.extend(warning);
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        let scope = self.scopes.pop().expect("Scope was opened");
+        let mut unused: Vec<_> = scope
+            .into_values()
+            .filter(|b| b.is_variable && !b.used)
+            .collect();
+        unused.sort_by_key(|b| b.token.line);
+        for binding in unused {
+            let name = &binding.token.lexeme;
+            let message = messages::text(messages::UNUSED_VARIABLE, &[name]);
+            let help = messages::text(messages::UNUSED_VARIABLE_HELP, &[name]);
+            self.warn(Lint::UnusedVariable, &binding.token, message, help);
+        }
+    }
+
+    fn declare(&mut self, name: &Token, is_variable: bool) {
+        let outer = self.scopes[..self.scopes.len() - 1]
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&name.lexeme))
+            .map(|binding| binding.token.line);
+        if let Some(line) = outer {
+            let message = messages::text(messages::SHADOWED_VARIABLE, &[&name.lexeme, &line]);
+            let help = messages::text(messages::SHADOWED_VARIABLE_HELP, &[&name.lexeme]);
+            self.warn(Lint::ShadowedVariable, name, message, help);
+        }
+
+        let scope = self.scopes.last_mut().expect("Global scope exists");
+        scope.insert(
+            name.lexeme.clone(),
+            Binding {
+                token: name.clone(),
+                used: false,
+                is_variable,
+            },
+        );
+    }
+
+    fn use_name(&mut self, name: &Token) {
+        let binding = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(&name.lexeme));
+        if let Some(binding) = binding {
+            binding.used = true;
+        }
+    }
+
+    fn function(&mut self, params: &[Token], body: &[Stmt]) {
+        self.begin_scope();
+        for param in params {
+            self.declare(param, false);
+        }
+        self.statements(body);
+        self.end_scope();
+    }
+
+    fn statements(&mut self, statements: &[Stmt]) {
+        let mut exit: Option<&Token> = None;
+        for statement in statements {
+            if let Some(keyword) = exit.take() {
+                let message = messages::text(messages::UNREACHABLE_CODE, &[&keyword.lexeme]);
+                let help = messages::text(messages::UNREACHABLE_CODE_HELP, &[]);
+                self.warn(Lint::UnreachableCode, keyword, message, help);
+                break;
+            }
+            self.statement(statement);
+            exit = match statement {
+                Stmt::Return { keyword, .. }
+                | Stmt::Break { keyword, .. }
+                | Stmt::Continue { keyword, .. }
+                | Stmt::Throw { keyword, .. } => Some(&**keyword),
+                _ => None,
+            };
+        }
+    }
+
+    fn statement(&mut self, statement: &Stmt) {
+        match statement {
+            Stmt::Block { statements } => {
+                self.begin_scope();
+                self.statements(statements);
+                self.end_scope();
+            }
+            Stmt::Break { .. } | Stmt::Continue { .. } => {}
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                ..
+            } => {
+                if let Some(superclass) = superclass {
+                    self.expression(superclass);
+                }
+                self.declare(name, false);
+                for method in methods {
+                    if let Stmt::Function { params, body, .. } = method {
+                        self.function(params, body);
+                    }
+                }
+            }
+            Stmt::Expression { expression } | Stmt::Print { expression, .. } => {
+                self.expression(expression)
+            }
+            Stmt::Function {
+                name, params, body, ..
+            } => {
+                self.declare(name, false);
+                self.function(params, body);
+            }
+            Stmt::If {
+                keyword,
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                if let Some(truthy) = constant_truthiness(condition) {
+                    let (message, help) = if truthy {
+                        (messages::IF_ALWAYS_TRUE, messages::IF_ALWAYS_TRUE_HELP)
+                    } else {
+                        (messages::IF_ALWAYS_FALSE, messages::IF_ALWAYS_FALSE_HELP)
+                    };
+                    let message = messages::text(message, &[]);
+                    let help = messages::text(help, &[]);
+                    self.warn(Lint::ConstantCondition, keyword, message, help);
+                }
+                self.expression(condition);
+                self.statement(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch);
+                }
+            }
+            Stmt::Return { value, .. } => {
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+            }
+            Stmt::Throw { value, .. } => self.expression(value),
+            Stmt::Try {
+                body,
+                name,
+                handler,
+            } => {
+                self.begin_scope();
+                self.statements(body);
+                self.end_scope();
+
+                self.begin_scope();
+                self.declare(name, false);
+                self.statements(handler);
+                self.end_scope();
+            }
+            Stmt::Var {
+                name, initializer, ..
+            } => {
+                if let Some(initializer) = initializer {
+                    self.expression(initializer);
+                }
+                self.declare(name, true);
+            }
+            Stmt::While {
+                keyword,
+                condition,
+                body,
+                increment,
+                ..
+            } => {
+                // `while (true)` is the usual way to write an endless loop
+                if constant_truthiness(condition) == Some(false) {
+                    let message = messages::text(messages::LOOP_ALWAYS_FALSE, &[&keyword.lexeme]);
+                    let help = messages::text(messages::LOOP_ALWAYS_FALSE_HELP, &[]);
+                    self.warn(Lint::ConstantCondition, keyword, message, help);
+                }
+                self.expression(condition);
+                self.statement(body);
+                if let Some(increment) = increment {
+                    self.expression(increment);
+                }
+            }
+        }
+    }
+
+    fn expression(&mut self, expression: &Expr) {
+        match expression {
+            Expr::Assign { name, value, .. } => {
+                if matches!(&**value, Expr::Variable { name: source, .. } if source.lexeme == name.lexeme)
+                {
+                    let message = messages::text(messages::SELF_ASSIGNMENT, &[&name.lexeme]);
+                    let help = messages::text(messages::SELF_ASSIGNMENT_HELP, &[]);
+                    self.warn(Lint::SelfAssignment, name, message, help);
+                }
+                self.expression(value)
+            }
+            Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+                self.expression(left);
+                self.expression(right);
+            }
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                self.expression(callee);
+                for argument in arguments {
+                    self.expression(argument);
+                }
+            }
+            Expr::Get { object, .. } => self.expression(object),
+            Expr::Index { object, index, .. } => {
+                self.expression(object);
+                self.expression(index);
+            }
+            Expr::Ternary {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expression(condition);
+                self.expression(then_branch);
+                self.expression(else_branch);
+            }
+            Expr::Set { object, value, .. } => {
+                self.expression(object);
+                self.expression(value);
+            }
+            Expr::SetIndex {
+                object,
+                index,
+                value,
+                ..
+            } => {
+                self.expression(object);
+                self.expression(index);
+                self.expression(value);
+            }
+            Expr::Grouping { expression }
+            | Expr::Postfix {
+                target: expression, ..
+            }
+            | Expr::Spread { expression, .. }
+            | Expr::Unary {
+                right: expression, ..
+            } => self.expression(expression),
+            Expr::Lambda { params, body, .. } => self.function(params, body),
+            Expr::List { elements } => {
+                for element in elements {
+                    self.expression(element);
+                }
+            }
+            Expr::Map { entries, .. } => {
+                for (key, value) in entries {
+                    self.expression(key);
+                    self.expression(value);
+                }
+            }
+            Expr::Literal { .. } | Expr::Super { .. } | Expr::This { .. } => {}
+            Expr::Variable { name, .. } => self.use_name(name),
+        }
+    }
+}
+
+/// The truthiness of `condition`, if it is known without running the program.
+fn constant_truthiness(condition: &Expr) -> Option<bool> {
+    match condition {
+        Expr::Literal { value } => Some(value::is_truthy(value)),
+        Expr::Grouping { expression } => constant_truthiness(expression),
+        Expr::Unary { operator, right } if operator.type_ == TokenType::Bang => {
+            constant_truthiness(right).map(|truthy| !truthy)
+        }
+        Expr::Logical {
+            left,
+            operator,
+            right,
+        } => match (&operator.type_, constant_truthiness(left)?) {
+            (TokenType::Or, true) => Some(true),
+            (TokenType::And, false) => Some(false),
+            _ => constant_truthiness(right),
+        },
+        Expr::Ternary {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            if constant_truthiness(condition)? {
+                constant_truthiness(then_branch)
+            } else {
+                constant_truthiness(else_branch)
+            }
+        }
+        _ => None,
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.level {
+            Level::Deny => "Error",
+            _ => "Warning",
+        };
+        write!(
+            f,
+            "[line {}] {} ({}): {}",
+            self.line,
+            kind,
+            self.lint.name(),
+            self.message
+        )
+    }
+}
@@ -1,55 +1,572 @@
-use std::io::{BufRead, Write};
+use std::cell::RefCell;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, info_span};
 
+use crate::ast::{Expr, Stmt};
+use crate::ast_printer;
+use crate::crash;
+use crate::diagnostic::{self, Diagnostic, ErrorFormat};
+use crate::emit::{self, Artifact};
+use crate::heap_snapshot::{self, Snapshot};
 use crate::interpreter::Interpreter;
-use crate::lox_error::LoxError;
+use crate::line_editor::{Input, LineEditor};
+use crate::line_index::LineIndex;
+use crate::lint::{self, Level, Lint, LintConfig, Warning};
+use crate::lox_error::{IoError, IoErrors, LoxError, TypeError};
+use crate::memory_profile::MemoryProfile;
 use crate::parser::Parser;
+use crate::post_mortem::{self, Failure};
+use crate::replay::Tape;
+use crate::resolver::{self, Definition};
 use crate::scanner::Scanner;
+use crate::stats::Stats;
+use crate::token::{Literal, Token};
+use crate::token_type::TokenType;
+use crate::typecheck;
+
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How `Lox::run_files` keeps the files it runs apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IsolationMode {
+    /// Each file sees the globals the files before it defined.
+    SharedGlobals,
+    /// Each file starts without any globals, as if it ran on its own.
+    FreshEnvironment,
+}
 
 pub struct Lox {
     interpreter: Interpreter,
+    scanner: Scanner,
+    parser: Parser,
+    line_index: LineIndex,
+    /// The runtime error the last run stopped with, for `:debug`.
+    failure: Option<Failure>,
+    /// How type annotations are checked before each run, or `None` to
+    /// ignore them.
+    type_checking: Option<LintConfig>,
+    /// What `compile` writes next to `script`, for `--emit`.
+    emit: Vec<Artifact>,
+    /// The file being run, if the source came from one.
+    script: Option<PathBuf>,
+    /// Whether source that is a single expression is run by printing its
+    /// value, as the REPL does.
+    echo: bool,
+    /// Where errors and warnings are reported.
+    errors: RefCell<Box<dyn Write>>,
+}
+
+impl Default for Lox {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Lox {
     pub fn new() -> Self {
+        Self::with_interpreter(Interpreter::new())
+    }
+
+    pub fn with_interpreter(interpreter: Interpreter) -> Self {
         Self {
-            interpreter: Interpreter::new(),
+            interpreter,
+            scanner: Scanner::new(""),
+            parser: Parser::new(&[]),
+            line_index: LineIndex::default(),
+            failure: None,
+            type_checking: None,
+            emit: Vec::new(),
+            script: None,
+            echo: false,
+            errors: RefCell::new(Box::new(io::stderr())),
+        }
+    }
+
+    /// Reports errors and warnings to `writer` instead of stderr.
+    pub fn set_error_writer<W: Write + 'static>(&mut self, writer: W) {
+        self.errors = RefCell::new(Box::new(writer));
+    }
+
+    pub fn memory_profile(&self) -> Option<&MemoryProfile> {
+        self.interpreter.memory_profile()
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.interpreter.stats
+    }
+
+    pub fn set_max_errors(&mut self, max_errors: usize) {
+        self.parser.set_max_errors(max_errors);
+    }
+
+    /// Checks type annotations before each run, reporting mismatches at
+    /// `level`. At `Level::Deny` a mismatch stops the program from running.
+    pub fn set_type_checking(&mut self, level: Option<Level>) {
+        self.type_checking = level.map(|level| {
+            let mut config = LintConfig::default();
+            config.set_level(Lint::TypeMismatch, level);
+            config
+        });
+    }
+
+    pub fn heap_snapshot(&self) -> Snapshot {
+        heap_snapshot::take(&self.interpreter)
+    }
+
+    /// Reports suspicious operations while programs run, at the levels in
+    /// `config`.
+    pub fn set_runtime_warnings(&mut self, config: Option<LintConfig>) {
+        self.interpreter.set_runtime_warnings(config);
+    }
+
+    pub fn tape(&self) -> &Tape {
+        &self.interpreter.tape
+    }
+
+    pub fn failure(&self) -> Option<&Failure> {
+        self.failure.as_ref()
+    }
+
+    /// Returns the text of `line` in the source that was last run.
+    pub fn source_line(&self, line: usize) -> Option<&str> {
+        self.line_index.line(self.scanner.source(), line)
+    }
+
+    pub fn run_file(&mut self, path: &Path) -> Result<(), LoxError> {
+        let contents = fs::read_to_string(path).map_err(|e| IoError::new(path, &e))?;
+        self.script = Some(path.to_path_buf());
+        self.run_script(&contents)
+    }
+
+    /// Runs `paths` in order in this session, stopping at the first one that
+    /// fails. Nothing runs unless every file can be read, and each one that
+    /// can't is part of the error.
+    pub fn run_files(
+        &mut self,
+        paths: &[PathBuf],
+        isolation: IsolationMode,
+    ) -> Result<(), LoxError> {
+        let mut sources = Vec::new();
+        let mut failures = Vec::new();
+        for path in paths {
+            match fs::read_to_string(path) {
+                Ok(source) => sources.push((path, source)),
+                Err(e) => failures.push(IoError::new(path, &e)),
+            }
+        }
+        if !failures.is_empty() {
+            return Err(LoxError::Io(IoErrors::new(failures)));
         }
+
+        for (path, source) in sources {
+            if isolation == IsolationMode::FreshEnvironment {
+                self.interpreter.reset_globals();
+            }
+            self.script = Some(path.to_path_buf());
+            self.run_script(&source)?;
+        }
+        Ok(())
     }
 
-    pub fn run_file(&mut self, path: &std::path::Path) -> Result<(), LoxError> {
-        let contents = std::fs::read_to_string(path).expect("Failed to read source");
-        self.run(&contents)
+    /// Writes these artifacts next to each file run from now on.
+    pub fn set_emit(&mut self, emit: Vec<Artifact>) {
+        self.emit = emit;
+    }
+
+    /// Where to write `artifact` of the current script, if it was asked for.
+    fn artifact_path(&self, artifact: Artifact) -> Option<PathBuf> {
+        let script = self.script.as_deref()?;
+        self.emit.contains(&artifact).then(|| artifact.path(script))
+    }
+
+    /// Runs a whole script, on an async runtime if async natives are enabled.
+    pub fn run_script(&mut self, source: &str) -> Result<(), LoxError> {
+        #[cfg(feature = "async")]
+        {
+            tokio::runtime::Runtime::new()
+                .expect("Failed to start async runtime")
+                .block_on(self.run_async(source))
+        }
+        #[cfg(not(feature = "async"))]
+        {
+            self.run(source)
+        }
     }
 
     pub fn run_prompt(&mut self) -> Result<(), LoxError> {
-        let stdin = std::io::stdin();
-        let mut stdout = std::io::stdout();
+        let mut editor = LineEditor::new();
+        self.echo = true;
 
-        print!("> ");
-        stdout.flush().unwrap();
+        // Lines are gathered until they make a complete statement, or until
+        // an empty line gives up on completing it
+        let mut source = String::new();
+        loop {
+            let prompt = if source.is_empty() { "> " } else { ".. " };
+            let line = match editor.read_line(prompt, self.completions()) {
+                Input::Line(line) => line,
+                Input::Interrupted => {
+                    source.clear();
+                    continue;
+                }
+                Input::End => break,
+            };
+            source.push_str(&line);
+            source.push('\n');
 
-        for line in stdin.lock().lines() {
-            if let Ok(line) = line {
-                if let Err(e) = self.run(&line) {
-                    eprintln!("{}", e);
-                };
-            } else {
-                break;
+            if source.trim() == ":debug" {
+                match &self.failure {
+                    Some(failure) => failure.inspect(std::io::stdin().lock()),
+                    None => eprintln!("No runtime error to inspect."),
+                }
+            } else if source.trim() == ":env" {
+                post_mortem::print_scopes(&self.interpreter.environment);
+            } else if let Some(name) = source.trim().strip_prefix(":doc ") {
+                self.print_doc(name.trim());
+            } else if !line.trim().is_empty() && self.is_incomplete(&source) {
+                continue;
+            } else if let Err(e) = self.run(&source) {
+                if let LoxError::Exit(_) = e {
+                    return Err(e);
+                }
+                eprintln!("{}", e);
+                if let LoxError::Runtime(_) = e {
+                    eprintln!("Type :debug to inspect it.");
+                }
             }
-            print!("> ");
-            stdout.flush().unwrap();
+            source.clear();
         }
         Ok(())
     }
 
-    fn run(&mut self, source: &str) -> Result<(), LoxError> {
-        let mut scanner = Scanner::new(source);
-        let tokens = scanner.scan_tokens()?;
-        let mut parser = Parser::new(&tokens);
+    /// The words the REPL completes: keywords and every name in scope.
+    fn completions(&self) -> Vec<String> {
+        let mut words = self.interpreter.environment.names();
+        words.extend(self.scanner.keywords().map(str::to_string));
+        words.sort();
+        words
+    }
 
-        let statements = parser.parse()?;
-        self.interpreter.interpret(statements)?;
+    /// Whether `source` is the start of a statement that needs more lines.
+    fn is_incomplete(&mut self, source: &str) -> bool {
+        matches!(self.compile(source), Err(e) if e.is_incomplete())
+    }
 
+    pub fn run(&mut self, source: &str) -> Result<(), LoxError> {
+        let statements = self.compile(source)?;
+        self.typecheck(&statements)?;
+        let result = info_span!("interpret")
+            .in_scope(|| self.interpreter.interpret(statements))
+            .map_err(|e| self.with_source(e));
+        self.print_runtime_warnings();
+        self.record_failure(&result);
+        result
+    }
+
+    /// Prints what type checking finds in `statements`, failing if any of
+    /// it is an error.
+    fn typecheck(&self, statements: &[Stmt]) -> Result<(), LoxError> {
+        let Some(config) = &self.type_checking else {
+            return Ok(());
+        };
+        let warnings = info_span!("typecheck").in_scope(|| typecheck::check(statements, config));
+        for warning in &warnings {
+            self.print_warning(warning);
+        }
+
+        let errors = warnings.iter().filter(|w| w.level == Level::Deny).count();
+        if errors > 0 {
+            return Err(TypeError::new(errors).into());
+        }
         Ok(())
     }
+
+    /// Prints `warning` with the source line it points at and its help.
+    pub fn print_warning(&self, warning: &Warning) {
+        let mut errors = self.errors.borrow_mut();
+        if diagnostic::format() == ErrorFormat::Json {
+            let _ = writeln!(errors, "{}", Diagnostic::from(warning).to_json());
+            return;
+        }
+        let _ = writeln!(errors, "{}", warning);
+        if let Some(text) = self.source_line(warning.line) {
+            let _ = writeln!(errors, "    | {}", text);
+        }
+        let _ = writeln!(errors, "    = help: {}", warning.help);
+    }
+
+    fn print_runtime_warnings(&mut self) {
+        for warning in self.interpreter.take_warnings() {
+            self.print_warning(&warning);
+        }
+    }
+
+    /// Prints `error` with the source it points at and the calls it unwound
+    /// through, or as diagnostics for tools.
+    pub fn report(&self, error: &LoxError) {
+        let mut errors = self.errors.borrow_mut();
+        match diagnostic::format() {
+            ErrorFormat::Human => {
+                let _ = writeln!(errors, "{}", error);
+            }
+            ErrorFormat::Json => {
+                for diagnostic in Diagnostic::all(error) {
+                    let _ = writeln!(errors, "{}", diagnostic.to_json());
+                }
+            }
+        }
+    }
+
+    /// Prints the signature and documentation of the global `name`.
+    fn print_doc(&self, name: &str) {
+        let token = Token::new(TokenType::Identifier, name, None, 0);
+        match self.interpreter.environment.get(&token) {
+            Ok(Literal::Callable(callable)) => println!("{}", callable.help()),
+            Ok(_) => eprintln!("'{}' is not a function.", name),
+            Err(LoxError::Runtime(e)) => eprintln!("{}", e.message()),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    /// Keeps a runtime error and the scope it was raised in for `:debug`.
+    fn record_failure(&mut self, result: &Result<(), LoxError>) {
+        self.failure = match result {
+            Err(LoxError::Runtime(error)) => {
+                self.interpreter.take_error_scope().map(|scope| Failure {
+                    error: error.clone(),
+                    scope,
+                })
+            }
+            _ => None,
+        };
+    }
+
+    #[cfg(feature = "async")]
+    pub async fn run_async(&mut self, source: &str) -> Result<(), LoxError> {
+        use tracing::Instrument;
+
+        let statements = self.compile(source)?;
+        self.typecheck(&statements)?;
+        let result = self
+            .interpreter
+            .run_async(statements)
+            .instrument(info_span!("interpret"))
+            .await
+            .map_err(|e| self.with_source(e));
+        self.print_runtime_warnings();
+        self.record_failure(&result);
+        result
+    }
+
+    /// Parses `source` and renders each statement's syntax tree on a line.
+    pub fn ast(&mut self, source: &str) -> Result<String, LoxError> {
+        let statements = self.compile(source)?;
+        Ok(statements
+            .iter()
+            .map(|stmt| ast_printer::print_stmt(stmt) + "\n")
+            .collect())
+    }
+
+    /// Parses `source` and finds the declaration of every variable it uses.
+    pub fn definitions(&mut self, source: &str) -> Result<Vec<Definition>, LoxError> {
+        let mut statements = self.compile(source)?;
+        Ok(resolver::definitions(
+            &mut statements,
+            self.interpreter.capture_by_iteration(),
+        ))
+    }
+
+    /// Parses `source` and runs the enabled lints over it without executing it.
+    pub fn check(&mut self, source: &str, config: &LintConfig) -> Result<Vec<Warning>, LoxError> {
+        let statements = self.compile(source)?;
+        Ok(info_span!("lint").in_scope(|| lint::check(&statements, config)))
+    }
+
+    /// Re-defines the functions in `source`, keeping the values of globals
+    /// that are already defined.
+    pub fn reload(&mut self, source: &str) -> Result<(), LoxError> {
+        let statements = self.compile(source)?;
+        info_span!("reload").in_scope(|| self.interpreter.reload(statements))
+    }
+
+    /// Polls `path` and reloads it whenever it changes. Never returns.
+    pub fn watch(&mut self, path: &Path) -> ! {
+        let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+        let mut last = modified(path);
+
+        loop {
+            thread::sleep(WATCH_INTERVAL);
+            let current = modified(path);
+            if current.is_none() || current == last {
+                continue;
+            }
+            last = current;
+
+            // Editors may replace the file in several steps, so a failed
+            // read is retried at the next change.
+            let Ok(source) = fs::read_to_string(path) else {
+                continue;
+            };
+            match self.reload(&source) {
+                Ok(()) => eprintln!("Reloaded {}", path.display()),
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+    }
+
+    pub fn compile(&mut self, source: &str) -> Result<Vec<Stmt>, LoxError> {
+        self.parse(source).map_err(|e| self.with_source(e))
+    }
+
+    /// Gives `error` the source lines it points at, to show with it.
+    fn with_source(&self, error: LoxError) -> LoxError {
+        error.with_source(|line| self.source_line(line))
+    }
+
+    fn parse(&mut self, source: &str) -> Result<Vec<Stmt>, LoxError> {
+        self.line_index.reset();
+        crash::set_source(source);
+        let tokens_path = self.artifact_path(Artifact::Tokens);
+        let tokens = info_span!("scan").in_scope(|| {
+            self.scanner.reset(source);
+            let tokens = self.scanner.scan_tokens()?;
+            debug!(tokens = tokens.len(), "scanned source");
+            Ok::<_, LoxError>(tokens)
+        })?;
+        if let Some(path) = tokens_path {
+            emit::write(&path, &emit::tokens(tokens));
+        }
+
+        let mut statements = info_span!("parse").in_scope(|| {
+            self.parser.reset(tokens);
+            let statements = match self.parser.parse() {
+                Err(e) if self.echo => {
+                    self.parser.reset(tokens);
+                    let expression = self.parser.parse_expression().map_err(|_| e)?;
+                    vec![echo(expression)]
+                }
+                statements => statements?,
+            };
+            debug!(statements = statements.len(), "parsed program");
+            Ok::<_, LoxError>(statements)
+        })?;
+        info_span!("resolve").in_scope(|| {
+            resolver::resolve(&mut statements, self.interpreter.capture_by_iteration())
+        });
+        if let Some(path) = self.artifact_path(Artifact::Ast) {
+            let tree: String = statements
+                .iter()
+                .map(|stmt| ast_printer::print_stmt(stmt) + "\n")
+                .collect();
+            emit::write(&path, &tree);
+        }
+
+        Ok(statements)
+    }
+}
+
+/// A statement printing the value of `expression`.
+fn echo(expression: Expr) -> Stmt {
+    let line = expression.line().unwrap_or(1);
+    Stmt::Print {
+        keyword: Box::new(Token::new(TokenType::Print, "print", None, line)),
+        expression: Box::new(expression),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::SharedBuffer;
+
+    #[test]
+    fn test_reload_keeps_globals() {
+        let output = SharedBuffer::default();
+        let mut lox = Lox::with_interpreter(Interpreter::with_writer(output.clone()));
+
+        lox.run("var count = 1; fun get() { return count; } print get();")
+            .unwrap();
+        lox.reload("var count = 100; var extra = 5; fun get() { return count + extra; } print 0;")
+            .unwrap();
+        lox.run("print get();").unwrap();
+
+        assert_eq!(output.take(), "1\n6\n");
+    }
+
+    #[test]
+    fn test_incomplete_input() {
+        let mut lox = Lox::new();
+        assert!(lox.is_incomplete("fun f() {\n  print 1;\n"));
+        assert!(lox.is_incomplete("var x = [1,\n"));
+        assert!(!lox.is_incomplete("fun f() {\n  print 1;\n}\n"));
+        assert!(!lox.is_incomplete("print 1 +;\n"));
+    }
+
+    #[test]
+    fn test_echo_expressions() {
+        let output = SharedBuffer::default();
+        let mut lox = Lox::with_interpreter(Interpreter::with_writer(output.clone()));
+        lox.echo = true;
+
+        lox.run("var x = 2;").unwrap();
+        lox.run("x * 21").unwrap();
+        lox.run("\"a\" + \"b\"\n").unwrap();
+        assert!(lox.run("x x").is_err());
+        assert!(!lox.is_incomplete("x"));
+
+        assert_eq!(output.take(), "42\nab\n");
+    }
+
+    #[test]
+    fn test_run_files_isolation() {
+        let dir = std::env::temp_dir().join(format!("lox-run-files-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let define = dir.join("define.lox");
+        let read = dir.join("read.lox");
+        let native = dir.join("native.lox");
+        fs::write(&define, "var shared = \"from define\";\nclock = 1;").unwrap();
+        fs::write(&read, "print shared;").unwrap();
+        fs::write(&native, "print clock;").unwrap();
+        let paths = [define.clone(), read];
+
+        let output = SharedBuffer::default();
+        let mut lox = Lox::with_interpreter(Interpreter::with_writer(output.clone()));
+        lox.run_files(&paths, IsolationMode::SharedGlobals).unwrap();
+        assert_eq!(output.take(), "from define\n");
+
+        let result = lox.run_files(&paths, IsolationMode::FreshEnvironment);
+        assert!(matches!(result, Err(LoxError::Runtime(_))));
+
+        // A reassigned native is only shadowed in the file that did it
+        lox.run_files(&[define, native], IsolationMode::FreshEnvironment)
+            .unwrap();
+        assert_eq!(output.take(), "callable(0)\n");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_run_missing_file() {
+        let mut lox = Lox::new();
+        let result = lox.run_file(Path::new("/nonexistent/missing.lox"));
+        assert!(matches!(result, Err(LoxError::Io(_))));
+
+        let output = SharedBuffer::default();
+        let mut lox = Lox::with_interpreter(Interpreter::with_writer(output.clone()));
+        let dir = std::env::temp_dir().join(format!("lox-missing-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let present = dir.join("present.lox");
+        fs::write(&present, "print \"ran\";").unwrap();
+        let paths = [dir.join("a.lox"), present, dir.join("b.lox")];
+        let Err(LoxError::Io(errors)) = lox.run_files(&paths, IsolationMode::SharedGlobals) else {
+            panic!("Missing files should fail the run");
+        };
+        assert_eq!(errors.iter().count(), 2);
+        assert_eq!(output.take(), "");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
 }
@@ -1,24 +1,73 @@
 use std::error::Error;
 use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
 
-use crate::token::{Literal, Token};
+use crate::messages::{self, Message};
+use crate::token::{Literal, Span, Token};
 use crate::token_type::TokenType;
 
 #[derive(Debug, Clone)]
 pub struct ParserError {
     token: Token,
     message: String,
+    code: &'static str,
+    /// The source line the error is on, once it is known.
+    snippet: Option<Box<str>>,
 }
 
+/// Every error found while parsing a program, in source order.
 #[derive(Debug, Clone)]
+pub struct ParserErrors {
+    errors: Vec<ParserError>,
+    truncated: bool,
+}
+
+#[derive(Clone)]
 pub struct RuntimeError {
-    token: Token,
+    span: Span,
     message: String,
+    /// The code of the catalog message, for errors that have one.
+    code: Option<&'static str>,
+    snippet: Option<Box<str>>,
+    /// Lox function calls the error unwound through, innermost first.
+    trace: Vec<Frame>,
+    /// The value given to `throw`, or nil.
+    payload: Box<Literal>,
+}
+
+/// A Lox function call on the stack when an error was raised.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub function: String,
+    /// The line the function was called from.
+    pub line: usize,
+}
+
+/// How many frames of a recursion are shown before the rest are summarized.
+const RECURSION_SHOWN: usize = 3;
+/// Recursion through this many frames or more is summarized.
+const DEEP_RECURSION: usize = 16;
+/// The most functions taking turns that are still recognized as recursion.
+const MAX_CYCLE: usize = 4;
+
+/// A line of a trace as shown to the user.
+pub enum TraceLine<'a> {
+    Frame(&'a Frame),
+    /// Frames left out of a deep recursion.
+    Omitted(String),
 }
 
 #[derive(Debug, Clone)]
 pub struct ScannerError {
-    line: usize,
+    span: Span,
+    message: String,
+    code: &'static str,
+    snippet: Option<Box<str>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LimitError {
     message: String,
 }
 
@@ -27,6 +76,61 @@ pub struct ReturnError {
     pub value: Literal,
 }
 
+/// Raised by native functions and turned into a RuntimeError at the call site.
+#[derive(Debug, Clone)]
+pub struct NativeError {
+    message: String,
+    code: Option<&'static str>,
+}
+
+/// A source file couldn't be read.
+#[derive(Debug, Clone)]
+pub struct IoError {
+    path: PathBuf,
+    /// What the operating system said went wrong.
+    reason: String,
+}
+
+/// Every source file of a run that couldn't be read.
+#[derive(Debug, Clone)]
+pub struct IoErrors {
+    errors: Vec<IoError>,
+}
+
+/// Type checking found errors, which have already been reported.
+#[derive(Debug, Clone)]
+pub struct TypeError {
+    count: usize,
+}
+
+/// Unwinds to the innermost loop, or to the loop with the given label.
+#[derive(Debug, Clone)]
+pub struct BreakError {
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ContinueError {
+    pub label: Option<String>,
+}
+
+/// Raised by `exit`, unwinding the whole program so the driver can end the
+/// process with `code`.
+#[derive(Debug, Clone)]
+pub struct ExitError {
+    pub code: u8,
+}
+
+impl fmt::Debug for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RuntimeError")
+            .field("span", &self.span)
+            .field("message", &self.message)
+            .field("trace", &self.trace)
+            .finish_non_exhaustive()
+    }
+}
+
 impl fmt::Debug for ReturnError {
     fn fmt(&self, _f: &mut fmt::Formatter) -> fmt::Result {
         unreachable!();
@@ -35,66 +139,445 @@ impl fmt::Debug for ReturnError {
 
 #[derive(Debug, Clone)]
 pub enum LoxError {
-    Parser(ParserError),
+    Parser(ParserErrors),
     Runtime(RuntimeError),
     Scanner(ScannerError),
+    Limit(LimitError),
+    Type(TypeError),
+    Io(IoErrors),
+    Native(NativeError),
     Return(ReturnError),
+    Break(BreakError),
+    Continue(ContinueError),
+    Exit(ExitError),
+}
+
+impl LoxError {
+    /// Keeps the source lines the error points at, as `source_line` gives
+    /// them, so they are shown with it. A runtime error keeps the line it
+    /// was first given, since it may have been raised in another source.
+    pub fn with_source<'a>(mut self, source_line: impl Fn(usize) -> Option<&'a str>) -> Self {
+        let snippet = |span: Span| source_line(span.line).map(Box::from);
+        match &mut self {
+            LoxError::Parser(e) => {
+                for error in &mut e.errors {
+                    error.snippet = snippet(error.span());
+                }
+            }
+            LoxError::Runtime(e) if e.snippet.is_none() => e.snippet = snippet(e.span),
+            LoxError::Scanner(e) => e.snippet = snippet(e.span),
+            _ => {}
+        }
+        self
+    }
+
+    /// Whether the source just ended too soon, so that more of it could
+    /// still make a valid program, like an unclosed block.
+    pub fn is_incomplete(&self) -> bool {
+        match self {
+            LoxError::Parser(e) => e
+                .errors
+                .first()
+                .is_some_and(|error| error.token.type_ == TokenType::Eof),
+            _ => false,
+        }
+    }
 }
 
 impl ParserError {
-    pub fn new(token: &Token, message: &str) -> Self {
+    pub fn new(token: &Token, message: Message, args: &[&dyn fmt::Display]) -> Self {
         Self {
             token: token.clone(),
-            message: message.to_string(),
+            message: messages::text(message, args),
+            code: message.code(),
+            snippet: None,
         }
     }
+
+    pub fn line(&self) -> usize {
+        self.token.line
+    }
+
+    pub fn span(&self) -> Span {
+        self.token.span()
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+}
+
+impl ParserErrors {
+    /// `truncated` is set when parsing stopped early because there were
+    /// too many errors.
+    pub fn new(errors: Vec<ParserError>, truncated: bool) -> Self {
+        Self { errors, truncated }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ParserError> {
+        self.errors.iter()
+    }
+}
+
+impl IntoIterator for ParserErrors {
+    type Item = ParserError;
+    type IntoIter = std::vec::IntoIter<ParserError>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.errors.into_iter()
+    }
 }
 
 impl RuntimeError {
     pub fn new(token: &Token, message: &str) -> Self {
+        Self::thrown(token, message, Literal::None)
+    }
+
+    /// An error at `token` with a message from the catalog.
+    pub fn from_message(token: &Token, message: Message, args: &[&dyn fmt::Display]) -> Self {
         Self {
-            token: token.clone(),
+            code: Some(message.code()),
+            ..Self::new(token, &messages::text(message, args))
+        }
+    }
+
+    /// An error with no token to point at, only the line it happened on.
+    pub fn at_line(line: usize, message: &str) -> Self {
+        Self {
+            span: Span::line(line),
             message: message.to_string(),
+            code: None,
+            snippet: None,
+            trace: Vec::new(),
+            payload: Box::new(Literal::None),
         }
     }
-}
 
-impl ScannerError {
-    pub fn new(line: usize, message: &str) -> Self {
+    /// An error raised by a `throw` statement carrying `payload`.
+    pub fn thrown(token: &Token, message: &str, payload: Literal) -> Self {
+        Self {
+            span: token.span(),
+            message: message.to_string(),
+            code: None,
+            snippet: None,
+            trace: Vec::new(),
+            payload: Box::new(payload),
+        }
+    }
+
+    pub fn from_native(token: &Token, err: NativeError) -> Self {
         Self {
+            code: err.code,
+            ..Self::new(token, &err.message)
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn line(&self) -> usize {
+        self.span.line
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn code(&self) -> Option<&'static str> {
+        self.code
+    }
+
+    pub fn trace(&self) -> &[Frame] {
+        &self.trace
+    }
+
+    pub fn payload(&self) -> &Literal {
+        &self.payload
+    }
+
+    /// The trace as lines to show the user, innermost call first, with each
+    /// deep recursion cut down to its innermost frames, a count of the ones
+    /// left out and the call that started it.
+    pub fn trace_lines(&self) -> Vec<TraceLine<'_>> {
+        let mut lines = Vec::new();
+        let mut start = 0;
+        while start < self.trace.len() {
+            let frames = &self.trace[start..];
+            let Some((cycle, length)) = recursion(frames) else {
+                lines.push(TraceLine::Frame(&frames[0]));
+                start += 1;
+                continue;
+            };
+
+            let shown = RECURSION_SHOWN.max(cycle);
+            lines.extend(frames[..shown].iter().map(TraceLine::Frame));
+            let mut functions: Vec<&str> = Vec::new();
+            for frame in &frames[..cycle] {
+                if !functions.contains(&frame.function.as_str()) {
+                    functions.push(&frame.function);
+                }
+            }
+            let message = if functions.len() == 1 {
+                messages::FRAMES_OMITTED
+            } else {
+                messages::FRAMES_OMITTED_MUTUAL
+            };
+            let omitted = length - shown - 1;
+            let names = functions.join(", ");
+            lines.push(TraceLine::Omitted(messages::text(
+                message,
+                &[&omitted, &names],
+            )));
+            lines.push(TraceLine::Frame(&frames[length - 1]));
+            start += length;
+        }
+        lines
+    }
+
+    /// Records that the error unwound out of a call to `function` made on `line`.
+    pub fn push_frame(&mut self, function: &str, line: usize) {
+        self.trace.push(Frame {
+            function: function.to_string(),
             line,
+        });
+    }
+}
+
+/// If `frames` start with a deep recursion, the number of calls it cycles
+/// through and how many frames it spans.
+fn recursion(frames: &[Frame]) -> Option<(usize, usize)> {
+    (1..=MAX_CYCLE).find_map(|cycle| {
+        let repeats = (cycle..frames.len())
+            .take_while(|&i| frames[i].function == frames[i - cycle].function)
+            .count();
+        let length = cycle + repeats;
+        (length >= DEEP_RECURSION).then_some((cycle, length))
+    })
+}
+
+impl LimitError {
+    pub fn new(message: &str) -> Self {
+        Self {
             message: message.to_string(),
         }
     }
 }
 
+impl IoError {
+    pub fn new(path: &Path, error: &io::Error) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            reason: error.to_string(),
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        messages::CANNOT_READ_FILE.code()
+    }
+}
+
+impl IoErrors {
+    pub fn new(errors: Vec<IoError>) -> Self {
+        Self { errors }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &IoError> {
+        self.errors.iter()
+    }
+}
+
+impl TypeError {
+    pub fn new(count: usize) -> Self {
+        Self { count }
+    }
+}
+
+impl NativeError {
+    pub fn new(message: &str) -> Self {
+        Self {
+            message: message.to_string(),
+            code: None,
+        }
+    }
+
+    /// An error with a message from the catalog.
+    pub fn from_message(message: Message, args: &[&dyn fmt::Display]) -> Self {
+        Self {
+            message: messages::text(message, args),
+            code: Some(message.code()),
+        }
+    }
+}
+
+impl ScannerError {
+    pub fn new(span: Span, message: Message) -> Self {
+        Self {
+            span,
+            message: messages::text(message, &[]),
+            code: message.code(),
+            snippet: None,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+}
+
 impl fmt::Display for ParserError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        if self.token.type_ == TokenType::Eof {
-            write!(
-                f,
-                "[line {}] Error at end: {}",
-                self.token.line, self.message
-            )
+        let label = if self.token.type_ == TokenType::Eof {
+            write!(f, "[{}] Error at end: {}", self.span(), self.message)?;
+            messages::LABEL_END_OF_INPUT
         } else {
             write!(
                 f,
-                "[line {}] Error at '{}': {}",
-                self.token.line, self.token.lexeme, self.message
-            )
+                "[{}] Error at '{}': {}",
+                self.span(),
+                self.token.lexeme,
+                self.message
+            )?;
+            messages::LABEL_UNEXPECTED
+        };
+        match &self.snippet {
+            Some(text) => write_snippet(f, text, self.span(), &messages::text(label, &[])),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Writes `text`, the source line `span` is on, with `span` underlined and
+/// labelled. A span without a column only gets its line shown.
+fn write_snippet(f: &mut fmt::Formatter, text: &str, span: Span, label: &str) -> fmt::Result {
+    if text.trim().is_empty() {
+        return Ok(());
+    }
+    write!(f, "\n    | {}", text)?;
+    if span.column == 0 {
+        return Ok(());
+    }
+
+    // Tabs are kept, so the carets line up however wide they are shown
+    let indent: String = text
+        .chars()
+        .take(span.column - 1)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let start = text
+        .char_indices()
+        .nth(span.column - 1)
+        .map_or(text.len(), |(i, _)| i);
+    // A span running past the end of the line, like a multi-line string,
+    // is underlined to the end of it
+    let end = (start + span.end - span.start).min(text.len());
+    let width = text.get(start..end).map_or(0, |s| s.chars().count()).max(1);
+    write!(f, "\n    | {}{} {}", indent, "^".repeat(width), label)
+}
+
+impl fmt::Display for ParserErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, error) in self.errors.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", error)?;
         }
+        if self.truncated {
+            write!(f, "\n{}", messages::text(messages::TOO_MANY_ERRORS, &[]))?;
+        }
+        Ok(())
     }
 }
 
 impl fmt::Display for RuntimeError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}\n[line {}]", self.message, self.token.line,)
+        write!(f, "{}\n[{}]", self.message, self.span)?;
+        if let Some(text) = &self.snippet {
+            let label = messages::text(messages::LABEL_RAISED, &[]);
+            write_snippet(f, text, self.span, &label)?;
+        }
+        for line in self.trace_lines() {
+            write!(f, "\n  {}", line)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (line {})", self.function, self.line)
+    }
+}
+
+impl fmt::Display for TraceLine<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TraceLine::Frame(frame) => write!(f, "in {}", frame),
+            TraceLine::Omitted(summary) => write!(f, "{}", summary),
+        }
     }
 }
 
 impl fmt::Display for ScannerError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "[line {}] Error: {}", self.line, self.message)
+        write!(f, "[{}] Error: {}", self.span, self.message)?;
+        if let Some(text) = &self.snippet {
+            let label = messages::text(messages::LABEL_SCANNER, &[]);
+            write_snippet(f, text, self.span, &label)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for LimitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl fmt::Display for IoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let path = self.path.display();
+        let message = messages::text(messages::CANNOT_READ_FILE, &[&path, &self.reason]);
+        write!(f, "{}", message)
+    }
+}
+
+impl fmt::Display for IoErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, error) in self.errors.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = messages::text(messages::TYPE_CHECK_FAILED, &[&self.count]);
+        write!(f, "{}", message)
+    }
+}
+
+impl fmt::Display for NativeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
     }
 }
 
@@ -104,26 +587,60 @@ impl fmt::Display for ReturnError {
     }
 }
 
+impl fmt::Display for BreakError {
+    fn fmt(&self, _f: &mut fmt::Formatter) -> fmt::Result {
+        unreachable!();
+    }
+}
+
+impl fmt::Display for ContinueError {
+    fn fmt(&self, _f: &mut fmt::Formatter) -> fmt::Result {
+        unreachable!();
+    }
+}
+
+impl fmt::Display for ExitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", messages::text(messages::EXITED, &[&self.code]))
+    }
+}
+
 impl fmt::Display for LoxError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             LoxError::Runtime(e) => e.fmt(f),
             LoxError::Scanner(e) => e.fmt(f),
             LoxError::Parser(e) => e.fmt(f),
+            LoxError::Limit(e) => e.fmt(f),
+            LoxError::Type(e) => e.fmt(f),
+            LoxError::Io(e) => e.fmt(f),
+            LoxError::Native(e) => e.fmt(f),
             LoxError::Return(e) => e.fmt(f),
+            LoxError::Break(e) => e.fmt(f),
+            LoxError::Continue(e) => e.fmt(f),
+            LoxError::Exit(e) => e.fmt(f),
         }
     }
 }
 
 impl Error for ParserError {}
+impl Error for ParserErrors {}
 impl Error for RuntimeError {}
 impl Error for ScannerError {}
+impl Error for LimitError {}
+impl Error for TypeError {}
+impl Error for IoError {}
+impl Error for IoErrors {}
+impl Error for NativeError {}
 impl Error for LoxError {}
 impl Error for ReturnError {}
+impl Error for BreakError {}
+impl Error for ContinueError {}
+impl Error for ExitError {}
 
 impl From<ParserError> for LoxError {
     fn from(err: ParserError) -> LoxError {
-        LoxError::Parser(err)
+        LoxError::Parser(ParserErrors::new(vec![err], false))
     }
 }
 
@@ -139,8 +656,50 @@ impl From<ScannerError> for LoxError {
     }
 }
 
+impl From<LimitError> for LoxError {
+    fn from(err: LimitError) -> LoxError {
+        LoxError::Limit(err)
+    }
+}
+
+impl From<IoError> for LoxError {
+    fn from(err: IoError) -> LoxError {
+        LoxError::Io(IoErrors::new(vec![err]))
+    }
+}
+
+impl From<TypeError> for LoxError {
+    fn from(err: TypeError) -> LoxError {
+        LoxError::Type(err)
+    }
+}
+
+impl From<NativeError> for LoxError {
+    fn from(err: NativeError) -> LoxError {
+        LoxError::Native(err)
+    }
+}
+
 impl From<ReturnError> for LoxError {
     fn from(err: ReturnError) -> LoxError {
         LoxError::Return(err)
     }
 }
+
+impl From<BreakError> for LoxError {
+    fn from(err: BreakError) -> LoxError {
+        LoxError::Break(err)
+    }
+}
+
+impl From<ContinueError> for LoxError {
+    fn from(err: ContinueError) -> LoxError {
+        LoxError::Continue(err)
+    }
+}
+
+impl From<ExitError> for LoxError {
+    fn from(err: ExitError) -> LoxError {
+        LoxError::Exit(err)
+    }
+}
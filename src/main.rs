@@ -1,55 +1,737 @@
-#![feature(is_some_and)]
-
-use clap::Parser;
-use lox_error::LoxError;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand};
+use std::cell::RefCell;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
+use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
+use tracing_subscriber::fmt::format::FmtSpan;
 
-mod ast;
-mod environment;
-mod interpreter;
-mod lox;
-mod lox_error;
-mod native_functions;
-mod parser;
-mod scanner;
-mod token;
-mod token_type;
-
-use crate::lox::Lox;
+use lox::diagnostic::ErrorFormat;
+use lox::interpreter::{Interpreter, DEFAULT_MAX_CALL_DEPTH, STACK_SIZE};
+#[cfg(feature = "jupyter")]
+use lox::kernel;
+use lox::lint::{Level, Lint};
+use lox::lox::IsolationMode;
+use lox::manifest::Manifest;
+use lox::number_format::NumberFormat;
+use lox::replay::Tape;
+#[cfg(unix)]
+use lox::time_travel;
+use lox::{
+    bundle, crash, diagnostic, difftest, emit, hooks, manifest, messages, server, Lox, LoxError,
+};
 
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, args_conflicts_with_subcommands = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Filename of the script to run
     #[arg()]
     script: Option<String>,
+
+    /// Emit tracing output for each interpreter phase at this level (error, warn, info, debug, trace)
+    #[arg(long, global = true)]
+    log_level: Option<tracing::Level>,
+
+    /// Stop reporting syntax errors after this many [default: 20]
+    #[arg(long, global = true)]
+    max_errors: Option<NonZeroUsize>,
+
+    /// Language of diagnostic messages (en, nl)
+    #[arg(long, global = true, default_value = "en")]
+    lang: messages::Language,
+
+    /// How errors and warnings are printed: human, or json for one JSON
+    /// object per line
+    #[arg(long, global = true, default_value = "human")]
+    error_format: ErrorFormat,
+
+    #[command(flatten)]
+    run: RunOptions,
 }
 
-fn main() -> ExitCode {
-    let args = Args::parse();
-    let mut lox = Lox::new();
+/// Flags for running a script, shared by `lox <script>` and `lox run`.
+#[derive(clap::Args, Debug)]
+struct RunOptions {
+    /// Raise a stack overflow error once Lox functions call each other this
+    /// deeply
+    #[arg(long, value_name = "DEPTH", default_value_t = DEFAULT_MAX_CALL_DEPTH)]
+    max_call_depth: usize,
 
-    let result = if let Some(script) = args.script {
-        let path = std::path::Path::new(&script);
-        lox.run_file(path)
-    } else {
-        lox.run_prompt()
+    /// Print a summary of execution statistics when the program finishes
+    #[arg(long)]
+    stats: bool,
+
+    /// When the program finishes, write every value it can still reach and
+    /// what keeps it alive to this JSON file, or to stdout for -
+    #[arg(long, value_name = "FILE")]
+    heap_dump: Option<PathBuf>,
+
+    /// Write what the scanner and parser produced next to the script, as
+    /// <script>.tokens and <script>.ast
+    #[arg(long, value_name = "ARTIFACTS", value_delimiter = ',')]
+    emit: Vec<emit::Artifact>,
+
+    /// Print every statement and call to stderr as it runs
+    #[arg(long)]
+    trace: bool,
+
+    /// Print how many strings, environments, lists and functions were allocated, and where
+    #[arg(long)]
+    profile_memory: bool,
+
+    /// Give every iteration of a for loop a fresh binding of its loop variable
+    #[arg(long)]
+    capture_by_iteration: bool,
+
+    /// After running the script, keep reloading its functions whenever it changes
+    #[arg(long)]
+    watch: bool,
+
+    /// Treat type annotation mismatches as errors that stop the script from running
+    #[arg(long)]
+    strict_types: bool,
+
+    /// Warn about suspicious operations as they happen, like comparing
+    /// values of different types with ==
+    #[arg(long)]
+    runtime_warnings: bool,
+
+    /// On a runtime error, open a prompt to inspect the variables and call stack
+    #[arg(long)]
+    debug_on_error: bool,
+
+    /// Show at most this many digits after the decimal point when printing numbers
+    #[arg(long, value_name = "DIGITS")]
+    precision: Option<usize>,
+
+    /// Print numbers of at least 10^EXPONENT, or below 10^-EXPONENT, in scientific notation
+    #[arg(long, value_name = "EXPONENT")]
+    scientific_threshold: Option<i32>,
+
+    /// Character printed in place of the decimal point
+    #[arg(long, value_name = "CHAR", default_value_t = '.')]
+    decimal_separator: char,
+
+    /// Abort the program once it has printed more than this, e.g. 64KB or 10MB
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    max_output: Option<usize>,
+
+    /// Abort the program after it has executed this many statements
+    #[arg(long, value_name = "STEPS")]
+    max_steps: Option<usize>,
+
+    /// Abort the program once it has run this long, e.g. 500ms, 10s or 1m
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    timeout: Option<Duration>,
+
+    /// Record clock readings, file reads and other inputs to this trace file
+    #[arg(long, value_name = "TRACE", conflicts_with = "replay")]
+    record: Option<PathBuf>,
+
+    /// Replay the inputs recorded in this trace file instead of reading them
+    #[arg(long, value_name = "TRACE")]
+    replay: Option<PathBuf>,
+
+    /// Arguments for the script, returned by its args()
+    #[arg(last = true, value_name = "ARGS")]
+    args: Vec<String>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run as a Jupyter kernel using the given connection file
+    #[cfg(feature = "jupyter")]
+    Kernel {
+        #[arg()]
+        connection_file: PathBuf,
+    },
+    /// Create a lox.toml and src/main.lox for a new project
+    Init {
+        /// Directory to create the project in
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+    },
+    /// Run a script, or the project in the nearest lox.toml: its prelude
+    /// scripts, then its entry point
+    Run {
+        #[arg()]
+        script: Option<PathBuf>,
+
+        #[command(flatten)]
+        options: RunOptions,
+    },
+    /// Package a script and this interpreter into a single executable
+    Bundle {
+        #[arg()]
+        script: PathBuf,
+
+        /// Where to write the executable
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Print the syntax tree of a script in parenthesized form
+    Ast {
+        #[arg()]
+        script: PathBuf,
+    },
+    /// Print facts about a script for editor plugins, as JSON
+    Analyze {
+        #[arg()]
+        script: PathBuf,
+
+        /// Every variable use, with the span of the declaration it refers to
+        #[arg(long, required = true)]
+        definitions: bool,
+    },
+    /// Run programs through this interpreter and a reference one, reporting
+    /// any difference in output or exit code
+    Difftest {
+        /// The reference interpreter, run as `<reference> <program>`
+        #[arg(long)]
+        reference: PathBuf,
+
+        /// Programs, or directories searched for .lox programs
+        #[arg(required = true)]
+        corpus: Vec<PathBuf>,
+
+        /// How many programs to run at once [default: the number of CPUs]
+        #[arg(short, long)]
+        jobs: Option<NonZeroUsize>,
+    },
+    /// Run a script, then step backwards and forwards through the statements
+    /// it executed
+    #[cfg(unix)]
+    Debug {
+        #[arg()]
+        script: PathBuf,
+    },
+    /// Lint scripts without running them, using the nearest lox.toml
+    Check {
+        /// Scripts to check [default: the scripts of the project in the nearest lox.toml]
+        #[arg()]
+        scripts: Vec<PathBuf>,
+
+        /// Don't report this lint
+        #[arg(long, value_name = "LINT")]
+        allow: Vec<Lint>,
+
+        /// Report this lint as a warning
+        #[arg(long, value_name = "LINT")]
+        warn: Vec<Lint>,
+
+        /// Report this lint as an error
+        #[arg(long, value_name = "LINT")]
+        deny: Vec<Lint>,
+
+        /// Treat every warning as an error
+        #[arg(long)]
+        deny_warnings: bool,
+    },
+    /// Serve a playground HTTP endpoint that runs submitted programs
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+
+        /// Port to listen on
+        #[arg(long, default_value_t = 8080)]
+        port: u16,
+
+        /// Maximum number of statements a submitted program may execute
+        #[arg(long, default_value_t = 1_000_000)]
+        fuel: usize,
+    },
+}
+
+/// Parses a byte count with an optional B, KB, MB or GB suffix, in
+/// powers of 1024.
+fn parse_size(text: &str) -> Result<usize, String> {
+    let digits = text.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let unit = match text[digits.len()..].to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        unit => return Err(format!("unknown unit '{}'", unit)),
     };
+    digits
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_mul(unit))
+        .ok_or_else(|| format!("invalid size '{}'", text))
+}
 
-    match result {
-        Ok(_) => ExitCode::SUCCESS,
-        Err(LoxError::Scanner(e)) => {
-            eprintln!("{}", e);
-            ExitCode::from(65)
+fn parse_duration(text: &str) -> Result<Duration, String> {
+    let number = text.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let unit = match &text[number.len()..] {
+        "ms" => 0.001,
+        "" | "s" => 1.0,
+        "m" => 60.0,
+        unit => return Err(format!("unknown unit '{}'", unit)),
+    };
+    number
+        .parse::<f64>()
+        .ok()
+        .and_then(|n| Duration::try_from_secs_f64(n * unit).ok())
+        .ok_or_else(|| format!("invalid duration '{}'", text))
+}
+
+fn save_heap_dump(lox: &Lox, path: &Path) {
+    let json = lox.heap_snapshot().to_json();
+    if path == Path::new("-") {
+        println!("{}", json);
+    } else if let Err(e) = std::fs::write(path, json + "\n") {
+        eprintln!("Failed to write heap dump {}: {}", path.display(), e);
+    }
+}
+
+/// Writes the recorded trace, if recording, even when the run failed.
+fn save_trace(lox: &Lox, path: Option<&Path>) {
+    if let Some(path) = path {
+        if let Err(e) = lox.tape().save(path) {
+            eprintln!("Failed to write trace {}: {}", path.display(), e);
         }
-        Err(LoxError::Parser(e)) => {
+    }
+}
+
+/// Runs `script`, then lets the user step back through what it did.
+#[cfg(unix)]
+fn debug(script: &Path, max_errors: Option<NonZeroUsize>) -> ExitCode {
+    let source = match std::fs::read_to_string(script) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", script.display(), e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut interpreter = Interpreter::new();
+    interpreter.tape = Tape::Record(Vec::new());
+    let mut lox = Lox::with_interpreter(interpreter);
+    if let Some(max_errors) = max_errors {
+        lox.set_max_errors(max_errors.get());
+    }
+    let exit = match lox.run(&source) {
+        Ok(()) | Err(LoxError::Return(_)) => ExitCode::SUCCESS,
+        Err(LoxError::Exit(e)) => ExitCode::from(e.code),
+        Err(e @ (LoxError::Scanner(_) | LoxError::Parser(_))) => {
+            lox.report(&e);
+            return ExitCode::from(65);
+        }
+        Err(e) => {
+            lox.report(&e);
+            ExitCode::from(70)
+        }
+    };
+
+    let steps = lox.stats().statements;
+    match time_travel::explore(&source, lox.tape(), steps, std::io::stdin().lock()) {
+        Ok(()) => exit,
+        Err(e) => {
             eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Lints each script, exiting with 65 if any of them has an error.
+fn check(
+    scripts: &[PathBuf],
+    levels: [(Vec<Lint>, Level); 3],
+    deny_warnings: bool,
+    max_errors: Option<NonZeroUsize>,
+) -> ExitCode {
+    let mut failed = false;
+    for script in scripts {
+        let dir = script.parent().unwrap_or(Path::new("."));
+        let mut config = match Manifest::discover(dir) {
+            Ok(manifest) => manifest.map(|m| m.lint_config()).unwrap_or_default(),
+            Err(e) => {
+                eprintln!("Failed to load lint configuration: {}", e);
+                return ExitCode::FAILURE;
+            }
+        };
+        for (lints, level) in &levels {
+            for lint in lints {
+                config.set_level(*lint, *level);
+            }
+        }
+        if deny_warnings {
+            config.set_deny_warnings(true);
+        }
+
+        let source = match std::fs::read_to_string(script) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("Failed to read {}: {}", script.display(), e);
+                return ExitCode::FAILURE;
+            }
+        };
+
+        let mut lox = Lox::new();
+        if let Some(max_errors) = max_errors {
+            lox.set_max_errors(max_errors.get());
+        }
+        match lox.check(&source, &config) {
+            Ok(warnings) => {
+                for warning in warnings {
+                    failed |= warning.level == Level::Deny;
+                    lox.print_warning(&warning);
+                }
+            }
+            Err(e) => {
+                failed = true;
+                lox.report(&e);
+            }
+        }
+    }
+
+    if failed {
+        ExitCode::from(65)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Checks that `script` parses, then bundles it into `output`.
+fn bundle(script: &Path, output: &Path) -> ExitCode {
+    let source = match std::fs::read_to_string(script) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", script.display(), e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut lox = Lox::new();
+    if let Err(e) = lox.compile(&source) {
+        lox.report(&e);
+        return ExitCode::from(65);
+    }
+    match bundle::bundle(&source, output) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Failed to write {}: {}", output.display(), e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Runs the script a bundle was built with.
+fn run_bundled(source: &str) -> ExitCode {
+    let mut lox = Lox::new();
+    match lox.run_script(source) {
+        Ok(()) | Err(LoxError::Return(_)) => ExitCode::SUCCESS,
+        Err(LoxError::Exit(e)) => ExitCode::from(e.code),
+        Err(e @ (LoxError::Scanner(_) | LoxError::Parser(_))) => {
+            lox.report(&e);
             ExitCode::from(65)
         }
-        Err(LoxError::Runtime(e)) => {
-            eprintln!("{}", e);
+        Err(e) => {
+            lox.report(&e);
             ExitCode::from(70)
         }
-        Err(LoxError::Return(_)) => ExitCode::from(0),
+    }
+}
+
+/// The scripts of the project in the nearest `lox.toml`, prelude first.
+fn project_scripts() -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let dir = std::env::current_dir()?;
+    match Manifest::discover(&dir)? {
+        Some(manifest) => manifest.scripts(),
+        None => Err(format!(
+            "No script given and no {} found in {} or its parents",
+            manifest::FILE_NAME,
+            dir.display()
+        )
+        .into()),
+    }
+}
+
+fn main() -> ExitCode {
+    thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(cli)
+        .expect("Failed to start the interpreter thread")
+        .join()
+        .unwrap_or(ExitCode::from(101))
+}
+
+fn cli() -> ExitCode {
+    crash::install_hook();
+    if let Some(source) = bundle::embedded_script() {
+        return run_bundled(&source);
+    }
+    let args = Args::parse();
+
+    if let Some(level) = args.log_level {
+        tracing_subscriber::fmt()
+            .with_max_level(level)
+            .with_span_events(FmtSpan::CLOSE)
+            .with_writer(std::io::stderr)
+            .init();
+    }
+    messages::set_language(args.lang);
+    diagnostic::set_format(args.error_format);
+
+    if let Some(command) = args.command {
+        return match command {
+            #[cfg(feature = "jupyter")]
+            Command::Kernel { connection_file } => match kernel::run(&connection_file) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    ExitCode::FAILURE
+                }
+            },
+            Command::Init { dir } => match manifest::init(&dir) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => {
+                    eprintln!("Failed to create project: {}", e);
+                    ExitCode::FAILURE
+                }
+            },
+            Command::Run {
+                script: Some(script),
+                options,
+            } => run(Some(script), &[], options, args.max_errors),
+            Command::Run {
+                script: None,
+                options,
+            } => match project_scripts() {
+                Ok(mut scripts) => {
+                    let entry = scripts.pop();
+                    run(entry, &scripts, options, args.max_errors)
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    ExitCode::FAILURE
+                }
+            },
+            Command::Bundle { script, output } => bundle(&script, &output),
+            Command::Ast { script } => {
+                let mut lox = Lox::new();
+                let result = std::fs::read_to_string(&script)
+                    .map_err(|e| format!("Failed to read {}: {}", script.display(), e))
+                    .map(|source| lox.ast(&source));
+                match result {
+                    Ok(Ok(tree)) => {
+                        print!("{}", tree);
+                        ExitCode::SUCCESS
+                    }
+                    Ok(Err(e)) => {
+                        lox.report(&e);
+                        ExitCode::from(65)
+                    }
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        ExitCode::FAILURE
+                    }
+                }
+            }
+            Command::Analyze { script, .. } => {
+                let mut lox = Lox::new();
+                let result = std::fs::read_to_string(&script)
+                    .map_err(|e| format!("Failed to read {}: {}", script.display(), e))
+                    .map(|source| lox.definitions(&source));
+                match result {
+                    Ok(Ok(definitions)) => {
+                        println!("{}", serde_json::to_string_pretty(&definitions).unwrap());
+                        ExitCode::SUCCESS
+                    }
+                    Ok(Err(e)) => {
+                        lox.report(&e);
+                        ExitCode::from(65)
+                    }
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        ExitCode::FAILURE
+                    }
+                }
+            }
+            Command::Difftest {
+                reference,
+                corpus,
+                jobs,
+            } => {
+                let jobs = jobs
+                    .or_else(|| std::thread::available_parallelism().ok())
+                    .unwrap_or(NonZeroUsize::MIN);
+                match difftest::run_corpus(&reference, &corpus, jobs) {
+                    Ok(true) => ExitCode::SUCCESS,
+                    Ok(false) => ExitCode::from(1),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        ExitCode::FAILURE
+                    }
+                }
+            }
+            #[cfg(unix)]
+            Command::Debug { script } => debug(&script, args.max_errors),
+            Command::Check {
+                scripts,
+                allow,
+                warn,
+                deny,
+                deny_warnings,
+            } => {
+                let scripts = if scripts.is_empty() {
+                    match project_scripts() {
+                        Ok(scripts) => scripts,
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            return ExitCode::FAILURE;
+                        }
+                    }
+                } else {
+                    scripts
+                };
+                let levels = [
+                    (allow, Level::Allow),
+                    (warn, Level::Warn),
+                    (deny, Level::Deny),
+                ];
+                check(&scripts, levels, deny_warnings, args.max_errors)
+            }
+            Command::Serve { host, port, fuel } => match server::serve(&host, port, fuel) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    ExitCode::FAILURE
+                }
+            },
+        };
+    }
+
+    let script = args.script.map(PathBuf::from);
+    run(script, &[], args.run, args.max_errors)
+}
+
+/// Runs the `prelude` scripts and then `script` in one interpreter, or
+/// starts the REPL if there is no script.
+fn run(
+    script: Option<PathBuf>,
+    prelude: &[PathBuf],
+    options: RunOptions,
+    max_errors: Option<NonZeroUsize>,
+) -> ExitCode {
+    if script.is_none() && (options.watch || options.debug_on_error || !options.emit.is_empty()) {
+        Args::command()
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                "--watch, --debug-on-error and --emit need a script to run",
+            )
+            .exit();
+    }
+
+    let mut interpreter = Interpreter::new();
+    interpreter.set_capture_by_iteration(options.capture_by_iteration);
+    interpreter.set_max_call_depth(Some(options.max_call_depth));
+    interpreter.set_memory_profiling(options.profile_memory);
+    if options.trace {
+        interpreter.add_hook(Rc::new(RefCell::new(hooks::Tracer)));
+    }
+    interpreter.set_max_output(options.max_output);
+    interpreter.set_limits(options.max_steps, options.timeout);
+    interpreter.set_args(options.args);
+    interpreter.set_number_format(NumberFormat {
+        precision: options.precision,
+        scientific_threshold: options.scientific_threshold,
+        decimal_separator: options.decimal_separator,
+    });
+    if let Some(trace) = &options.replay {
+        match Tape::load(trace) {
+            Ok(tape) => interpreter.tape = tape,
+            Err(e) => {
+                eprintln!("Failed to load trace {}: {}", trace.display(), e);
+                return ExitCode::FAILURE;
+            }
+        }
+    } else if options.record.is_some() {
+        interpreter.tape = Tape::Record(Vec::new());
+    }
+    let mut lox = Lox::with_interpreter(interpreter);
+    if let Some(max_errors) = max_errors {
+        lox.set_max_errors(max_errors.get());
+    }
+    lox.set_emit(options.emit);
+    let type_level = if options.strict_types {
+        Level::Deny
+    } else {
+        Level::Warn
+    };
+    lox.set_type_checking(Some(type_level));
+    if options.runtime_warnings {
+        // Their levels come from the same lox.toml `lox check` uses
+        let dir = script
+            .as_deref()
+            .and_then(Path::parent)
+            .unwrap_or(Path::new("."));
+        match Manifest::discover(dir) {
+            Ok(manifest) => lox
+                .set_runtime_warnings(Some(manifest.map(|m| m.lint_config()).unwrap_or_default())),
+            Err(e) => {
+                eprintln!("Failed to load lint configuration: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    let result = if let Some(path) = &script {
+        let scripts: Vec<PathBuf> = prelude.iter().chain([path]).cloned().collect();
+        let result = lox.run_files(&scripts, IsolationMode::SharedGlobals);
+        save_trace(&lox, options.record.as_deref());
+        if options.watch {
+            if let Err(e) = &result {
+                lox.report(e);
+            }
+            lox.watch(path);
+        }
+        result
+    } else {
+        let result = lox.run_prompt();
+        save_trace(&lox, options.record.as_deref());
+        result
+    };
+    if let Some(path) = &options.heap_dump {
+        save_heap_dump(&lox, path);
+    }
+
+    if options.stats {
+        eprint!("{}", lox.stats());
+    }
+    if let Some(profile) = lox.memory_profile() {
+        eprint!("{}", profile);
+    }
+
+    let error = match result {
+        Ok(_) | Err(LoxError::Return(_)) => return ExitCode::SUCCESS,
+        Err(LoxError::Exit(e)) => return ExitCode::from(e.code),
+        Err(e) => e,
+    };
+
+    lox.report(&error);
+    if options.debug_on_error {
+        if let Some(failure) = lox.failure() {
+            failure.inspect(std::io::stdin().lock());
+        }
+    }
+    match error {
+        LoxError::Scanner(_) | LoxError::Parser(_) | LoxError::Type(_) => ExitCode::from(65),
+        LoxError::Runtime(_) | LoxError::Limit(_) => ExitCode::from(70),
+        LoxError::Io(_) => ExitCode::from(66),
+        LoxError::Return(_)
+        | LoxError::Native(_)
+        | LoxError::Break(_)
+        | LoxError::Continue(_)
+        | LoxError::Exit(_) => {
+            unreachable!()
+        }
     }
 }
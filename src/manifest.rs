@@ -0,0 +1,143 @@
+//! `lox.toml`, the manifest at the root of a Lox project. Its `[project]`
+//! table tells `lox run` and `lox check` which scripts make up the project,
+//! and its `[lints]` table and `deny-warnings` key configure the linter.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::lint::{Level, Lint, LintConfig};
+
+pub const FILE_NAME: &str = "lox.toml";
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Manifest {
+    /// The directory the manifest is in, which its paths are relative to.
+    #[serde(skip)]
+    root: PathBuf,
+    project: Option<Project>,
+    #[serde(default)]
+    lints: HashMap<Lint, Level>,
+    #[serde(default)]
+    deny_warnings: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct Project {
+    /// The script `lox run` runs.
+    entry: PathBuf,
+    /// Scripts run, in order, before the entry point.
+    #[serde(default)]
+    prelude: Vec<PathBuf>,
+    /// Directories searched for imported scripts.
+    #[serde(default)]
+    #[allow(dead_code)]
+    paths: Vec<PathBuf>,
+}
+
+impl Manifest {
+    #[cfg(feature = "config-formats")]
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut manifest: Self = toml::from_str(&fs::read_to_string(path)?)?;
+        manifest.root = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+        Ok(manifest)
+    }
+
+    /// Without TOML support a `lox.toml` can't be read, so it is an error
+    /// rather than silently ignored.
+    #[cfg(not(feature = "config-formats"))]
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Err(format!(
+            "Reading {} requires the config-formats feature",
+            path.display()
+        )
+        .into())
+    }
+
+    /// Finds the nearest `lox.toml` in `dir` or one of its ancestors.
+    pub fn discover(dir: &Path) -> Result<Option<Self>, Box<dyn Error>> {
+        dir.ancestors()
+            .map(|dir| dir.join(FILE_NAME))
+            .find(|path| path.is_file())
+            .map(|path| Self::load(&path))
+            .transpose()
+    }
+
+    pub fn lint_config(&self) -> LintConfig {
+        LintConfig::new(self.lints.clone(), self.deny_warnings)
+    }
+
+    /// The project's scripts in the order they run: the prelude, then the
+    /// entry point.
+    pub fn scripts(&self) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        let Some(project) = &self.project else {
+            let path = self.root.join(FILE_NAME);
+            return Err(format!("{} has no [project] table", path.display()).into());
+        };
+        Ok(project
+            .prelude
+            .iter()
+            .chain([&project.entry])
+            .map(|script| self.root.join(script))
+            .collect())
+    }
+}
+
+/// Creates `lox.toml` and `src/main.lox` for a new project in `dir`.
+pub fn init(dir: &Path) -> Result<(), Box<dyn Error>> {
+    let path = dir.join(FILE_NAME);
+    if path.exists() {
+        return Err(format!("{} already exists", path.display()).into());
+    }
+    fs::create_dir_all(dir)?;
+    fs::write(&path, TEMPLATE)?;
+    let entry = dir.join("src/main.lox");
+    if !entry.exists() {
+        fs::create_dir_all(dir.join("src"))?;
+        fs::write(entry, "print \"Hello, world!\";\n")?;
+    }
+    Ok(())
+}
+
+const TEMPLATE: &str = r#"[project]
+entry = "src/main.lox"
+# Scripts run before the entry point, e.g. shared helper functions
+prelude = []
+# Directories searched for imported scripts
+paths = ["src"]
+
+[lints]
+unused-variable = "warn"
+"#;
+
+#[cfg(all(test, feature = "config-formats"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template_parses() {
+        let manifest: Manifest = toml::from_str(TEMPLATE).unwrap();
+        assert_eq!(
+            manifest.scripts().unwrap(),
+            vec![PathBuf::from("src/main.lox")]
+        );
+    }
+
+    #[test]
+    fn test_scripts_run_prelude_first() {
+        let mut manifest: Manifest = toml::from_str(
+            "[project]\nentry = \"main.lox\"\nprelude = [\"lib/a.lox\", \"lib/b.lox\"]",
+        )
+        .unwrap();
+        manifest.root = PathBuf::from("demo");
+        assert_eq!(
+            manifest.scripts().unwrap(),
+            ["demo/lib/a.lox", "demo/lib/b.lox", "demo/main.lox"].map(PathBuf::from)
+        );
+    }
+}
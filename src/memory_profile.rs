@@ -0,0 +1,212 @@
+//! Counts the values a script allocates, by kind and by the line that
+//! allocated them, for `--profile-memory`. With the `memory-profile`
+//! feature a counting global allocator also measures the peak heap size.
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// How many allocation sites the report lists.
+const TOP_SITES: usize = 10;
+
+#[derive(Clone, Copy)]
+pub enum Allocation {
+    String,
+    Environment,
+    List,
+    Map,
+    Function,
+}
+
+impl Allocation {
+    const ALL: [Allocation; 5] = [
+        Allocation::String,
+        Allocation::Environment,
+        Allocation::List,
+        Allocation::Map,
+        Allocation::Function,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Allocation::String => "strings",
+            Allocation::Environment => "environments",
+            Allocation::List => "lists",
+            Allocation::Map => "maps",
+            Allocation::Function => "functions",
+        }
+    }
+}
+
+/// Allocation counts, indexed by `Allocation`.
+type Counts = [usize; Allocation::ALL.len()];
+
+pub struct MemoryProfile {
+    /// The line allocations are currently attributed to.
+    line: Option<usize>,
+    totals: Counts,
+    sites: HashMap<usize, Counts>,
+    /// Live heap bytes when profiling started.
+    baseline: usize,
+}
+
+impl Default for MemoryProfile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryProfile {
+    pub fn new() -> Self {
+        heap::reset_peak();
+        Self {
+            line: None,
+            totals: Counts::default(),
+            sites: HashMap::new(),
+            baseline: heap::live(),
+        }
+    }
+
+    pub fn set_line(&mut self, line: Option<usize>) {
+        self.line = line;
+    }
+
+    pub fn record(&mut self, allocation: Allocation) {
+        self.totals[allocation as usize] += 1;
+        if let Some(line) = self.line {
+            self.sites.entry(line).or_default()[allocation as usize] += 1;
+        }
+    }
+
+    /// The lines that allocated the most values, most first.
+    fn top_sites(&self) -> Vec<(usize, Counts)> {
+        let mut sites: Vec<_> = self.sites.iter().map(|(&line, &c)| (line, c)).collect();
+        sites
+            .sort_by_key(|&(line, counts)| (std::cmp::Reverse(counts.iter().sum::<usize>()), line));
+        sites.truncate(TOP_SITES);
+        sites
+    }
+}
+
+impl fmt::Display for MemoryProfile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for allocation in Allocation::ALL {
+            let name = format!("{} allocated", allocation.name());
+            writeln!(f, "{:<24}{:>12}", name, self.totals[allocation as usize])?;
+        }
+        match heap::peak() {
+            Some(peak) => {
+                let growth = peak.saturating_sub(self.baseline);
+                writeln!(f, "{:<24}{:>12}", "peak heap growth", format_bytes(growth))?;
+            }
+            None => writeln!(
+                f,
+                "{:<24}{:>12}",
+                "peak heap growth", "unavailable (build with the memory-profile feature)"
+            )?,
+        }
+
+        let sites = self.top_sites();
+        if sites.is_empty() {
+            return Ok(());
+        }
+        writeln!(f, "top allocation sites")?;
+        for (line, counts) in sites {
+            let kinds: Vec<_> = Allocation::ALL
+                .into_iter()
+                .filter(|&a| counts[a as usize] > 0)
+                .map(|a| format!("{} {}", counts[a as usize], a.name()))
+                .collect();
+            writeln!(f, "  line {:<6}{}", line, kinds.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+fn format_bytes(bytes: usize) -> String {
+    match bytes {
+        0..1024 => format!("{} B", bytes),
+        1024..1_048_576 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+#[cfg(feature = "memory-profile")]
+mod heap {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static LIVE: AtomicUsize = AtomicUsize::new(0);
+    static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+    /// The system allocator, keeping track of how many bytes are live.
+    struct Counting;
+
+    unsafe impl GlobalAlloc for Counting {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let ptr = System.alloc(layout);
+            if !ptr.is_null() {
+                let live = LIVE.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+                PEAK.fetch_max(live, Ordering::Relaxed);
+            }
+            ptr
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout);
+            LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: Counting = Counting;
+
+    pub fn live() -> usize {
+        LIVE.load(Ordering::Relaxed)
+    }
+
+    pub fn reset_peak() {
+        PEAK.store(live(), Ordering::Relaxed);
+    }
+
+    pub fn peak() -> Option<usize> {
+        Some(PEAK.load(Ordering::Relaxed))
+    }
+}
+
+#[cfg(not(feature = "memory-profile"))]
+mod heap {
+    pub fn live() -> usize {
+        0
+    }
+
+    pub fn reset_peak() {}
+
+    pub fn peak() -> Option<usize> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::lox::Lox;
+    use crate::output::SharedBuffer;
+
+    #[test]
+    fn test_attributes_allocations_to_lines() {
+        let mut interpreter = Interpreter::with_writer(SharedBuffer::default());
+        interpreter.set_memory_profiling(true);
+        let mut lox = Lox::with_interpreter(interpreter);
+        lox.run("var xs = [];\nvar m = {};\nfor (var i = 0; i < 3; i += 1) {\n  xs.push(\"a\" + \"b\");\n}")
+            .unwrap();
+
+        let profile = lox.memory_profile().unwrap();
+        assert_eq!(profile.totals[Allocation::List as usize], 1);
+        assert_eq!(profile.totals[Allocation::Map as usize], 1);
+        let (line, counts) = profile.top_sites()[0];
+        assert_eq!(line, 4);
+        // Two literals and their concatenation per iteration
+        assert_eq!(counts[Allocation::String as usize], 9);
+    }
+}
@@ -0,0 +1,555 @@
+//! The catalog of user-facing diagnostic messages.
+//!
+//! Every message has a stable code and an English default. Translations are
+//! keyed by code, and fall back to English for messages they don't cover.
+
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Message {
+    code: &'static str,
+    english: &'static str,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Language {
+    #[default]
+    English,
+    Dutch,
+}
+
+static LANGUAGE: OnceLock<Language> = OnceLock::new();
+
+/// Sets the language of all messages. Only the first call has an effect.
+pub fn set_language(language: Language) {
+    let _ = LANGUAGE.set(language);
+}
+
+/// Renders `message` in the current language, replacing each `{}` in it
+/// with the next of `args`.
+pub fn text(message: Message, args: &[&dyn Display]) -> String {
+    let catalog: &[(&str, &str)] = match LANGUAGE.get().copied().unwrap_or_default() {
+        Language::English => &[],
+        Language::Dutch => DUTCH,
+    };
+    let template = catalog
+        .iter()
+        .find(|(code, _)| *code == message.code)
+        .map_or(message.english, |(_, text)| text);
+
+    let mut out = String::new();
+    let mut args = args.iter();
+    let mut parts = template.split("{}");
+    out.push_str(parts.next().unwrap_or_default());
+    for part in parts {
+        if let Some(arg) = args.next() {
+            out.push_str(&arg.to_string());
+        }
+        out.push_str(part);
+    }
+    out
+}
+
+impl FromStr for Language {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "en" => Ok(Language::English),
+            "nl" => Ok(Language::Dutch),
+            _ => Err(format!("Unknown language '{}'. Available: en, nl.", name)),
+        }
+    }
+}
+
+impl Message {
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+}
+
+const fn message(code: &'static str, english: &'static str) -> Message {
+    Message { code, english }
+}
+
+// Scanner
+pub const UNEXPECTED_CHARACTER: Message = message("S001", "Unexpected character.");
+pub const UNTERMINATED_STRING: Message = message("S002", "Unterminated string.");
+pub const LABEL_SCANNER: Message = message("S003", "here");
+
+// Parser
+pub const LABEL_ON_NON_LOOP: Message = message("P001", "Labels can only be applied to loops.");
+pub const EXPECT_SEMICOLON_AFTER_BREAK: Message = message("P002", "Expect ';' after 'break'.");
+pub const EXPECT_SEMICOLON_AFTER_CONTINUE: Message =
+    message("P003", "Expect ';' after 'continue'.");
+pub const OUTSIDE_OF_LOOP: Message = message("P004", "Can't use '{}' outside of a loop.");
+pub const NO_LOOP_LABELED: Message = message("P005", "No enclosing loop labeled '{}'.");
+pub const EXPECT_PAREN_AFTER_FOR: Message = message("P006", "Expect '(' after 'for'.");
+pub const EXPECT_SEMICOLON_AFTER_LOOP_CONDITION: Message =
+    message("P007", "Expect ';' after loop condition.");
+pub const EXPECT_PAREN_AFTER_FOR_CLAUSES: Message =
+    message("P008", "Expect ')' after for clauses.");
+pub const EXPECT_PAREN_AFTER_IF: Message = message("P009", "Expect '(' after 'if'.");
+pub const EXPECT_PAREN_AFTER_IF_CONDITION: Message =
+    message("P010", "Expect ')' after if condition");
+pub const EXPECT_SEMICOLON_AFTER_VALUE: Message = message("P011", "Expect ';' after value.");
+pub const EXPECT_SEMICOLON_AFTER_RETURN: Message =
+    message("P012", "Expect ';' after return value.");
+pub const EXPECT_PAREN_AFTER_WHILE: Message = message("P013", "Expect '(' after while.");
+pub const EXPECT_PAREN_AFTER_CONDITION: Message = message("P014", "Expect ')' after condition.");
+pub const EXPECT_BRACE_AFTER_BLOCK: Message = message("P015", "Expect '}' after block.");
+pub const EXPECT_VARIABLE_NAME: Message = message("P016", "Expect variable name.");
+pub const EXPECT_SEMICOLON_AFTER_VAR: Message =
+    message("P017", "Expect ';' after variable declaration.");
+pub const EXPECT_SEMICOLON_AFTER_EXPRESSION: Message =
+    message("P018", "Expect ';' after expression.");
+pub const EXPECT_KIND_NAME: Message = message("P019", "Expect {} name.");
+pub const EXPECT_PAREN_AFTER_KIND_NAME: Message = message("P020", "Expect '(' after {} name.");
+pub const EXPECT_BRACE_BEFORE_KIND_BODY: Message = message("P021", "Expect '{' before {} body.");
+pub const TOO_MANY_PARAMETERS: Message = message("P022", "Can't have more than 255 parameters.");
+pub const EXPECT_PARAMETER_NAME: Message = message("P023", "Expect parameter name.");
+pub const EXPECT_PAREN_AFTER_PARAMETERS: Message = message("P024", "Expect ')' after parameters.");
+pub const EXPECT_PAREN_AFTER_FUN: Message = message("P025", "Expect '(' after 'fun'.");
+pub const EXPECT_BRACE_BEFORE_FUNCTION_BODY: Message =
+    message("P026", "Expect '{' before function body.");
+pub const INVALID_ASSIGNMENT_TARGET: Message = message("P027", "Invalid assignment target.");
+pub const TOO_MANY_ARGUMENTS: Message = message("P028", "Can't have more than 255 arguments.");
+pub const EXPECT_PAREN_AFTER_ARGUMENTS: Message = message("P029", "Expect ')' after arguments.");
+pub const EXPECT_BRACKET_AFTER_LIST: Message = message("P030", "Expect ']' after list elements.");
+pub const EXPECT_PROPERTY_NAME: Message = message("P031", "Expect property name after '.'.");
+pub const EXPECT_PAREN_AFTER_EXPRESSION: Message = message("P032", "Expect ')' after expression.");
+pub const EXPECT_EXPRESSION: Message = message("P033", "Expect expression.");
+pub const TOO_MANY_ERRORS: Message = message("P034", "Too many errors, stopping.");
+pub const EXPECT_BRACKET_AFTER_INDEX: Message = message("P035", "Expect ']' after index.");
+pub const EXPECT_BRACE_AFTER_TRY: Message = message("P036", "Expect '{' after 'try'.");
+pub const EXPECT_CATCH: Message = message("P037", "Expect 'catch' after try block.");
+pub const EXPECT_PAREN_AFTER_CATCH: Message = message("P038", "Expect '(' after 'catch'.");
+pub const EXPECT_ERROR_NAME: Message = message("P039", "Expect error variable name.");
+pub const EXPECT_PAREN_AFTER_ERROR_NAME: Message =
+    message("P040", "Expect ')' after error variable name.");
+pub const EXPECT_BRACE_BEFORE_CATCH_BODY: Message =
+    message("P041", "Expect '{' before catch body.");
+pub const EXPECT_SEMICOLON_AFTER_THROW: Message = message("P042", "Expect ';' after thrown value.");
+pub const EXPECT_TYPE_NAME: Message = message("P043", "Expect type name.");
+pub const EXPECT_BRACE_AFTER_CLASS_BODY: Message = message("P044", "Expect '}' after class body.");
+pub const THIS_OUTSIDE_CLASS: Message = message("P045", "Can't use 'this' outside of a class.");
+pub const RETURN_VALUE_FROM_INITIALIZER: Message =
+    message("P046", "Can't return a value from an initializer.");
+pub const EXPECT_SUPERCLASS_NAME: Message = message("P047", "Expect superclass name.");
+pub const INHERIT_FROM_SELF: Message = message("P048", "A class can't inherit from itself.");
+pub const SUPER_OUTSIDE_CLASS: Message = message("P049", "Can't use 'super' outside of a class.");
+pub const SUPER_WITHOUT_SUPERCLASS: Message =
+    message("P050", "Can't use 'super' in a class with no superclass.");
+pub const EXPECT_DOT_AFTER_SUPER: Message = message("P051", "Expect '.' after 'super'.");
+pub const EXPECT_SUPERCLASS_METHOD: Message = message("P052", "Expect superclass method name.");
+pub const EXPECT_COLON_AFTER_MAP_KEY: Message = message("P053", "Expect ':' after map key.");
+pub const EXPECT_BRACE_AFTER_MAP: Message = message("P054", "Expect '}' after map entries.");
+pub const INVALID_INCREMENT_TARGET: Message = message(
+    "P055",
+    "Operand of '{}' must be a variable, property or list element.",
+);
+pub const EXPECT_COLON_IN_CONDITIONAL: Message = message(
+    "P056",
+    "Expect ':' and an else branch after the then branch of '?'.",
+);
+pub const EXPECT_END_OF_EXPRESSION: Message = message("P057", "Expect end of expression.");
+pub const LABEL_UNEXPECTED: Message = message("P058", "unexpected here");
+pub const LABEL_END_OF_INPUT: Message = message("P059", "input ends here");
+
+// Runtime
+pub const OPERANDS_MUST_BE_NUMBERS: Message = message("R001", "Operands must be numbers.");
+pub const OPERAND_MUST_BE_NUMBER: Message = message("R002", "Operand must be a number.");
+pub const OPERANDS_MUST_BE_NUMBERS_OR_STRINGS: Message =
+    message("R003", "Operands must be two numbers or two strings.");
+pub const SEARCH_STRING_FOR_STRING: Message =
+    message("R004", "Can only search a string for a string.");
+pub const MAP_KEYS_MUST_BE_STRINGS: Message = message("R005", "Map keys must be strings.");
+pub const IN_RIGHT_OPERAND: Message = message(
+    "R006",
+    "Right operand of 'in' must be a string, list or map.",
+);
+pub const STACK_OVERFLOW: Message = message("R007", "Stack overflow.");
+pub const WRONG_ARGUMENT_COUNT: Message = message("R008", "Expected {} but got {}.");
+pub const NOT_CALLABLE: Message = message("R009", "Can only call functions and classes.");
+pub const UNDEFINED_PROPERTY: Message = message("R010", "Undefined property '{}'.");
+pub const ONLY_FOREIGN_PROPERTIES: Message = message(
+    "R011",
+    "Only instances, strings, numbers, lists, maps and foreign values have properties.",
+);
+pub const SPREAD_NON_LIST: Message = message("R012", "Can only spread lists.");
+pub const STEP_LIMIT_EXCEEDED: Message = message("R013", "Step limit exceeded.");
+pub const UNDEFINED_VARIABLE: Message = message("R014", "Undefined variable '{}'.");
+pub const UNDEFINED_VARIABLE_SUGGESTION: Message =
+    message("R015", "Undefined variable '{}'. Did you mean '{}'?");
+pub const OUTPUT_LIMIT_EXCEEDED: Message = message("R017", "Output limit of {} bytes exceeded.");
+pub const ONLY_INSTANCES_HAVE_FIELDS: Message = message("R018", "Only instances have fields.");
+pub const ONLY_INDEX_COLLECTIONS: Message = message("R019", "Can only index lists and maps.");
+pub const LIST_INDEX_NOT_INTEGER: Message =
+    message("R020", "List index must be a non-negative integer.");
+pub const LIST_INDEX_OUT_OF_RANGE: Message = message(
+    "R021",
+    "List index {} is out of range for a list of length {}.",
+);
+pub const UNDEFINED_KEY: Message = message("R022", "Undefined key '{}'.");
+pub const SUPERCLASS_NOT_CLASS: Message = message("R023", "Superclass must be a class.");
+pub const FRAMES_OMITTED: Message = message("R024", "... {} frames omitted (recursive call to {})");
+pub const FRAMES_OMITTED_MUTUAL: Message = message(
+    "R025",
+    "... {} frames omitted (mutually recursive calls to {})",
+);
+pub const LABEL_RAISED: Message = message("R026", "raised here");
+pub const TIME_LIMIT_EXCEEDED: Message = message("R027", "Time limit of {} exceeded.");
+pub const WRONG_TYPE: Message = message("R028", "Expected {} but got {}.");
+pub const ARGUMENT_NOUN: Message = message("R029", "argument");
+pub const ARGUMENTS_NOUN: Message = message("R030", "arguments");
+pub const ARITY_AT_LEAST: Message = message("R031", "at least {} {}");
+pub const ARITY_BETWEEN: Message = message("R032", "{} to {} {}");
+pub const OUTPUT_FAILED: Message = message("R033", "Failed to write output: {}.");
+
+// Files
+pub const CANNOT_READ_FILE: Message = message("F001", "Could not read file '{}': {}.");
+
+// Natives
+pub const ARGUMENT_NOT_STRING: Message = message("N001", "Argument to '{}' must be a string.");
+pub const ARGUMENTS_NOT_STRINGS: Message = message("N002", "Arguments to '{}' must be strings.");
+pub const ARGUMENT_NOT_ROWS: Message = message("N003", "Argument to '{}' must be a list of lists.");
+pub const ARGUMENT_NOT_FOREIGN: Message =
+    message("N004", "Argument to '{}' must be a foreign value.");
+pub const ARGUMENT_NOT_FUNCTION: Message = message("N005", "Argument to '{}' must be a function.");
+pub const ARGUMENT_NOT_DURATION: Message =
+    message("N006", "Argument to '{}' must be a non-negative number.");
+pub const ARGUMENTS_NOT_POSITION: Message =
+    message("N007", "Arguments to '{}' must be non-negative numbers.");
+pub const INVALID_CSV: Message = message("N008", "Invalid CSV: {}.");
+pub const INVALID_TOML: Message = message("N009", "Invalid TOML {}.");
+pub const INVALID_TOML_REASON: Message = message("N010", "Invalid TOML {}: {}.");
+pub const INVALID_YAML: Message = message("N011", "Invalid YAML: {}");
+pub const YAML_KEY_NOT_SCALAR: Message = message("N012", "YAML map keys must be scalars.");
+pub const INVALID_STOPWATCH: Message = message("N013", "Invalid stopwatch handle.");
+pub const TIMERS_SANDBOXED: Message = message("N014", "Timers are disabled in the sandbox.");
+pub const TIMER_CALLBACK_ARITY: Message =
+    message("N015", "Timer callbacks must take no arguments.");
+pub const TIMER_ARGUMENTS: Message = message(
+    "N016",
+    "Arguments to '{}' must be a function and a non-negative number.",
+);
+pub const INVALID_TIMER_ID: Message = message("N017", "Invalid timer id.");
+pub const WORKERS_SANDBOXED: Message = message("N018", "Workers are disabled in the sandbox.");
+pub const CANNOT_READ_WORKER: Message = message("N019", "Could not read '{}': {}.");
+pub const SEND_NOT_WORKER: Message = message("N020", "First argument to 'send' must be a worker.");
+pub const ONLY_PLAIN_DATA: Message =
+    message("N021", "Only plain data can be sent between workers.");
+pub const WORKER_STOPPED: Message = message("N022", "Worker has stopped.");
+pub const INPUT_SANDBOXED: Message = message("N023", "Reading input is disabled in the sandbox.");
+pub const CANNOT_READ_INPUT: Message = message("N024", "Failed to read input: {}.");
+pub const INPUT_NOT_NUMBER: Message = message("N025", "Input '{}' is not a number.");
+pub const UNKNOWN_COLOR: Message = message("N026", "Unknown color '{}'.");
+pub const FILES_SANDBOXED: Message = message("N027", "File access is disabled in the sandbox.");
+pub const UNKNOWN_FILE_MODE: Message = message("N028", "Unknown file mode '{}'.");
+pub const CANNOT_OPEN_FILE: Message = message("N029", "Could not open '{}': {}.");
+pub const CANNOT_READ_OPEN_FILE: Message = message("N030", "Could not read file: {}.");
+pub const CANNOT_WRITE_FILE: Message = message("N031", "Could not write file: {}.");
+pub const FILE_NOT_READABLE: Message = message("N032", "File is not open for reading.");
+pub const FILE_NOT_WRITABLE: Message = message("N033", "File is not open for writing.");
+pub const NETWORK_SANDBOXED: Message =
+    message("N034", "Network access is disabled in the sandbox.");
+pub const HTTP_FAILED: Message = message("N035", "HTTP request failed: {}.");
+pub const EMPTY_SEPARATOR: Message = message("N036", "Separator for '{}' can't be empty.");
+pub const POP_EMPTY_LIST: Message = message("N037", "Can't pop from an empty list.");
+pub const TRACE_ENDED: Message = message("N038", "Replay trace ended early.");
+pub const REPLAY_DIVERGED: Message = message(
+    "N039",
+    "Replay diverged: trace has '{}' where the program called '{}'.",
+);
+pub const ARGUMENTS_NOT_INDICES: Message = message(
+    "N040",
+    "Arguments to '{}' must be whole numbers from 0 to the string's length, {}.",
+);
+pub const SUBSTRING_END_BEFORE_START: Message =
+    message("N041", "End of 'substring' comes before its start.");
+pub const EMPTY_REPLACE_TEXT: Message = message("N042", "Text to '{}' can't be empty.");
+pub const ARGUMENTS_NOT_NUMBERS: Message = message("N043", "Arguments to '{}' must be numbers.");
+pub const REPLAYED_SEED_NOT_NUMBER: Message =
+    message("N044", "Replayed random seed is not a number.");
+pub const RANDOM_INT_BOUNDS: Message = message(
+    "N045",
+    "Arguments to '{}' must be whole numbers, the first no greater than the second.",
+);
+pub const ENV_SANDBOXED: Message =
+    message("N046", "Environment variables are hidden in the sandbox.");
+pub const EXIT_STATUS_RANGE: Message = message(
+    "N047",
+    "Argument to '{}' must be a whole number from 0 to 255.",
+);
+pub const EXITED: Message = message("N048", "Exited with status {}.");
+pub const STRING_INDEX_NOT_INTEGER: Message =
+    message("N049", "String index must be a non-negative integer.");
+pub const STRING_INDEX_OUT_OF_RANGE: Message = message(
+    "N050",
+    "String index {} is out of range for a string of length {}.",
+);
+
+// Lints
+pub const UNUSED_VARIABLE: Message = message("L001", "Local variable '{}' is never used.");
+pub const UNUSED_VARIABLE_HELP: Message = message("L002", "remove '{}' or use it");
+pub const SHADOWED_VARIABLE: Message =
+    message("L003", "'{}' shadows the variable declared on line {}.");
+pub const SHADOWED_VARIABLE_HELP: Message =
+    message("L004", "rename it to keep the outer '{}' visible");
+pub const UNREACHABLE_CODE: Message = message("L005", "Code after '{}' is unreachable.");
+pub const UNREACHABLE_CODE_HELP: Message = message("L006", "remove the statements that follow it");
+pub const IF_ALWAYS_TRUE: Message = message("L007", "This 'if' condition is always true.");
+pub const IF_ALWAYS_TRUE_HELP: Message =
+    message("L008", "remove the 'if' and keep its then branch");
+pub const IF_ALWAYS_FALSE: Message = message("L009", "This 'if' condition is always false.");
+pub const IF_ALWAYS_FALSE_HELP: Message =
+    message("L010", "remove the 'if' and keep its else branch, if any");
+pub const LOOP_ALWAYS_FALSE: Message = message("L011", "This '{}' condition is always false.");
+pub const LOOP_ALWAYS_FALSE_HELP: Message = message("L012", "the body never runs; remove the loop");
+pub const SELF_ASSIGNMENT: Message = message("L013", "'{}' is assigned to itself.");
+pub const SELF_ASSIGNMENT_HELP: Message = message("L014", "remove the assignment");
+pub const VARIABLE_TYPE_MISMATCH: Message =
+    message("L015", "'{}' is declared as {} but is given {}.");
+pub const ARGUMENT_TYPE_MISMATCH: Message =
+    message("L016", "Argument {} of '{}' should be {} but is {}.");
+pub const RETURN_TYPE_MISMATCH: Message = message("L017", "'{}' should return {} but returns {}.");
+pub const TYPE_MISMATCH_HELP: Message = message("L018", "change the value or the type annotation");
+pub const UNKNOWN_TYPE: Message = message("L019", "Unknown type '{}'.");
+pub const UNKNOWN_TYPE_HELP: Message = message("L020", "use one of: {}");
+pub const TYPE_CHECK_FAILED: Message = message("L021", "Type checking failed with {} error(s).");
+pub const MIXED_TYPE_EQUALITY: Message =
+    message("L022", "Comparing a {} with a {} using '{}' is always {}.");
+pub const MIXED_TYPE_EQUALITY_HELP: Message =
+    message("L023", "values of different types are never equal");
+pub const FUNCTION_CONDITION: Message = message(
+    "L024",
+    "The condition is the function '{}', which is always truthy.",
+);
+pub const FUNCTION_CONDITION_HELP: Message = message("L025", "did you mean to call it?");
+pub const IMPLICIT_NIL: Message = message(
+    "L026",
+    "'{}' ended without returning a value, so it returned nil.",
+);
+pub const IMPLICIT_NIL_HELP: Message = message("L027", "add a return statement to '{}'");
+
+const DUTCH: &[(&str, &str)] = &[
+    ("S001", "Onverwacht teken."),
+    ("S002", "Niet afgesloten string."),
+    ("S003", "hier"),
+    ("P001", "Labels kunnen alleen aan lussen worden gegeven."),
+    ("P002", "Verwacht ';' na 'break'."),
+    ("P003", "Verwacht ';' na 'continue'."),
+    ("P004", "'{}' kan niet buiten een lus worden gebruikt."),
+    ("P005", "Geen omliggende lus met label '{}'."),
+    ("P006", "Verwacht '(' na 'for'."),
+    ("P007", "Verwacht ';' na de lusvoorwaarde."),
+    ("P008", "Verwacht ')' na de for-clausules."),
+    ("P009", "Verwacht '(' na 'if'."),
+    ("P010", "Verwacht ')' na de if-voorwaarde."),
+    ("P011", "Verwacht ';' na de waarde."),
+    ("P012", "Verwacht ';' na de returnwaarde."),
+    ("P013", "Verwacht '(' na 'while'."),
+    ("P014", "Verwacht ')' na de voorwaarde."),
+    ("P015", "Verwacht '}' na het blok."),
+    ("P016", "Verwacht een variabelenaam."),
+    ("P017", "Verwacht ';' na de variabeledeclaratie."),
+    ("P018", "Verwacht ';' na de expressie."),
+    ("P019", "Verwacht de naam van de {}."),
+    ("P020", "Verwacht '(' na de naam van de {}."),
+    ("P021", "Verwacht '{' voor de body van de {}."),
+    ("P022", "Niet meer dan 255 parameters toegestaan."),
+    ("P023", "Verwacht een parameternaam."),
+    ("P024", "Verwacht ')' na de parameters."),
+    ("P025", "Verwacht '(' na 'fun'."),
+    ("P026", "Verwacht '{' voor de functiebody."),
+    ("P027", "Ongeldig doel voor toewijzing."),
+    ("P028", "Niet meer dan 255 argumenten toegestaan."),
+    ("P029", "Verwacht ')' na de argumenten."),
+    ("P030", "Verwacht ']' na de elementen van de lijst."),
+    ("P031", "Verwacht een eigenschapsnaam na '.'."),
+    ("P032", "Verwacht ')' na de expressie."),
+    ("P033", "Verwacht een expressie."),
+    ("P034", "Te veel fouten, gestopt."),
+    ("P035", "Verwacht ']' na de index."),
+    ("P036", "Verwacht '{' na 'try'."),
+    ("P037", "Verwacht 'catch' na het try-blok."),
+    ("P038", "Verwacht '(' na 'catch'."),
+    ("P039", "Verwacht een naam voor de foutvariabele."),
+    ("P040", "Verwacht ')' na de naam van de foutvariabele."),
+    ("P041", "Verwacht '{' voor de catch-body."),
+    ("P042", "Verwacht ';' na de gegooide waarde."),
+    ("P043", "Verwacht een typenaam."),
+    ("P044", "Verwacht '}' na de klassebody."),
+    ("P045", "Kan 'this' niet buiten een klasse gebruiken."),
+    ("P046", "Kan geen waarde teruggeven vanuit een initializer."),
+    ("P047", "Verwacht de naam van de superklasse."),
+    ("P048", "Een klasse kan niet van zichzelf erven."),
+    ("P049", "Kan 'super' niet buiten een klasse gebruiken."),
+    ("P050", "Kan 'super' niet gebruiken in een klasse zonder superklasse."),
+    ("P051", "Verwacht '.' na 'super'."),
+    ("P052", "Verwacht de naam van een methode van de superklasse."),
+    ("P053", "Verwacht ':' na de sleutel in een map."),
+    ("P054", "Verwacht '}' na de elementen van een map."),
+    (
+        "P055",
+        "De operand van '{}' moet een variabele, eigenschap of lijstelement zijn.",
+    ),
+    (
+        "P056",
+        "Verwacht ':' en een else-tak na de then-tak van '?'.",
+    ),
+    ("P057", "Verwacht het einde van de expressie."),
+    ("P058", "hier onverwacht"),
+    ("P059", "de invoer eindigt hier"),
+    ("R001", "Operanden moeten getallen zijn."),
+    ("R002", "Operand moet een getal zijn."),
+    (
+        "R003",
+        "Operanden moeten twee getallen of twee strings zijn.",
+    ),
+    (
+        "R004",
+        "In een string kan alleen naar een string worden gezocht.",
+    ),
+    ("R005", "Sleutels van een map moeten strings zijn."),
+    (
+        "R006",
+        "De rechteroperand van 'in' moet een string, lijst of map zijn.",
+    ),
+    ("R007", "Stack overflow."),
+    ("R008", "Verwacht {} maar kreeg {}."),
+    (
+        "R009",
+        "Alleen functies en klassen kunnen worden aangeroepen.",
+    ),
+    ("R010", "Onbekende eigenschap '{}'."),
+    (
+        "R011",
+        "Alleen instanties, strings, getallen, lijsten, maps en foreign waarden hebben eigenschappen.",
+    ),
+    ("R012", "Alleen lijsten kunnen worden gespreid."),
+    ("R013", "Staplimiet overschreden."),
+    ("R014", "Onbekende variabele '{}'."),
+    ("R015", "Onbekende variabele '{}'. Bedoelde je '{}'?"),
+    ("R017", "Uitvoerlimiet van {} bytes overschreden."),
+    ("R018", "Alleen instanties hebben velden."),
+    ("R019", "Alleen lijsten en maps kunnen geïndexeerd worden."),
+    (
+        "R020",
+        "Een lijstindex moet een niet-negatief geheel getal zijn.",
+    ),
+    ("R021", "Lijstindex {} valt buiten een lijst met lengte {}."),
+    ("R022", "Onbekende sleutel '{}'."),
+    ("R023", "De superklasse moet een klasse zijn."),
+    ("R024", "... {} frames weggelaten (recursieve aanroep van {})"),
+    ("R025", "... {} frames weggelaten (wederzijds recursieve aanroepen van {})"),
+    ("R026", "hier opgetreden"),
+    ("R027", "Tijdslimiet van {} overschreden."),
+    ("R028", "Verwacht {} maar kreeg {}."),
+    ("R029", "argument"),
+    ("R030", "argumenten"),
+    ("R031", "minstens {} {}"),
+    ("R032", "{} tot {} {}"),
+    ("R033", "Kon de uitvoer niet schrijven: {}."),
+    ("F001", "Kon bestand '{}' niet lezen: {}."),
+    ("N001", "Het argument van '{}' moet een string zijn."),
+    ("N002", "De argumenten van '{}' moeten strings zijn."),
+    ("N003", "Het argument van '{}' moet een lijst van lijsten zijn."),
+    ("N004", "Het argument van '{}' moet een foreign waarde zijn."),
+    ("N005", "Het argument van '{}' moet een functie zijn."),
+    ("N006", "Het argument van '{}' moet een niet-negatief getal zijn."),
+    ("N007", "De argumenten van '{}' moeten niet-negatieve getallen zijn."),
+    ("N008", "Ongeldige CSV: {}."),
+    ("N009", "Ongeldige TOML {}."),
+    ("N010", "Ongeldige TOML {}: {}."),
+    ("N011", "Ongeldige YAML: {}"),
+    ("N012", "Sleutels van YAML-maps moeten scalairen zijn."),
+    ("N013", "Ongeldige stopwatch."),
+    ("N014", "Timers zijn uitgeschakeld in de sandbox."),
+    ("N015", "Callbacks van timers mogen geen argumenten hebben."),
+    ("N016", "De argumenten van '{}' moeten een functie en een niet-negatief getal zijn."),
+    ("N017", "Ongeldig timer-id."),
+    ("N018", "Workers zijn uitgeschakeld in de sandbox."),
+    ("N019", "Kon '{}' niet lezen: {}."),
+    ("N020", "Het eerste argument van 'send' moet een worker zijn."),
+    ("N021", "Alleen gewone gegevens kunnen tussen workers worden verstuurd."),
+    ("N022", "De worker is gestopt."),
+    ("N023", "Invoer lezen is uitgeschakeld in de sandbox."),
+    ("N024", "Kon invoer niet lezen: {}."),
+    ("N025", "Invoer '{}' is geen getal."),
+    ("N026", "Onbekende kleur '{}'."),
+    ("N027", "Bestandstoegang is uitgeschakeld in de sandbox."),
+    ("N028", "Onbekende bestandsmodus '{}'."),
+    ("N029", "Kon '{}' niet openen: {}."),
+    ("N030", "Kon bestand niet lezen: {}."),
+    ("N031", "Kon bestand niet schrijven: {}."),
+    ("N032", "Het bestand is niet geopend om te lezen."),
+    ("N033", "Het bestand is niet geopend om te schrijven."),
+    ("N034", "Netwerktoegang is uitgeschakeld in de sandbox."),
+    ("N035", "HTTP-verzoek mislukt: {}."),
+    ("N036", "Het scheidingsteken van '{}' mag niet leeg zijn."),
+    ("N037", "Kan niets verwijderen uit een lege lijst."),
+    ("N038", "Het replay-spoor eindigde te vroeg."),
+    ("N039", "Replay week af: het spoor heeft '{}' waar het programma '{}' aanriep."),
+    (
+        "N040",
+        "De argumenten van '{}' moeten gehele getallen van 0 tot de lengte van de string, {}, zijn.",
+    ),
+    ("N041", "Het einde van 'substring' ligt voor het begin."),
+    ("N042", "De te vervangen tekst van '{}' mag niet leeg zijn."),
+    ("N043", "De argumenten van '{}' moeten getallen zijn."),
+    ("N044", "De afgespeelde random seed is geen getal."),
+    (
+        "N045",
+        "De argumenten van '{}' moeten gehele getallen zijn, het eerste niet groter dan het tweede.",
+    ),
+    ("N046", "Omgevingsvariabelen zijn verborgen in de sandbox."),
+    ("N047", "Het argument van '{}' moet een geheel getal van 0 tot 255 zijn."),
+    ("N048", "Afgesloten met status {}."),
+    (
+        "N049",
+        "Een stringindex moet een niet-negatief geheel getal zijn.",
+    ),
+    ("N050", "Stringindex {} valt buiten een string met lengte {}."),
+    ("L001", "Lokale variabele '{}' wordt nooit gebruikt."),
+    ("L002", "verwijder '{}' of gebruik hem"),
+    (
+        "L003",
+        "'{}' verbergt de variabele gedeclareerd op regel {}.",
+    ),
+    (
+        "L004",
+        "hernoem hem zodat de buitenste '{}' zichtbaar blijft",
+    ),
+    ("L005", "Code na '{}' wordt nooit bereikt."),
+    ("L006", "verwijder de statements die erop volgen"),
+    ("L007", "Deze 'if'-voorwaarde is altijd waar."),
+    ("L008", "verwijder de 'if' en behoud de then-tak"),
+    ("L009", "Deze 'if'-voorwaarde is altijd onwaar."),
+    ("L010", "verwijder de 'if' en behoud de eventuele else-tak"),
+    ("L011", "Deze '{}'-voorwaarde is altijd onwaar."),
+    ("L012", "de body wordt nooit uitgevoerd; verwijder de lus"),
+    ("L013", "'{}' wordt aan zichzelf toegewezen."),
+    ("L014", "verwijder de toewijzing"),
+    ("L015", "'{}' is gedeclareerd als {} maar krijgt {}."),
+    ("L016", "Argument {} van '{}' moet {} zijn maar is {}."),
+    ("L017", "'{}' moet {} teruggeven maar geeft {}."),
+    ("L018", "pas de waarde of de typeannotatie aan"),
+    ("L019", "Onbekend type '{}'."),
+    ("L020", "gebruik een van: {}"),
+    ("L021", "Typecontrole mislukt met {} fout(en)."),
+    ("L022", "Een {} vergelijken met een {} met '{}' is altijd {}."),
+    ("L023", "waarden van verschillende typen zijn nooit gelijk"),
+    (
+        "L024",
+        "De voorwaarde is de functie '{}', die altijd waar is.",
+    ),
+    ("L025", "wilde je hem aanroepen?"),
+    (
+        "L026",
+        "'{}' eindigde zonder een waarde terug te geven en gaf dus nil terug.",
+    ),
+    ("L027", "voeg een return-statement toe aan '{}'"),
+];
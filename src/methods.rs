@@ -0,0 +1,311 @@
+//! Methods on built-in values, so that `"abc".len()`, `xs.push(4)` and
+//! `n.floor()` work without wrapping the value in an object.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use crate::interpreter::Interpreter;
+use crate::lox_error::{LoxError, NativeError};
+use crate::messages;
+use crate::token::{BuiltinMethod, Callable, Literal, MethodFn};
+
+type MethodTable = &'static [(&'static str, usize, MethodFn)];
+
+fn string_argument<'a>(method: &str, arguments: &'a [Literal]) -> Result<&'a str, LoxError> {
+    match &arguments[0] {
+        Literal::String(text) => Ok(text),
+        _ => Err(NativeError::from_message(messages::ARGUMENT_NOT_STRING, &[&method]).into()),
+    }
+}
+
+/// The position argument `index` of `method`, which must be a whole number
+/// no greater than `len`.
+fn index_argument(
+    method: &str,
+    arguments: &[Literal],
+    index: usize,
+    len: usize,
+) -> Result<usize, LoxError> {
+    match arguments[index] {
+        Literal::Number(n) if n.fract() == 0.0 && n >= 0.0 && n <= len as f64 => Ok(n as usize),
+        _ => {
+            Err(NativeError::from_message(messages::ARGUMENTS_NOT_INDICES, &[&method, &len]).into())
+        }
+    }
+}
+
+fn string(receiver: &Literal) -> &str {
+    match receiver {
+        Literal::String(text) => text,
+        _ => unreachable!("String method called on a non-string"),
+    }
+}
+
+fn number(receiver: &Literal) -> f64 {
+    match receiver {
+        Literal::Number(n) => *n,
+        _ => unreachable!("Number method called on a non-number"),
+    }
+}
+
+fn list(receiver: &Literal) -> &Rc<RefCell<Vec<Literal>>> {
+    match receiver {
+        Literal::List(elements) => elements,
+        _ => unreachable!("List method called on a non-list"),
+    }
+}
+
+fn string_len(_: &mut Interpreter, receiver: &Literal, _: &[Literal]) -> Result<Literal, LoxError> {
+    Ok(Literal::Number(string(receiver).chars().count() as f64))
+}
+
+fn upper(_: &mut Interpreter, receiver: &Literal, _: &[Literal]) -> Result<Literal, LoxError> {
+    Ok(Literal::String(string(receiver).to_uppercase()))
+}
+
+fn lower(_: &mut Interpreter, receiver: &Literal, _: &[Literal]) -> Result<Literal, LoxError> {
+    Ok(Literal::String(string(receiver).to_lowercase()))
+}
+
+fn trim(_: &mut Interpreter, receiver: &Literal, _: &[Literal]) -> Result<Literal, LoxError> {
+    Ok(Literal::String(string(receiver).trim().to_string()))
+}
+
+fn contains(
+    _: &mut Interpreter,
+    receiver: &Literal,
+    arguments: &[Literal],
+) -> Result<Literal, LoxError> {
+    let needle = string_argument("contains", arguments)?;
+    Ok(Literal::Bool(string(receiver).contains(needle)))
+}
+
+fn split(
+    _: &mut Interpreter,
+    receiver: &Literal,
+    arguments: &[Literal],
+) -> Result<Literal, LoxError> {
+    let separator = string_argument("split", arguments)?;
+    if separator.is_empty() {
+        return Err(NativeError::from_message(messages::EMPTY_SEPARATOR, &[&"split"]).into());
+    }
+    let parts = string(receiver)
+        .split(separator)
+        .map(|part| Literal::String(part.to_string()))
+        .collect();
+    Ok(Literal::List(Rc::new(RefCell::new(parts))))
+}
+
+fn substring(
+    _: &mut Interpreter,
+    receiver: &Literal,
+    arguments: &[Literal],
+) -> Result<Literal, LoxError> {
+    let text = string(receiver);
+    let len = text.chars().count();
+    let start = index_argument("substring", arguments, 0, len)?;
+    let end = index_argument("substring", arguments, 1, len)?;
+    if end < start {
+        return Err(NativeError::from_message(messages::SUBSTRING_END_BEFORE_START, &[]).into());
+    }
+    let substring = text.chars().skip(start).take(end - start);
+    Ok(Literal::String(substring.collect()))
+}
+
+fn char_at(
+    _: &mut Interpreter,
+    receiver: &Literal,
+    arguments: &[Literal],
+) -> Result<Literal, LoxError> {
+    let text = string(receiver);
+    let Literal::Number(n) = arguments[0] else {
+        return Err(NativeError::from_message(messages::STRING_INDEX_NOT_INTEGER, &[]).into());
+    };
+    if n < 0.0 || n.fract() != 0.0 {
+        return Err(NativeError::from_message(messages::STRING_INDEX_NOT_INTEGER, &[]).into());
+    }
+    match text.chars().nth(n as usize) {
+        Some(c) => Ok(Literal::String(c.to_string())),
+        None => {
+            let len = text.chars().count();
+            Err(NativeError::from_message(messages::STRING_INDEX_OUT_OF_RANGE, &[&n, &len]).into())
+        }
+    }
+}
+
+/// The position of the first `needle`, counted in characters, or -1.
+fn index_of(
+    _: &mut Interpreter,
+    receiver: &Literal,
+    arguments: &[Literal],
+) -> Result<Literal, LoxError> {
+    let text = string(receiver);
+    let needle = string_argument("indexOf", arguments)?;
+    let index = text
+        .find(needle)
+        .map_or(-1.0, |byte| text[..byte].chars().count() as f64);
+    Ok(Literal::Number(index))
+}
+
+fn replace(
+    _: &mut Interpreter,
+    receiver: &Literal,
+    arguments: &[Literal],
+) -> Result<Literal, LoxError> {
+    let (Literal::String(from), Literal::String(to)) = (&arguments[0], &arguments[1]) else {
+        return Err(
+            NativeError::from_message(messages::ARGUMENTS_NOT_STRINGS, &[&"replace"]).into(),
+        );
+    };
+    if from.is_empty() {
+        return Err(NativeError::from_message(messages::EMPTY_REPLACE_TEXT, &[&"replace"]).into());
+    }
+    Ok(Literal::String(string(receiver).replace(from.as_str(), to)))
+}
+
+fn floor(_: &mut Interpreter, receiver: &Literal, _: &[Literal]) -> Result<Literal, LoxError> {
+    Ok(Literal::Number(number(receiver).floor()))
+}
+
+fn ceil(_: &mut Interpreter, receiver: &Literal, _: &[Literal]) -> Result<Literal, LoxError> {
+    Ok(Literal::Number(number(receiver).ceil()))
+}
+
+fn round(_: &mut Interpreter, receiver: &Literal, _: &[Literal]) -> Result<Literal, LoxError> {
+    Ok(Literal::Number(number(receiver).round()))
+}
+
+fn abs(_: &mut Interpreter, receiver: &Literal, _: &[Literal]) -> Result<Literal, LoxError> {
+    Ok(Literal::Number(number(receiver).abs()))
+}
+
+fn list_len(_: &mut Interpreter, receiver: &Literal, _: &[Literal]) -> Result<Literal, LoxError> {
+    Ok(Literal::Number(list(receiver).borrow().len() as f64))
+}
+
+fn push(
+    _: &mut Interpreter,
+    receiver: &Literal,
+    arguments: &[Literal],
+) -> Result<Literal, LoxError> {
+    list(receiver).borrow_mut().push(arguments[0].clone());
+    Ok(Literal::None)
+}
+
+fn pop(_: &mut Interpreter, receiver: &Literal, _: &[Literal]) -> Result<Literal, LoxError> {
+    list(receiver)
+        .borrow_mut()
+        .pop()
+        .ok_or_else(|| NativeError::from_message(messages::POP_EMPTY_LIST, &[]).into())
+}
+
+fn map(receiver: &Literal) -> &Rc<RefCell<BTreeMap<String, Literal>>> {
+    match receiver {
+        Literal::Map(entries) => entries,
+        _ => unreachable!("Map method called on a non-map"),
+    }
+}
+
+fn new_list(elements: Vec<Literal>) -> Literal {
+    Literal::List(Rc::new(RefCell::new(elements)))
+}
+
+fn map_len(_: &mut Interpreter, receiver: &Literal, _: &[Literal]) -> Result<Literal, LoxError> {
+    Ok(Literal::Number(map(receiver).borrow().len() as f64))
+}
+
+fn keys(_: &mut Interpreter, receiver: &Literal, _: &[Literal]) -> Result<Literal, LoxError> {
+    let keys = map(receiver)
+        .borrow()
+        .keys()
+        .cloned()
+        .map(Literal::String)
+        .collect();
+    Ok(new_list(keys))
+}
+
+fn values(_: &mut Interpreter, receiver: &Literal, _: &[Literal]) -> Result<Literal, LoxError> {
+    Ok(new_list(map(receiver).borrow().values().cloned().collect()))
+}
+
+/// The entries as `[key, value]` lists, in key order, for looping over.
+fn entries(_: &mut Interpreter, receiver: &Literal, _: &[Literal]) -> Result<Literal, LoxError> {
+    let entries = map(receiver)
+        .borrow()
+        .iter()
+        .map(|(key, value)| new_list(vec![Literal::String(key.clone()), value.clone()]))
+        .collect();
+    Ok(new_list(entries))
+}
+
+/// Deletes `key`, returning its value, or `nil` if it wasn't there.
+fn remove(
+    _: &mut Interpreter,
+    receiver: &Literal,
+    arguments: &[Literal],
+) -> Result<Literal, LoxError> {
+    let key = string_argument("remove", arguments)?;
+    Ok(map(receiver)
+        .borrow_mut()
+        .remove(key)
+        .unwrap_or(Literal::None))
+}
+
+const STRING_METHODS: MethodTable = &[
+    ("len", 0, string_len),
+    ("upper", 0, upper),
+    ("toUpperCase", 0, upper),
+    ("lower", 0, lower),
+    ("toLowerCase", 0, lower),
+    ("trim", 0, trim),
+    ("contains", 1, contains),
+    ("split", 1, split),
+    ("substring", 2, substring),
+    ("charAt", 1, char_at),
+    ("indexOf", 1, index_of),
+    ("replace", 2, replace),
+];
+
+const NUMBER_METHODS: MethodTable = &[
+    ("floor", 0, floor),
+    ("ceil", 0, ceil),
+    ("round", 0, round),
+    ("abs", 0, abs),
+];
+
+const LIST_METHODS: MethodTable = &[("len", 0, list_len), ("push", 1, push), ("pop", 0, pop)];
+
+const MAP_METHODS: MethodTable = &[
+    ("len", 0, map_len),
+    ("keys", 0, keys),
+    ("values", 0, values),
+    ("entries", 0, entries),
+    ("remove", 1, remove),
+];
+
+/// The methods of `value`'s type, or `None` if the type has no properties.
+fn methods(value: &Literal) -> Option<MethodTable> {
+    match value {
+        Literal::String(_) => Some(STRING_METHODS),
+        Literal::Number(_) => Some(NUMBER_METHODS),
+        Literal::List(_) => Some(LIST_METHODS),
+        Literal::Map(_) => Some(MAP_METHODS),
+        _ => None,
+    }
+}
+
+/// Whether `value`'s type has methods, even if `name` isn't one of them.
+pub fn has_methods(value: &Literal) -> bool {
+    methods(value).is_some()
+}
+
+/// Binds the method `name` of a built-in value, e.g. `push` for a list.
+pub fn lookup_method(value: &Literal, name: &str) -> Option<Callable> {
+    let &(_, arity, closure) = methods(value)?.iter().find(|(n, ..)| *n == name)?;
+    Some(Callable::BuiltinMethod(BuiltinMethod {
+        receiver: Box::new(value.clone()),
+        arity,
+        closure,
+    }))
+}
@@ -1,28 +1,859 @@
-use crate::environment::Environment;
+use crate::actors::Message;
+use crate::file_handle::FileHandle;
+use crate::foreign::{Finalized, WeakRef};
+#[cfg(debug_assertions)]
+use crate::heap_snapshot;
 use crate::interpreter::Interpreter;
-use crate::lox_error::LoxError;
-use crate::token::{Callable, Literal, NativeFunction, Token};
-use crate::token_type::TokenType;
+use crate::lox_error::{ExitError, LoxError, NativeError};
+use crate::messages;
+use crate::random::Random;
+use crate::token::Arity::{self, AtLeast, Between, Exactly};
+#[cfg(feature = "async")]
+use crate::token::{AsyncNativeFunction, NativeFuture};
+use crate::token::{Callable, Literal, NativeFn, NativeFunction};
+use crate::value;
 
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-fn clock_fn(_interpreter: &mut Interpreter, _arguments: &[Literal]) -> Result<Literal, LoxError> {
-    let now = SystemTime::now();
-    let secs = now.duration_since(UNIX_EPOCH).unwrap().as_secs_f64();
-    Ok(Literal::Number(secs))
+fn clock_fn(interpreter: &mut Interpreter, _arguments: &[Literal]) -> Result<Literal, LoxError> {
+    interpreter.tape.observe("clock", || {
+        let now = SystemTime::now();
+        let secs = now.duration_since(UNIX_EPOCH).unwrap().as_secs_f64();
+        Ok(Literal::Number(secs))
+    })
 }
 
-pub fn setup_native_functions(environment: &mut Environment) {
-    environment.define(
-        &Token {
-            type_: TokenType::Fun,
-            lexeme: "clock".to_string(),
-            literal: None,
-            line: 0,
+#[cfg(feature = "crypto")]
+fn hash_argument<'a>(name: &str, arguments: &'a [Literal]) -> Result<&'a str, LoxError> {
+    match &arguments[0] {
+        Literal::String(text) => Ok(text),
+        _ => Err(NativeError::from_message(messages::ARGUMENT_NOT_STRING, &[&name]).into()),
+    }
+}
+
+#[cfg(feature = "crypto")]
+fn sha256_fn(_interpreter: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, LoxError> {
+    use sha2::{Digest, Sha256};
+    let text = hash_argument("sha256", arguments)?;
+    Ok(Literal::String(format!("{:x}", Sha256::digest(text))))
+}
+
+#[cfg(feature = "crypto")]
+fn md5_fn(_interpreter: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, LoxError> {
+    use md5::{Digest, Md5};
+    let text = hash_argument("md5", arguments)?;
+    Ok(Literal::String(format!("{:x}", Md5::digest(text))))
+}
+
+#[cfg(feature = "crypto")]
+fn crc32_fn(_interpreter: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, LoxError> {
+    let text = hash_argument("crc32", arguments)?;
+    Ok(Literal::String(format!(
+        "{:08x}",
+        crc32fast::hash(text.as_bytes())
+    )))
+}
+
+fn csv_parse_fn(
+    _interpreter: &mut Interpreter,
+    arguments: &[Literal],
+) -> Result<Literal, LoxError> {
+    let Literal::String(text) = &arguments[0] else {
+        return Err(
+            NativeError::from_message(messages::ARGUMENT_NOT_STRING, &[&"csvParse"]).into(),
+        );
+    };
+
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(text.as_bytes());
+
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| NativeError::from_message(messages::INVALID_CSV, &[&e]))?;
+        let fields = record
+            .iter()
+            .map(|field| Literal::String(field.to_string()))
+            .collect();
+        rows.push(Literal::List(Rc::new(RefCell::new(fields))));
+    }
+    Ok(Literal::List(Rc::new(RefCell::new(rows))))
+}
+
+fn csv_stringify_fn(
+    interpreter: &mut Interpreter,
+    arguments: &[Literal],
+) -> Result<Literal, LoxError> {
+    let error = || NativeError::from_message(messages::ARGUMENT_NOT_ROWS, &[&"csvStringify"]);
+    let Literal::List(rows) = &arguments[0] else {
+        return Err(error().into());
+    };
+
+    let mut writer = csv::WriterBuilder::new()
+        .flexible(true)
+        .from_writer(Vec::new());
+    for row in rows.borrow().iter() {
+        let Literal::List(fields) = row else {
+            return Err(error().into());
+        };
+        let fields: Vec<String> = fields
+            .borrow()
+            .iter()
+            .map(|f| interpreter.stringify(f))
+            .collect();
+        writer
+            .write_record(&fields)
+            .map_err(|e| NativeError::from_message(messages::INVALID_CSV, &[&e]))?;
+    }
+
+    let bytes = writer.into_inner().expect("Writing to a Vec can't fail");
+    Ok(Literal::String(
+        String::from_utf8(bytes).expect("Fields are valid UTF-8"),
+    ))
+}
+
+#[cfg(feature = "config-formats")]
+fn from_toml(value: toml::Value) -> Literal {
+    match value {
+        toml::Value::String(s) => Literal::String(s),
+        toml::Value::Integer(i) => Literal::Number(i as f64),
+        toml::Value::Float(n) => Literal::Number(n),
+        toml::Value::Boolean(b) => Literal::Bool(b),
+        toml::Value::Datetime(d) => Literal::String(d.to_string()),
+        toml::Value::Array(elements) => Literal::List(Rc::new(RefCell::new(
+            elements.into_iter().map(from_toml).collect(),
+        ))),
+        toml::Value::Table(table) => Literal::Map(Rc::new(RefCell::new(
+            table.into_iter().map(|(k, v)| (k, from_toml(v))).collect(),
+        ))),
+    }
+}
+
+#[cfg(feature = "config-formats")]
+fn from_yaml(value: serde_yaml::Value) -> Result<Literal, NativeError> {
+    use serde_yaml::Value;
+    Ok(match value {
+        Value::Null => Literal::None,
+        Value::Bool(b) => Literal::Bool(b),
+        Value::Number(n) => Literal::Number(n.as_f64().unwrap_or(f64::NAN)),
+        Value::String(s) => Literal::String(s),
+        Value::Sequence(elements) => Literal::List(Rc::new(RefCell::new(
+            elements
+                .into_iter()
+                .map(from_yaml)
+                .collect::<Result<_, _>>()?,
+        ))),
+        Value::Mapping(mapping) => {
+            let mut map = std::collections::BTreeMap::new();
+            for (key, value) in mapping {
+                let key = match from_yaml(key)? {
+                    Literal::String(key) => key,
+                    key @ (Literal::Number(_) | Literal::Bool(_)) => key.to_string(),
+                    _ => {
+                        return Err(NativeError::from_message(
+                            messages::YAML_KEY_NOT_SCALAR,
+                            &[],
+                        ))
+                    }
+                };
+                map.insert(key, from_yaml(value)?);
+            }
+            Literal::Map(Rc::new(RefCell::new(map)))
+        }
+        Value::Tagged(tagged) => from_yaml(tagged.value)?,
+    })
+}
+
+#[cfg(feature = "config-formats")]
+fn toml_parse_fn(
+    _interpreter: &mut Interpreter,
+    arguments: &[Literal],
+) -> Result<Literal, LoxError> {
+    let Literal::String(text) = &arguments[0] else {
+        return Err(
+            NativeError::from_message(messages::ARGUMENT_NOT_STRING, &[&"tomlParse"]).into(),
+        );
+    };
+    let table: toml::Table = text.parse().map_err(|e: toml::de::Error| {
+        // The full error renders a multi-line source snippet; keep its
+        // location header only.
+        let rendered = e.to_string();
+        let location = rendered
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .trim_start_matches("TOML parse error ");
+        match e.message() {
+            "" => NativeError::from_message(messages::INVALID_TOML, &[&location]),
+            message => {
+                NativeError::from_message(messages::INVALID_TOML_REASON, &[&location, &message])
+            }
+        }
+    })?;
+    Ok(from_toml(toml::Value::Table(table)))
+}
+
+#[cfg(feature = "config-formats")]
+fn yaml_parse_fn(
+    _interpreter: &mut Interpreter,
+    arguments: &[Literal],
+) -> Result<Literal, LoxError> {
+    let Literal::String(text) = &arguments[0] else {
+        return Err(
+            NativeError::from_message(messages::ARGUMENT_NOT_STRING, &[&"yamlParse"]).into(),
+        );
+    };
+    let value = serde_yaml::from_str(text)
+        .map_err(|e| NativeError::from_message(messages::INVALID_YAML, &[&e]))?;
+    Ok(from_yaml(value)?)
+}
+
+fn stopwatch_start_fn(
+    interpreter: &mut Interpreter,
+    _arguments: &[Literal],
+) -> Result<Literal, LoxError> {
+    interpreter.stopwatches.push(Instant::now());
+    Ok(Literal::Number((interpreter.stopwatches.len() - 1) as f64))
+}
+
+fn stopwatch_elapsed_fn(
+    interpreter: &mut Interpreter,
+    arguments: &[Literal],
+) -> Result<Literal, LoxError> {
+    let start = match &arguments[0] {
+        Literal::Number(handle) if handle.fract() == 0.0 && *handle >= 0.0 => {
+            interpreter.stopwatches.get(*handle as usize)
+        }
+        _ => None,
+    };
+    let elapsed = match start {
+        Some(start) => start.elapsed().as_secs_f64(),
+        None => return Err(NativeError::from_message(messages::INVALID_STOPWATCH, &[]).into()),
+    };
+    interpreter
+        .tape
+        .observe("stopwatchElapsed", || Ok(Literal::Number(elapsed)))
+}
+
+fn schedule(
+    interpreter: &mut Interpreter,
+    name: &str,
+    arguments: &[Literal],
+    repeat: bool,
+) -> Result<Literal, LoxError> {
+    if interpreter.is_sandboxed() {
+        return Err(NativeError::from_message(messages::TIMERS_SANDBOXED, &[]).into());
+    }
+
+    match (&arguments[0], &arguments[1]) {
+        (Literal::Callable(callback), Literal::Number(ms)) if *ms >= 0.0 => {
+            if !callback.arity().accepts(0) {
+                return Err(NativeError::from_message(messages::TIMER_CALLBACK_ARITY, &[]).into());
+            }
+            let delay = Duration::from_secs_f64(ms / 1000.0);
+            let id = interpreter.timers.schedule(callback.clone(), delay, repeat);
+            Ok(Literal::Number(id as f64))
+        }
+        _ => Err(NativeError::from_message(messages::TIMER_ARGUMENTS, &[&name]).into()),
+    }
+}
+
+fn set_timeout_fn(
+    interpreter: &mut Interpreter,
+    arguments: &[Literal],
+) -> Result<Literal, LoxError> {
+    schedule(interpreter, "setTimeout", arguments, false)
+}
+
+fn set_interval_fn(
+    interpreter: &mut Interpreter,
+    arguments: &[Literal],
+) -> Result<Literal, LoxError> {
+    schedule(interpreter, "setInterval", arguments, true)
+}
+
+fn clear_timer_fn(
+    interpreter: &mut Interpreter,
+    arguments: &[Literal],
+) -> Result<Literal, LoxError> {
+    match &arguments[0] {
+        Literal::Number(id) if id.fract() == 0.0 && *id >= 0.0 => {
+            Ok(Literal::Bool(interpreter.timers.cancel(*id as usize)))
+        }
+        _ => Err(NativeError::from_message(messages::INVALID_TIMER_ID, &[]).into()),
+    }
+}
+
+fn run_event_loop_fn(
+    interpreter: &mut Interpreter,
+    _arguments: &[Literal],
+) -> Result<Literal, LoxError> {
+    while let Some(timer) = interpreter.timers.pop_next() {
+        let wait = timer.deadline.saturating_duration_since(Instant::now());
+        if !wait.is_zero() {
+            thread::sleep(wait);
+        }
+        timer.callback.call(interpreter, &[])?;
+    }
+    Ok(Literal::None)
+}
+
+fn spawn_fn(interpreter: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, LoxError> {
+    if interpreter.is_sandboxed() {
+        return Err(NativeError::from_message(messages::WORKERS_SANDBOXED, &[]).into());
+    }
+    let Literal::String(path) = &arguments[0] else {
+        return Err(NativeError::from_message(messages::ARGUMENT_NOT_STRING, &[&"spawn"]).into());
+    };
+
+    let source = std::fs::read_to_string(path)
+        .map_err(|e| NativeError::from_message(messages::CANNOT_READ_WORKER, &[path, &e]))?;
+    let address = interpreter.mailbox.spawn(path, source);
+    Ok(Literal::Foreign(Rc::new(RefCell::new(address))))
+}
+
+fn send_fn(interpreter: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, LoxError> {
+    match &arguments[0] {
+        Literal::Foreign(handle) if handle.borrow().type_name() == "worker" => handle
+            .borrow_mut()
+            .call_method(interpreter, "send", &arguments[1..]),
+        _ => Err(NativeError::from_message(messages::SEND_NOT_WORKER, &[]).into()),
+    }
+}
+
+fn receive_fn(interpreter: &mut Interpreter, _arguments: &[Literal]) -> Result<Literal, LoxError> {
+    let mailbox = &mut interpreter.mailbox;
+    interpreter.tape.observe("receive", || {
+        Ok(mailbox
+            .receive()
+            .map_or(Literal::None, Message::into_literal))
+    })
+}
+
+fn parent_fn(interpreter: &mut Interpreter, _arguments: &[Literal]) -> Result<Literal, LoxError> {
+    Ok(match interpreter.mailbox.parent() {
+        Some(address) => Literal::Foreign(Rc::new(RefCell::new(address))),
+        None => Literal::None,
+    })
+}
+
+fn term_width_fn(
+    interpreter: &mut Interpreter,
+    _arguments: &[Literal],
+) -> Result<Literal, LoxError> {
+    interpreter.tape.observe("termWidth", || {
+        let width = terminal_size::terminal_size().map_or(80, |(width, _)| width.0);
+        Ok(Literal::Number(width as f64))
+    })
+}
+
+/// Reads a line of input, recording it when recording a trace.
+fn read_input(interpreter: &mut Interpreter, name: &str) -> Result<Literal, LoxError> {
+    if interpreter.is_sandboxed() {
+        return Err(NativeError::from_message(messages::INPUT_SANDBOXED, &[]).into());
+    }
+
+    let mut tape = std::mem::take(&mut interpreter.tape);
+    let result = tape.observe(name, || match interpreter.read_line() {
+        Ok(line) => Ok(line.map_or(Literal::None, Literal::String)),
+        Err(e) => Err(NativeError::from_message(messages::CANNOT_READ_INPUT, &[&e]).into()),
+    });
+    interpreter.tape = tape;
+    result
+}
+
+fn read_line_fn(
+    interpreter: &mut Interpreter,
+    _arguments: &[Literal],
+) -> Result<Literal, LoxError> {
+    read_input(interpreter, "readLine")
+}
+
+fn read_number_fn(
+    interpreter: &mut Interpreter,
+    _arguments: &[Literal],
+) -> Result<Literal, LoxError> {
+    match read_input(interpreter, "readNumber")? {
+        Literal::String(line) => match line.trim().parse::<f64>() {
+            Ok(n) => Ok(Literal::Number(n)),
+            Err(_) => {
+                Err(NativeError::from_message(messages::INPUT_NOT_NUMBER, &[&line.trim()]).into())
+            }
+        },
+        _ => Ok(Literal::None),
+    }
+}
+
+fn set_color_fn(interpreter: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, LoxError> {
+    let code = match &arguments[0] {
+        Literal::String(name) => match name.as_str() {
+            "reset" => 0,
+            "black" => 30,
+            "red" => 31,
+            "green" => 32,
+            "yellow" => 33,
+            "blue" => 34,
+            "magenta" => 35,
+            "cyan" => 36,
+            "white" => 37,
+            _ => {
+                return Err(NativeError::from_message(messages::UNKNOWN_COLOR, &[name]).into());
+            }
         },
-        &Literal::Callable(Callable::NativeFunction(NativeFunction {
-            arity: 0,
-            closure: clock_fn,
-        })),
-    );
+        _ => {
+            return Err(
+                NativeError::from_message(messages::ARGUMENT_NOT_STRING, &[&"setColor"]).into(),
+            )
+        }
+    };
+    interpreter.write(&format!("\x1b[{}m", code))?;
+    Ok(Literal::None)
+}
+
+fn clear_screen_fn(
+    interpreter: &mut Interpreter,
+    _arguments: &[Literal],
+) -> Result<Literal, LoxError> {
+    interpreter.write("\x1b[2J\x1b[H")?;
+    Ok(Literal::None)
+}
+
+fn cursor_to_fn(interpreter: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, LoxError> {
+    match (&arguments[0], &arguments[1]) {
+        (Literal::Number(x), Literal::Number(y)) if *x >= 0.0 && *y >= 0.0 => {
+            // ANSI positions are 1-based, rows first
+            interpreter.write(&format!("\x1b[{};{}H", *y as u64 + 1, *x as u64 + 1))?;
+            Ok(Literal::None)
+        }
+        _ => {
+            Err(NativeError::from_message(messages::ARGUMENTS_NOT_POSITION, &[&"cursorTo"]).into())
+        }
+    }
+}
+
+fn open_fn(interpreter: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, LoxError> {
+    if interpreter.is_sandboxed() {
+        return Err(NativeError::from_message(messages::FILES_SANDBOXED, &[]).into());
+    }
+
+    match (&arguments[0], &arguments[1]) {
+        (Literal::String(path), Literal::String(mode)) => {
+            // Whether the open succeeded is recorded; replays read from
+            // the trace instead of the file.
+            let mut handle = None;
+            interpreter.tape.observe("open", || {
+                handle = Some(FileHandle::open(path, mode)?);
+                Ok(Literal::None)
+            })?;
+            let handle = handle.unwrap_or_else(FileHandle::replayed);
+            // Flush a file nothing refers to anymore right away, reporting
+            // what dropping the buffer would silently ignore
+            let handle = Finalized::new(handle, |file| {
+                if let Err(e) = file.close() {
+                    eprintln!("{}", e);
+                }
+            });
+            Ok(Literal::Foreign(Rc::new(RefCell::new(handle))))
+        }
+        _ => Err(NativeError::from_message(messages::ARGUMENTS_NOT_STRINGS, &[&"open"]).into()),
+    }
+}
+
+fn weak_fn(_interpreter: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, LoxError> {
+    let Literal::Foreign(target) = &arguments[0] else {
+        return Err(NativeError::from_message(messages::ARGUMENT_NOT_FOREIGN, &[&"weak"]).into());
+    };
+    Ok(Literal::Foreign(Rc::new(RefCell::new(WeakRef::new(
+        target,
+    )))))
+}
+
+fn lox_version_fn(
+    _interpreter: &mut Interpreter,
+    _arguments: &[Literal],
+) -> Result<Literal, LoxError> {
+    Ok(Literal::String(env!("CARGO_PKG_VERSION").to_string()))
+}
+
+/// Whether the running interpreter supports `name`. Unknown names are
+/// unsupported rather than an error, so scripts can probe for features
+/// newer than the interpreter.
+fn has_feature_fn(
+    interpreter: &mut Interpreter,
+    arguments: &[Literal],
+) -> Result<Literal, LoxError> {
+    let Literal::String(name) = &arguments[0] else {
+        return Err(
+            NativeError::from_message(messages::ARGUMENT_NOT_STRING, &[&"hasFeature"]).into(),
+        );
+    };
+
+    let sandboxed = interpreter.is_sandboxed();
+    let supported = match name.as_str() {
+        "async" => cfg!(feature = "async"),
+        "config-formats" => cfg!(feature = "config-formats"),
+        "crypto" => cfg!(feature = "crypto"),
+        "files" | "timers" | "workers" => !sandboxed,
+        "net" => cfg!(feature = "async") && !sandboxed,
+        "sandbox" => sandboxed,
+        "tree-walk" => true,
+        _ => false,
+    };
+    Ok(Literal::Bool(supported))
+}
+
+#[cfg(feature = "async")]
+type AsyncNativeFn = fn(&mut Interpreter, &[Literal]) -> Result<NativeFuture, LoxError>;
+
+#[cfg(feature = "async")]
+fn sleep_fn(
+    _interpreter: &mut Interpreter,
+    arguments: &[Literal],
+) -> Result<NativeFuture, LoxError> {
+    match &arguments[0] {
+        Literal::Number(seconds) if *seconds >= 0.0 => {
+            let duration = std::time::Duration::from_secs_f64(*seconds);
+            Ok(Box::pin(async move {
+                tokio::time::sleep(duration).await;
+                Ok(Literal::None)
+            }))
+        }
+        _ => Err(NativeError::from_message(messages::ARGUMENT_NOT_DURATION, &[&"sleep"]).into()),
+    }
+}
+
+#[cfg(feature = "async")]
+fn http_get_fn(
+    interpreter: &mut Interpreter,
+    arguments: &[Literal],
+) -> Result<NativeFuture, LoxError> {
+    if interpreter.is_sandboxed() {
+        return Err(NativeError::from_message(messages::NETWORK_SANDBOXED, &[]).into());
+    }
+    let Literal::String(url) = &arguments[0] else {
+        return Err(NativeError::from_message(messages::ARGUMENT_NOT_STRING, &[&"httpGet"]).into());
+    };
+
+    // ureq is blocking, so keep it off the runtime's worker threads
+    let url = url.clone();
+    let request = tokio::task::spawn_blocking(move || -> Result<String, String> {
+        let response = ureq::get(&url).call().map_err(|e| e.to_string())?;
+        response.into_string().map_err(|e| e.to_string())
+    });
+    Ok(Box::pin(async move {
+        match request.await {
+            Ok(Ok(body)) => Ok(Literal::String(body)),
+            Ok(Err(e)) => Err(NativeError::from_message(messages::HTTP_FAILED, &[&e]).into()),
+            Err(e) => Err(NativeError::from_message(messages::HTTP_FAILED, &[&e]).into()),
+        }
+    }))
+}
+
+fn deep_equal_fn(
+    _interpreter: &mut Interpreter,
+    arguments: &[Literal],
+) -> Result<Literal, LoxError> {
+    Ok(Literal::Bool(arguments[0].deep_equals(&arguments[1])))
+}
+
+#[cfg(debug_assertions)]
+fn heap_dump_fn(
+    interpreter: &mut Interpreter,
+    _arguments: &[Literal],
+) -> Result<Literal, LoxError> {
+    Ok(Literal::String(heap_snapshot::take(interpreter).to_json()))
+}
+
+fn print_fn(interpreter: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, LoxError> {
+    interpreter.print(arguments)?;
+    Ok(Literal::None)
+}
+
+fn help_fn(interpreter: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, LoxError> {
+    let Literal::Callable(callable) = &arguments[0] else {
+        return Err(NativeError::from_message(messages::ARGUMENT_NOT_FUNCTION, &[&"help"]).into());
+    };
+    interpreter.print(&[Literal::String(callable.help())])?;
+    Ok(Literal::None)
+}
+
+fn type_fn(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, LoxError> {
+    Ok(Literal::String(value::type_name(&arguments[0]).to_string()))
+}
+
+fn str_fn(interpreter: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, LoxError> {
+    match &arguments[0] {
+        Literal::String(text) => Ok(Literal::String(text.clone())),
+        value => Ok(Literal::String(interpreter.stringify(value))),
+    }
+}
+
+/// Numbers are returned as they are and strings parsed; anything else, or
+/// a string that isn't a number, gives nil.
+fn num_fn(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, LoxError> {
+    Ok(match &arguments[0] {
+        Literal::Number(n) => Literal::Number(*n),
+        Literal::String(text) => text
+            .trim()
+            .parse::<f64>()
+            .map_or(Literal::None, Literal::Number),
+        _ => Literal::None,
+    })
+}
+
+fn bool_fn(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, LoxError> {
+    Ok(Literal::Bool(value::is_truthy(&arguments[0])))
+}
+
+fn args_fn(interpreter: &mut Interpreter, _arguments: &[Literal]) -> Result<Literal, LoxError> {
+    let args = interpreter
+        .args()
+        .iter()
+        .map(|arg| Literal::String(arg.clone()))
+        .collect();
+    Ok(Literal::List(Rc::new(RefCell::new(args))))
+}
+
+/// The value of an environment variable, or nil if it isn't set.
+fn getenv_fn(interpreter: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, LoxError> {
+    if interpreter.is_sandboxed() {
+        return Err(NativeError::from_message(messages::ENV_SANDBOXED, &[]).into());
+    }
+    let Literal::String(name) = &arguments[0] else {
+        return Err(NativeError::from_message(messages::ARGUMENT_NOT_STRING, &[&"getenv"]).into());
+    };
+    interpreter.tape.observe("getenv", || {
+        Ok(std::env::var(name).map_or(Literal::None, Literal::String))
+    })
+}
+
+/// Ends the program with the given status, 0 unless one is passed.
+fn exit_fn(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, LoxError> {
+    let code = match arguments.first() {
+        None => 0,
+        Some(Literal::Number(n)) if n.fract() == 0.0 && (0.0..=255.0).contains(n) => *n as u8,
+        Some(_) => {
+            return Err(NativeError::from_message(messages::EXIT_STATUS_RANGE, &[&"exit"]).into())
+        }
+    };
+    Err(ExitError { code }.into())
+}
+
+/// The interpreter's random number generator, seeding it from the clock if
+/// the script hasn't.
+fn random(interpreter: &mut Interpreter) -> Result<&mut Random, LoxError> {
+    if interpreter.random.is_none() {
+        let seed = interpreter.tape.observe("random seed", || {
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos();
+            Ok(Literal::Number(nanos as f64))
+        })?;
+        let Literal::Number(seed) = seed else {
+            return Err(NativeError::from_message(messages::REPLAYED_SEED_NOT_NUMBER, &[]).into());
+        };
+        interpreter.random = Some(Random::new(seed.to_bits()));
+    }
+    Ok(interpreter.random.as_mut().expect("Seeded above"))
+}
+
+fn random_fn(interpreter: &mut Interpreter, _arguments: &[Literal]) -> Result<Literal, LoxError> {
+    Ok(Literal::Number(random(interpreter)?.next_f64()))
+}
+
+fn random_int_fn(
+    interpreter: &mut Interpreter,
+    arguments: &[Literal],
+) -> Result<Literal, LoxError> {
+    let bounds = number_arguments("randomInt", arguments)?;
+    let (min, max) = (bounds[0], bounds[1]);
+    if min.fract() != 0.0 || max.fract() != 0.0 || min > max {
+        return Err(NativeError::from_message(messages::RANDOM_INT_BOUNDS, &[&"randomInt"]).into());
+    }
+    let n = random(interpreter)?.range(min as i64, max as i64);
+    Ok(Literal::Number(n as f64))
+}
+
+fn random_seed_fn(
+    interpreter: &mut Interpreter,
+    arguments: &[Literal],
+) -> Result<Literal, LoxError> {
+    let seed = number_arguments("randomSeed", arguments)?[0];
+    interpreter.random = Some(Random::new(seed.to_bits()));
+    Ok(Literal::None)
+}
+
+/// The numbers in `arguments`, failing if any isn't one.
+fn number_arguments(name: &str, arguments: &[Literal]) -> Result<Vec<f64>, LoxError> {
+    arguments
+        .iter()
+        .map(|argument| match argument {
+            Literal::Number(n) => Ok(*n),
+            _ => Err(NativeError::from_message(messages::ARGUMENTS_NOT_NUMBERS, &[&name]).into()),
+        })
+        .collect()
+}
+
+/// Defines a native applying `$f` to its number arguments.
+macro_rules! math_fn {
+    ($name:ident, $lox_name:literal, |$($arg:ident),*| $f:expr) => {
+        fn $name(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, LoxError> {
+            let [$($arg),*] = number_arguments($lox_name, arguments)?[..] else {
+                unreachable!("Arity was checked");
+            };
+            Ok(Literal::Number($f))
+        }
+    };
+}
+
+math_fn!(sqrt_fn, "sqrt", |x| x.sqrt());
+math_fn!(abs_fn, "abs", |x| x.abs());
+math_fn!(floor_fn, "floor", |x| x.floor());
+math_fn!(ceil_fn, "ceil", |x| x.ceil());
+math_fn!(round_fn, "round", |x| x.round());
+math_fn!(pow_fn, "pow", |base, exponent| base.powf(exponent));
+math_fn!(sin_fn, "sin", |x| x.sin());
+math_fn!(cos_fn, "cos", |x| x.cos());
+math_fn!(tan_fn, "tan", |x| x.tan());
+math_fn!(log_fn, "log", |x| x.ln());
+
+fn min_fn(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, LoxError> {
+    let numbers = number_arguments("min", arguments)?;
+    Ok(Literal::Number(
+        numbers.into_iter().fold(f64::INFINITY, f64::min),
+    ))
+}
+
+fn max_fn(_: &mut Interpreter, arguments: &[Literal]) -> Result<Literal, LoxError> {
+    let numbers = number_arguments("max", arguments)?;
+    Ok(Literal::Number(
+        numbers.into_iter().fold(f64::NEG_INFINITY, f64::max),
+    ))
+}
+
+/// Every built-in native with its arity.
+const NATIVES: &[(&str, Arity, NativeFn)] = &[
+    ("clock", Exactly(0), clock_fn),
+    ("loxVersion", Exactly(0), lox_version_fn),
+    ("hasFeature", Exactly(1), has_feature_fn),
+    ("print", AtLeast(1), print_fn),
+    ("help", Exactly(1), help_fn),
+    ("deepEqual", Exactly(2), deep_equal_fn),
+    #[cfg(debug_assertions)]
+    ("heapDump", Exactly(0), heap_dump_fn),
+    ("stopwatchStart", Exactly(0), stopwatch_start_fn),
+    ("stopwatchElapsed", Exactly(1), stopwatch_elapsed_fn),
+    ("setTimeout", Exactly(2), set_timeout_fn),
+    ("setInterval", Exactly(2), set_interval_fn),
+    ("clearTimer", Exactly(1), clear_timer_fn),
+    ("runEventLoop", Exactly(0), run_event_loop_fn),
+    ("spawn", Exactly(1), spawn_fn),
+    ("send", Exactly(2), send_fn),
+    ("receive", Exactly(0), receive_fn),
+    ("parent", Exactly(0), parent_fn),
+    ("readLine", Exactly(0), read_line_fn),
+    ("readNumber", Exactly(0), read_number_fn),
+    ("termWidth", Exactly(0), term_width_fn),
+    ("setColor", Exactly(1), set_color_fn),
+    ("clearScreen", Exactly(0), clear_screen_fn),
+    ("cursorTo", Exactly(2), cursor_to_fn),
+    ("open", Exactly(2), open_fn),
+    ("weak", Exactly(1), weak_fn),
+    ("args", Exactly(0), args_fn),
+    ("getenv", Exactly(1), getenv_fn),
+    ("exit", Between(0, 1), exit_fn),
+    ("random", Exactly(0), random_fn),
+    ("randomInt", Exactly(2), random_int_fn),
+    ("randomSeed", Exactly(1), random_seed_fn),
+    ("type", Exactly(1), type_fn),
+    ("str", Exactly(1), str_fn),
+    ("num", Exactly(1), num_fn),
+    ("bool", Exactly(1), bool_fn),
+    ("sqrt", Exactly(1), sqrt_fn),
+    ("abs", Exactly(1), abs_fn),
+    ("floor", Exactly(1), floor_fn),
+    ("ceil", Exactly(1), ceil_fn),
+    ("round", Exactly(1), round_fn),
+    ("min", AtLeast(1), min_fn),
+    ("max", AtLeast(1), max_fn),
+    ("pow", Exactly(2), pow_fn),
+    ("sin", Exactly(1), sin_fn),
+    ("cos", Exactly(1), cos_fn),
+    ("tan", Exactly(1), tan_fn),
+    ("log", Exactly(1), log_fn),
+    ("csvParse", Exactly(1), csv_parse_fn),
+    ("csvStringify", Exactly(1), csv_stringify_fn),
+    #[cfg(feature = "crypto")]
+    ("sha256", Exactly(1), sha256_fn),
+    #[cfg(feature = "crypto")]
+    ("md5", Exactly(1), md5_fn),
+    #[cfg(feature = "crypto")]
+    ("crc32", Exactly(1), crc32_fn),
+    #[cfg(feature = "config-formats")]
+    ("tomlParse", Exactly(1), toml_parse_fn),
+    #[cfg(feature = "config-formats")]
+    ("yamlParse", Exactly(1), yaml_parse_fn),
+];
+
+/// Async natives, which all take a single argument.
+#[cfg(feature = "async")]
+const ASYNC_NATIVES: &[(&str, AsyncNativeFn)] = &[("sleep", sleep_fn), ("httpGet", http_get_fn)];
+
+/// Built-in constants.
+const CONSTANTS: &[(&str, f64)] = &[("PI", std::f64::consts::PI), ("E", std::f64::consts::E)];
+
+thread_local! {
+    /// Every built-in native and constant by name, built the first time
+    /// one is looked up and shared by the thread's interpreters.
+    static BUILT_INS: HashMap<&'static str, Literal> = built_ins();
+}
+
+fn built_ins() -> HashMap<&'static str, Literal> {
+    let mut built_ins = HashMap::new();
+    for &(name, arity, closure) in NATIVES {
+        let native = NativeFunction {
+            arity,
+            closure: Rc::new(closure),
+        };
+        built_ins.insert(name, Literal::Callable(Callable::NativeFunction(native)));
+    }
+
+    #[cfg(feature = "async")]
+    for &(name, closure) in ASYNC_NATIVES {
+        let native = AsyncNativeFunction {
+            arity: Exactly(1),
+            closure,
+        };
+        built_ins.insert(
+            name,
+            Literal::Callable(Callable::AsyncNativeFunction(native)),
+        );
+    }
+
+    for &(name, value) in CONSTANTS {
+        built_ins.insert(name, Literal::Number(value));
+    }
+    built_ins
+}
+
+/// Resolves a built-in native or constant by name. They are looked up on
+/// first use rather than copied into every interpreter's globals, so
+/// creating an interpreter costs nothing per native.
+pub fn lookup_native(name: &str) -> Option<Literal> {
+    BUILT_INS.with(|built_ins| built_ins.get(name).cloned())
+}
+
+/// The names of all built-in natives and constants.
+pub fn native_names() -> impl Iterator<Item = &'static str> {
+    let names = NATIVES.iter().map(|(name, ..)| *name);
+    let names = names.chain(CONSTANTS.iter().map(|(name, _)| *name));
+    #[cfg(feature = "async")]
+    let names = names.chain(ASYNC_NATIVES.iter().map(|(name, _)| *name));
+    names
 }
@@ -0,0 +1,150 @@
+use std::fmt;
+
+use crate::token::{Callable, Literal};
+
+/// How numbers are turned into text when values are printed or converted
+/// to strings. The default matches Rust's `f64` formatting.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumberFormat {
+    /// Most digits shown after the decimal point. Trailing zeros are dropped.
+    pub precision: Option<usize>,
+    /// Numbers of at least `10^n`, or nonzero and below `10^-n`, are shown in
+    /// scientific notation.
+    pub scientific_threshold: Option<i32>,
+    pub decimal_separator: char,
+}
+
+/// A value rendered with a `NumberFormat`, see `NumberFormat::display`.
+pub struct Formatted<'a> {
+    value: &'a Literal,
+    format: &'a NumberFormat,
+}
+
+impl NumberFormat {
+    pub const DEFAULT: NumberFormat = NumberFormat {
+        precision: None,
+        scientific_threshold: None,
+        decimal_separator: '.',
+    };
+
+    pub fn format(&self, n: f64) -> String {
+        if !n.is_finite() {
+            return n.to_string();
+        }
+
+        let scientific = self.scientific_threshold.is_some_and(|exponent| {
+            n != 0.0 && (n.abs() >= 10f64.powi(exponent) || n.abs() < 10f64.powi(-exponent))
+        });
+        let text = match (scientific, self.precision) {
+            (false, None) => n.to_string(),
+            (false, Some(precision)) => trim_fraction(format!("{:.*}", precision, n)),
+            (true, None) => format!("{:e}", n),
+            (true, Some(precision)) => {
+                let text = format!("{:.*e}", precision, n);
+                let (mantissa, exponent) = text.split_once('e').expect("Has an exponent");
+                format!("{}e{}", trim_fraction(mantissa.to_string()), exponent)
+            }
+        };
+
+        if self.decimal_separator == '.' {
+            text
+        } else {
+            text.replace('.', &self.decimal_separator.to_string())
+        }
+    }
+
+    /// Renders `value` as `print` would, formatting every number in it.
+    pub fn display<'a>(&'a self, value: &'a Literal) -> Formatted<'a> {
+        Formatted {
+            value,
+            format: self,
+        }
+    }
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Drops trailing zeros after the decimal point, and the point itself if
+/// nothing follows it.
+fn trim_fraction(text: String) -> String {
+    if !text.contains('.') {
+        return text;
+    }
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+impl fmt::Display for Formatted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.value {
+            Literal::None => write!(f, "nil"),
+            Literal::Bool(b) => write!(f, "{}", b),
+            Literal::Callable(Callable::Class(c)) => write!(f, "{}", c.name),
+            Literal::Callable(c) => write!(f, "callable({})", c.arity()),
+            Literal::Foreign(o) => write!(f, "<{}>", o.borrow().type_name()),
+            Literal::Instance(i) => write!(f, "{} instance", i.borrow().class.name),
+            Literal::List(l) => {
+                write!(f, "[")?;
+                for (i, element) in l.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", self.format.display(element))?;
+                }
+                write!(f, "]")
+            }
+            Literal::Map(m) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in m.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", key, self.format.display(value))?;
+                }
+                write!(f, "}}")
+            }
+            Literal::String(t) => write!(f, "{}", t),
+            Literal::Number(n) => write!(f, "{}", self.format.format(*n)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_matches_f64_display() {
+        for n in [
+            0.0,
+            -0.0,
+            1.0,
+            0.1,
+            1.0 / 3.0,
+            1e21,
+            1e-7,
+            f64::NAN,
+            f64::INFINITY,
+        ] {
+            assert_eq!(NumberFormat::DEFAULT.format(n), n.to_string());
+        }
+    }
+
+    #[test]
+    fn test_precision_threshold_and_separator() {
+        let format = NumberFormat {
+            precision: Some(3),
+            scientific_threshold: Some(6),
+            decimal_separator: ',',
+        };
+        assert_eq!(format.format(1.0 / 3.0), "0,333");
+        assert_eq!(format.format(2.5), "2,5");
+        assert_eq!(format.format(42.0), "42");
+        assert_eq!(format.format(1234567.0), "1,235e6");
+        assert_eq!(format.format(0.0000001), "1e-7");
+        assert_eq!(format.format(0.0), "0");
+    }
+}
@@ -1,34 +1,135 @@
-use crate::ast::{Expr, Stmt};
-use crate::lox_error::{LoxError, ParserError};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::rc::Rc;
+
+use crate::ast::{Expr, Signature, Stmt};
+use crate::crash;
+use crate::inline_cache::InlineCache;
+use crate::lox_error::{LoxError, ParserError, ParserErrors};
+use crate::messages::{self, Message};
 use crate::token::{Literal, Token};
 use crate::token_type::TokenType;
 
+const MAX_ERRORS: usize = 20;
+
 #[derive(Default, Clone)]
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    loops: Vec<Option<Token>>,
+    /// Whether each class body the current position is nested in has a
+    /// superclass, innermost last.
+    classes: Vec<bool>,
+    /// Whether the current function is a class's `init` method.
+    initializer: bool,
+    /// `///` comments, by the index of the token they precede.
+    docs: HashMap<usize, Rc<str>>,
+
+    errors: Vec<ParserError>,
+    max_errors: usize,
+    truncated: bool,
+    /// Line of the last error, until a declaration parses cleanly again.
+    error_line: Option<usize>,
 }
 
 impl Parser {
     pub fn new(tokens: &[Token]) -> Self {
-        Self {
-            tokens: tokens.to_vec(),
+        let mut parser = Self {
+            max_errors: MAX_ERRORS,
             ..Default::default()
+        };
+        parser.reset(tokens);
+        parser
+    }
+
+    /// Prepares the parser for new tokens, keeping its buffers.
+    pub fn reset(&mut self, tokens: &[Token]) {
+        self.tokens.clear();
+        self.docs.clear();
+        let mut doc: Vec<&str> = Vec::new();
+        for token in tokens {
+            match (&token.type_, &token.literal) {
+                (TokenType::DocComment, Some(Literal::String(line))) => doc.push(line),
+                _ => {
+                    if !doc.is_empty() {
+                        self.docs.insert(self.tokens.len(), doc.join("\n").into());
+                        doc.clear();
+                    }
+                    self.tokens.push(token.clone());
+                }
+            }
         }
+        self.current = 0;
+        self.loops.clear();
+        self.classes.clear();
+        self.initializer = false;
+        self.errors.clear();
+        self.truncated = false;
+        self.error_line = None;
+    }
+
+    /// Stops parsing after this many errors.
+    pub fn set_max_errors(&mut self, max_errors: usize) {
+        self.max_errors = max_errors;
     }
 
     pub fn parse(&mut self) -> Result<Vec<Stmt>, LoxError> {
         let mut statements = Vec::new();
 
         while !self.is_at_end() {
-            statements.push(self.declaration()?);
+            statements.extend(self.declaration());
+        }
+
+        if self.errors.is_empty() {
+            Ok(statements)
+        } else {
+            let errors = std::mem::take(&mut self.errors);
+            Err(LoxError::Parser(ParserErrors::new(errors, self.truncated)))
+        }
+    }
+
+    /// Parses a declaration, or records the error and skips to the next
+    /// statement so parsing can continue.
+    fn declaration(&mut self) -> Option<Stmt> {
+        match self.declaration_or_error() {
+            Ok(stmt) => {
+                self.error_line = None;
+                Some(stmt)
+            }
+            Err(LoxError::Parser(errors)) => {
+                errors.into_iter().for_each(|e| self.record(e));
+                self.synchronize();
+                None
+            }
+            Err(_) => unreachable!("The parser only raises parser errors"),
         }
-        Ok(statements)
     }
 
-    fn declaration(&mut self) -> Result<Stmt, LoxError> {
-        if self.match_(&[TokenType::Fun]) {
-            self.function("function")
+    fn record(&mut self, error: ParserError) {
+        // Further errors on the same line are most likely caused by the first
+        if self.truncated || self.error_line == Some(error.line()) {
+            return;
+        }
+
+        if self.errors.len() == self.max_errors {
+            // Skip the rest of the program
+            self.truncated = true;
+            self.current = self.tokens.len() - 1;
+            return;
+        }
+        self.error_line = Some(error.line());
+        self.errors.push(error);
+    }
+
+    fn declaration_or_error(&mut self) -> Result<Stmt, LoxError> {
+        if self.check(TokenType::Class) {
+            let doc = self.docs.get(&self.current).cloned();
+            self.advance();
+            self.class_declaration(doc)
+        } else if self.check(TokenType::Fun) && self.check_next(TokenType::Identifier) {
+            let doc = self.docs.get(&self.current).cloned();
+            self.advance();
+            self.function("function", doc)
         } else if self.match_(&[TokenType::Var]) {
             self.var_declaration()
         } else {
@@ -37,27 +138,115 @@ impl Parser {
     }
 
     fn statement(&mut self) -> Result<Stmt, LoxError> {
-        if self.match_(&[TokenType::For]) {
-            self.for_statement()
+        if self.check(TokenType::Identifier) && self.check_next(TokenType::Colon) {
+            self.labeled_statement()
+        } else if self.match_(&[TokenType::Break]) {
+            self.break_statement()
+        } else if self.match_(&[TokenType::Continue]) {
+            self.continue_statement()
+        } else if self.match_(&[TokenType::For]) {
+            self.for_statement(None)
         } else if self.match_(&[TokenType::If]) {
             self.if_statement()
+        } else if self.print_is_call() {
+            self.expression_statement()
         } else if self.match_(&[TokenType::Print]) {
             self.print_statement()
         } else if self.match_(&[TokenType::Return]) {
             self.return_statement()
+        } else if self.match_(&[TokenType::Throw]) {
+            self.throw_statement()
+        } else if self.match_(&[TokenType::Try]) {
+            self.try_statement()
         } else if self.match_(&[TokenType::While]) {
-            self.while_statement()
+            self.while_statement(None)
         } else if self.match_(&[TokenType::LeftBrace]) {
-            return Ok(Stmt::Block {
+            Ok(Stmt::Block {
                 statements: self.block()?,
-            });
+            })
         } else {
             self.expression_statement()
         }
     }
 
-    fn for_statement(&mut self) -> Result<Stmt, LoxError> {
-        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.")?;
+    fn labeled_statement(&mut self) -> Result<Stmt, LoxError> {
+        let label = self.advance();
+        self.advance(); // Eat the ':'
+
+        if self.match_(&[TokenType::For]) {
+            self.for_statement(Some(label))
+        } else if self.match_(&[TokenType::While]) {
+            self.while_statement(Some(label))
+        } else {
+            Err(ParserError::new(&label, messages::LABEL_ON_NON_LOOP, &[]).into())
+        }
+    }
+
+    fn break_statement(&mut self) -> Result<Stmt, LoxError> {
+        let keyword = self.previous();
+        let label = self.loop_target(&keyword)?;
+        self.consume(TokenType::Semicolon, messages::EXPECT_SEMICOLON_AFTER_BREAK)?;
+
+        Ok(Stmt::Break {
+            keyword: Box::new(keyword),
+            label,
+        })
+    }
+
+    fn continue_statement(&mut self) -> Result<Stmt, LoxError> {
+        let keyword = self.previous();
+        let label = self.loop_target(&keyword)?;
+        self.consume(
+            TokenType::Semicolon,
+            messages::EXPECT_SEMICOLON_AFTER_CONTINUE,
+        )?;
+
+        Ok(Stmt::Continue {
+            keyword: Box::new(keyword),
+            label,
+        })
+    }
+
+    /// Parses the optional label after `break`/`continue`, checking that it
+    /// refers to an enclosing loop.
+    fn loop_target(&mut self, keyword: &Token) -> Result<Option<Box<Token>>, LoxError> {
+        if self.loops.is_empty() {
+            return Err(
+                ParserError::new(keyword, messages::OUTSIDE_OF_LOOP, &[&keyword.lexeme]).into(),
+            );
+        }
+
+        if !self.match_(&[TokenType::Identifier]) {
+            return Ok(None);
+        }
+
+        let label = self.previous();
+        let found = self
+            .loops
+            .iter()
+            .flatten()
+            .any(|l| l.lexeme == label.lexeme);
+        if !found {
+            return Err(
+                ParserError::new(&label, messages::NO_LOOP_LABELED, &[&label.lexeme]).into(),
+            );
+        }
+        Ok(Some(Box::new(label)))
+    }
+
+    fn loop_body(&mut self, label: &Option<Token>) -> Result<Stmt, LoxError> {
+        self.loops.push(label.clone());
+        let body = self.statement();
+        self.loops.pop();
+        body
+    }
+
+    /// Desugars a `for` loop into a block and a `while`. The generated nodes
+    /// only hold tokens from the clauses the user wrote, and the `while`
+    /// keeps the `for` keyword, so errors point at the original source.
+    fn for_statement(&mut self, label: Option<Token>) -> Result<Stmt, LoxError> {
+        let keyword = Box::new(self.previous());
+        self.consume(TokenType::LeftParen, messages::EXPECT_PAREN_AFTER_FOR)?;
         let initializer = if self.match_(&[TokenType::Semicolon]) {
             None
         } else if self.match_(&[TokenType::Var]) {
@@ -73,30 +262,35 @@ impl Parser {
         } else {
             self.expression()?
         };
-        self.consume(TokenType::Semicolon, "Expect ';' after loop condition.")?;
+        self.consume(
+            TokenType::Semicolon,
+            messages::EXPECT_SEMICOLON_AFTER_LOOP_CONDITION,
+        )?;
 
         let increment = if self.check(TokenType::RightParen) {
             None
         } else {
             Some(self.expression()?)
         };
-        self.consume(TokenType::RightParen, "Expect ')' after for clauses.")?;
-        let mut body = self.statement()?;
+        self.consume(
+            TokenType::RightParen,
+            messages::EXPECT_PAREN_AFTER_FOR_CLAUSES,
+        )?;
+        let body = self.loop_body(&label)?;
 
-        if let Some(increment) = increment {
-            body = Stmt::Block {
-                statements: vec![
-                    body,
-                    Stmt::Expression {
-                        expression: Box::new(increment),
-                    },
-                ],
-            };
+        // Variables declared by the initializer can be rebound on every iteration
+        let bindings = match &initializer {
+            Some(Stmt::Var { name, .. }) => vec![*name.clone()],
+            _ => Vec::new(),
         };
 
-        body = Stmt::While {
+        let mut body = Stmt::While {
+            keyword,
             condition: Box::new(condition),
             body: Box::new(body),
+            increment: increment.map(Box::new),
+            bindings,
+            label: label.map(Box::new),
         };
 
         if let Some(initializer) = initializer {
@@ -109,9 +303,13 @@ impl Parser {
     }
 
     fn if_statement(&mut self) -> Result<Stmt, LoxError> {
-        self.consume(TokenType::LeftParen, "Expect '(' after 'if'.")?;
+        let keyword = Box::new(self.previous());
+        self.consume(TokenType::LeftParen, messages::EXPECT_PAREN_AFTER_IF)?;
         let condition = Box::new(self.expression()?);
-        self.consume(TokenType::RightParen, "Expect ')' after if condition")?;
+        self.consume(
+            TokenType::RightParen,
+            messages::EXPECT_PAREN_AFTER_IF_CONDITION,
+        )?;
 
         let then_branch = Box::new(self.statement()?);
         let else_branch = if self.match_(&[TokenType::Else]) {
@@ -121,6 +319,7 @@ impl Parser {
         };
 
         Ok(Stmt::If {
+            keyword,
             condition,
             then_branch,
             else_branch,
@@ -128,49 +327,134 @@ impl Parser {
     }
 
     fn print_statement(&mut self) -> Result<Stmt, LoxError> {
+        let keyword = Box::new(self.previous());
         let value = self.expression()?;
-        self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
+        self.consume(TokenType::Semicolon, messages::EXPECT_SEMICOLON_AFTER_VALUE)?;
 
         Ok(Stmt::Print {
+            keyword,
             expression: Box::new(value),
         })
     }
 
+    /// Whether the `print` at the current token calls the native function,
+    /// as in `print(a, b)` or `print()`, rather than starting a statement.
+    /// A parenthesized expression never holds a comma or nothing at all.
+    fn print_is_call(&self) -> bool {
+        if !self.check(TokenType::Print) || !self.check_next(TokenType::LeftParen) {
+            return false;
+        }
+
+        let mut depth = 0;
+        for (i, token) in self.tokens[self.current + 1..].iter().enumerate() {
+            match token.type_ {
+                TokenType::LeftParen | TokenType::LeftBrace | TokenType::LeftBracket => depth += 1,
+                TokenType::RightParen | TokenType::RightBrace | TokenType::RightBracket => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return i == 1;
+                    }
+                }
+                TokenType::Comma if depth == 1 => return true,
+                _ => {}
+            }
+        }
+        false
+    }
+
     fn return_statement(&mut self) -> Result<Stmt, LoxError> {
         let keyword = Box::new(self.previous());
         let value = if self.check(TokenType::Semicolon) {
             None
         } else {
+            if self.initializer {
+                return Err(ParserError::new(
+                    &keyword,
+                    messages::RETURN_VALUE_FROM_INITIALIZER,
+                    &[],
+                )
+                .into());
+            }
             Some(Box::new(self.expression()?))
         };
 
-        self.consume(TokenType::Semicolon, "Expect ';' after return value.")?;
+        self.consume(
+            TokenType::Semicolon,
+            messages::EXPECT_SEMICOLON_AFTER_RETURN,
+        )?;
 
         Ok(Stmt::Return { keyword, value })
     }
 
-    fn while_statement(&mut self) -> Result<Stmt, LoxError> {
-        self.consume(TokenType::LeftParen, "Expect '(' after while.")?;
+    fn throw_statement(&mut self) -> Result<Stmt, LoxError> {
+        let keyword = Box::new(self.previous());
+        let value = Box::new(self.expression()?);
+        self.consume(TokenType::Semicolon, messages::EXPECT_SEMICOLON_AFTER_THROW)?;
+        Ok(Stmt::Throw { keyword, value })
+    }
+
+    fn try_statement(&mut self) -> Result<Stmt, LoxError> {
+        self.consume(TokenType::LeftBrace, messages::EXPECT_BRACE_AFTER_TRY)?;
+        let body = self.block()?;
+
+        self.consume(TokenType::Catch, messages::EXPECT_CATCH)?;
+        self.consume(TokenType::LeftParen, messages::EXPECT_PAREN_AFTER_CATCH)?;
+        let name = Box::new(self.consume(TokenType::Identifier, messages::EXPECT_ERROR_NAME)?);
+        self.consume(
+            TokenType::RightParen,
+            messages::EXPECT_PAREN_AFTER_ERROR_NAME,
+        )?;
+        self.consume(
+            TokenType::LeftBrace,
+            messages::EXPECT_BRACE_BEFORE_CATCH_BODY,
+        )?;
+        let handler = self.block()?;
+
+        Ok(Stmt::Try {
+            body,
+            name,
+            handler,
+        })
+    }
+
+    fn while_statement(&mut self, label: Option<Token>) -> Result<Stmt, LoxError> {
+        let keyword = Box::new(self.previous());
+        self.consume(TokenType::LeftParen, messages::EXPECT_PAREN_AFTER_WHILE)?;
         let condition = Box::new(self.expression()?);
-        self.consume(TokenType::RightParen, "Expect ')' after condition.")?;
-        let body = Box::new(self.statement()?);
+        self.consume(
+            TokenType::RightParen,
+            messages::EXPECT_PAREN_AFTER_CONDITION,
+        )?;
+        let body = Box::new(self.loop_body(&label)?);
 
-        Ok(Stmt::While { condition, body })
+        Ok(Stmt::While {
+            keyword,
+            condition,
+            body,
+            increment: None,
+            bindings: Vec::new(),
+            label: label.map(Box::new),
+        })
     }
 
     fn block(&mut self) -> Result<Vec<Stmt>, LoxError> {
         let mut statements = Vec::new();
 
         while !self.check(TokenType::RightBrace) && !self.is_at_end() {
-            statements.push(self.declaration()?);
+            statements.extend(self.declaration());
         }
 
-        self.consume(TokenType::RightBrace, "Expect '}' after block.")?;
+        self.consume(TokenType::RightBrace, messages::EXPECT_BRACE_AFTER_BLOCK)?;
         Ok(statements)
     }
 
     fn var_declaration(&mut self) -> Result<Stmt, LoxError> {
-        let name = self.consume(TokenType::Identifier, "Expect variable name.")?;
+        let name = self.consume(TokenType::Identifier, messages::EXPECT_VARIABLE_NAME)?;
+        let annotation = if self.match_(&[TokenType::Colon]) {
+            Some(Box::new(self.type_name()?))
+        } else {
+            None
+        };
 
         let initializer = if self.match_(&[TokenType::Equal]) {
             Some(Box::new(self.expression()?))
@@ -178,76 +462,287 @@ impl Parser {
             None
         };
 
-        self.consume(
-            TokenType::Semicolon,
-            "Expect ';' after variable declaration.",
-        )?;
+        self.consume(TokenType::Semicolon, messages::EXPECT_SEMICOLON_AFTER_VAR)?;
 
         Ok(Stmt::Var {
             name: Box::new(name),
+            annotation,
             initializer,
         })
     }
 
     fn expression_statement(&mut self) -> Result<Stmt, LoxError> {
         let expr = Box::new(self.expression()?);
-        self.consume(TokenType::Semicolon, "Expect ';' after expression.")?;
+        self.consume(
+            TokenType::Semicolon,
+            messages::EXPECT_SEMICOLON_AFTER_EXPRESSION,
+        )?;
 
         Ok(Stmt::Expression { expression: expr })
     }
 
-    fn function(&mut self, kind: &str) -> Result<Stmt, LoxError> {
-        let error_msg = format!("Expect {} name.", kind);
-        let name = self.consume(TokenType::Identifier, &error_msg)?;
-        let error_msg = format!("Expect '(' after {} name.", kind);
-        self.consume(TokenType::LeftParen, &error_msg)?;
+    fn class_declaration(&mut self, doc: Option<Rc<str>>) -> Result<Stmt, LoxError> {
+        let name = self.consume_with(
+            TokenType::Identifier,
+            messages::EXPECT_KIND_NAME,
+            &[&"class"],
+        )?;
+        let superclass = if self.match_(&[TokenType::Less]) {
+            let superclass =
+                self.consume(TokenType::Identifier, messages::EXPECT_SUPERCLASS_NAME)?;
+            if superclass.lexeme == name.lexeme {
+                return Err(ParserError::new(&superclass, messages::INHERIT_FROM_SELF, &[]).into());
+            }
+            Some(Box::new(Expr::Variable {
+                name: superclass,
+                depth: None,
+            }))
+        } else {
+            None
+        };
+        self.consume_with(
+            TokenType::LeftBrace,
+            messages::EXPECT_BRACE_BEFORE_KIND_BODY,
+            &[&"class"],
+        )?;
+
+        self.classes.push(superclass.is_some());
+        let methods = self.methods();
+        self.classes.pop();
+        let methods = methods?;
+        self.consume(
+            TokenType::RightBrace,
+            messages::EXPECT_BRACE_AFTER_CLASS_BODY,
+        )?;
+
+        Ok(Stmt::Class {
+            name: Box::new(name),
+            superclass,
+            methods,
+            doc,
+        })
+    }
+
+    fn methods(&mut self) -> Result<Vec<Stmt>, LoxError> {
+        let mut methods = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            let doc = self.docs.get(&self.current).cloned();
+            methods.push(self.function("method", doc)?);
+        }
+        Ok(methods)
+    }
+
+    fn function(&mut self, kind: &str, doc: Option<Rc<str>>) -> Result<Stmt, LoxError> {
+        let name =
+            self.consume_with(TokenType::Identifier, messages::EXPECT_KIND_NAME, &[&kind])?;
+        self.consume_with(
+            TokenType::LeftParen,
+            messages::EXPECT_PAREN_AFTER_KIND_NAME,
+            &[&kind],
+        )?;
+        let (params, signature) = self.parameters()?;
+        self.consume_with(
+            TokenType::LeftBrace,
+            messages::EXPECT_BRACE_BEFORE_KIND_BODY,
+            &[&kind],
+        )?;
+        let initializer = kind == "method" && name.lexeme == "init";
+        let body = self.function_body(|parser| {
+            parser.initializer = initializer;
+            parser.block()
+        })?;
+
+        Ok(Stmt::Function {
+            name: Box::new(name),
+            params,
+            signature,
+            body,
+            doc,
+        })
+    }
+
+    /// Parses a function body, where loops of the enclosing code can't be
+    /// targeted by `break` or `continue`.
+    fn function_body<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, LoxError>,
+    ) -> Result<T, LoxError> {
+        let enclosing = std::mem::take(&mut self.loops);
+        let initializer = std::mem::take(&mut self.initializer);
+        let body = parse(self);
+        self.loops = enclosing;
+        self.initializer = initializer;
+        body
+    }
 
+    /// Parses a parameter list and the return type after it, each with
+    /// optional type annotations.
+    fn parameters(&mut self) -> Result<(Vec<Token>, Signature), LoxError> {
         let mut params = Vec::new();
+        let mut signature = Signature::default();
         if !self.check(TokenType::RightParen) {
             loop {
                 if params.len() >= 255 {
-                    return Err(ParserError::new(
-                        &self.peek(),
-                        "Can't have more than 255 parameters.",
-                    )
-                    .into());
+                    return Err(
+                        ParserError::new(&self.peek(), messages::TOO_MANY_PARAMETERS, &[]).into(),
+                    );
                 }
 
-                params.push(self.consume(TokenType::Identifier, "Expect parameter name.")?);
+                params.push(self.consume(TokenType::Identifier, messages::EXPECT_PARAMETER_NAME)?);
+                signature.params.push(if self.match_(&[TokenType::Colon]) {
+                    Some(self.type_name()?)
+                } else {
+                    None
+                });
 
                 if !self.match_(&[TokenType::Comma]) {
                     break;
                 }
             }
         }
-        self.consume(TokenType::RightParen, "Expect ')' after parameters.")?;
-        let error_msg = format!("Expect '{{' before {} body.", kind);
-        self.consume(TokenType::LeftBrace, &error_msg)?;
-        let body = self.block()?;
+        self.consume(
+            TokenType::RightParen,
+            messages::EXPECT_PAREN_AFTER_PARAMETERS,
+        )?;
+        if self.match_(&[TokenType::ThinArrow]) {
+            signature.returns = Some(self.type_name()?);
+        }
+        Ok((params, signature))
+    }
 
-        Ok(Stmt::Function {
-            name: Box::new(name),
+    /// Parses the name of a type in an annotation. `nil` is a keyword, so
+    /// it is accepted separately.
+    fn type_name(&mut self) -> Result<Token, LoxError> {
+        if self.match_(&[TokenType::Nil]) {
+            return Ok(self.previous());
+        }
+        self.consume(TokenType::Identifier, messages::EXPECT_TYPE_NAME)
+    }
+
+    fn super_expression(&mut self) -> Result<Expr, LoxError> {
+        let keyword = self.previous();
+        let error = match self.classes.last() {
+            None => Some(messages::SUPER_OUTSIDE_CLASS),
+            Some(false) => Some(messages::SUPER_WITHOUT_SUPERCLASS),
+            Some(true) => None,
+        };
+        if let Some(error) = error {
+            return Err(ParserError::new(&keyword, error, &[]).into());
+        }
+
+        self.consume(TokenType::Dot, messages::EXPECT_DOT_AFTER_SUPER)?;
+        let method = self.consume(TokenType::Identifier, messages::EXPECT_SUPERCLASS_METHOD)?;
+        Ok(Expr::Super { keyword, method })
+    }
+
+    fn lambda(&mut self) -> Result<Expr, LoxError> {
+        let keyword = self.previous();
+        self.consume(TokenType::LeftParen, messages::EXPECT_PAREN_AFTER_FUN)?;
+        let (params, signature) = self.parameters()?;
+
+        // `fun (x) => x * 2` is shorthand for `fun (x) { return x * 2; }`
+        let body = if self.match_(&[TokenType::Arrow]) {
+            let arrow = self.previous();
+            let value = self.function_body(|parser| parser.expression())?;
+            vec![Stmt::Return {
+                keyword: Box::new(arrow),
+                value: Some(Box::new(value)),
+            }]
+        } else {
+            self.consume(
+                TokenType::LeftBrace,
+                messages::EXPECT_BRACE_BEFORE_FUNCTION_BODY,
+            )?;
+            self.function_body(|parser| parser.block())?
+        };
+
+        Ok(Expr::Lambda {
+            keyword,
             params,
+            signature,
             body,
         })
     }
 
+    /// Parses the tokens as a single expression with nothing after it,
+    /// for the REPL to echo the value of.
+    pub fn parse_expression(&mut self) -> Result<Expr, LoxError> {
+        let expr = self.expression()?;
+        if !self.is_at_end() {
+            return Err(
+                ParserError::new(&self.peek(), messages::EXPECT_END_OF_EXPRESSION, &[]).into(),
+            );
+        }
+        Ok(expr)
+    }
+
     fn expression(&mut self) -> Result<Expr, LoxError> {
         self.assignment()
     }
 
     fn assignment(&mut self) -> Result<Expr, LoxError> {
-        let expr = self.or()?;
-
-        if self.match_(&[TokenType::Equal]) {
+        let expr = self.ternary()?;
+
+        if self.match_(&[
+            TokenType::Equal,
+            TokenType::PlusEqual,
+            TokenType::MinusEqual,
+            TokenType::StarEqual,
+            TokenType::SlashEqual,
+        ]) {
             let equals = self.previous();
+            let operator = compound_operator(&equals);
             let value = Box::new(self.assignment()?);
 
-            if let Expr::Variable { name } = expr {
-                return Ok(Expr::Assign { name, value });
-            }
+            return assign(expr, &equals, operator, value);
+        }
+
+        Ok(expr)
+    }
+
+    /// `a ? b : c ? d : e` groups to the right, as `a ? b : (c ? d : e)`.
+    fn ternary(&mut self) -> Result<Expr, LoxError> {
+        let condition = self.pipe()?;
+
+        if self.match_(&[TokenType::Question]) {
+            let then_branch = Box::new(self.expression()?);
+            self.consume(TokenType::Colon, messages::EXPECT_COLON_IN_CONDITIONAL)?;
+            let else_branch = Box::new(self.ternary()?);
+            return Ok(Expr::Ternary {
+                condition: Box::new(condition),
+                then_branch,
+                else_branch,
+            });
+        }
+
+        Ok(condition)
+    }
 
-            return Err(ParserError::new(&equals, "Invalid assignment target.").into());
+    /// `x |> f` is parsed as `f(x)`, and `x |> f(y)` as `f(x, y)`.
+    fn pipe(&mut self) -> Result<Expr, LoxError> {
+        let mut expr = self.or()?;
+
+        while self.match_(&[TokenType::Pipe]) {
+            let pipe = self.previous();
+            expr = match self.or()? {
+                Expr::Call {
+                    callee,
+                    paren,
+                    mut arguments,
+                } => {
+                    arguments.insert(0, expr);
+                    Expr::Call {
+                        callee,
+                        paren,
+                        arguments,
+                    }
+                }
+                callee => Expr::Call {
+                    callee: Box::new(callee),
+                    paren: pipe,
+                    arguments: vec![expr],
+                },
+            };
         }
 
         Ok(expr)
@@ -308,6 +803,7 @@ impl Parser {
             TokenType::GreaterEqual,
             TokenType::Less,
             TokenType::LessEqual,
+            TokenType::In,
         ]) {
             let operator = self.previous();
             let right = Box::new(self.term()?);
@@ -342,9 +838,47 @@ impl Parser {
             let operator = self.previous();
             let right = Box::new(self.unary()?);
             Ok(Expr::Unary { operator, right })
+        } else if self.match_(&[TokenType::PlusPlus, TokenType::MinusMinus]) {
+            // `++x` is `x += 1`, which already evaluates to the new value
+            let operator = self.previous();
+            let target = increment_target(&operator, self.unary()?)?;
+            let step = Box::new(Expr::Literal {
+                value: Literal::Number(1.0),
+            });
+            assign(target, &operator, Some(increment_operator(&operator)), step)
         } else {
-            self.call()
+            self.power()
+        }
+    }
+
+    /// `**` binds tighter than a unary operator on its left, so `-2 ** 2` is
+    /// -4, and groups to the right, so `2 ** 3 ** 2` is `2 ** (3 ** 2)`.
+    fn power(&mut self) -> Result<Expr, LoxError> {
+        let expr = self.postfix()?;
+
+        if self.match_(&[TokenType::StarStar]) {
+            let operator = self.previous();
+            let right = Box::new(self.unary()?);
+            return Ok(Expr::Binary {
+                left: Box::new(expr),
+                operator,
+                right,
+            });
         }
+
+        Ok(expr)
+    }
+
+    fn postfix(&mut self) -> Result<Expr, LoxError> {
+        let expr = self.call()?;
+
+        if self.match_(&[TokenType::PlusPlus, TokenType::MinusMinus]) {
+            let operator = self.previous();
+            let target = Box::new(increment_target(&operator, expr)?);
+            return Ok(Expr::Postfix { operator, target });
+        }
+
+        Ok(expr)
     }
 
     fn finish_call(&mut self, callee: Expr) -> Result<Expr, LoxError> {
@@ -353,20 +887,21 @@ impl Parser {
         if !self.check(TokenType::RightParen) {
             loop {
                 if arguments.len() >= 255 {
-                    return Err(ParserError::new(
-                        &self.peek(),
-                        "Can't have more than 255 arguments.",
-                    )
-                    .into());
+                    return Err(
+                        ParserError::new(&self.peek(), messages::TOO_MANY_ARGUMENTS, &[]).into(),
+                    );
                 }
-                arguments.push(self.expression()?);
+                arguments.push(self.spreadable()?);
 
                 if !self.match_(&[TokenType::Comma]) {
                     break;
                 }
             }
         }
-        let paren = self.consume(TokenType::RightParen, "Expect ')' after arguments.")?;
+        let paren = self.consume(
+            TokenType::RightParen,
+            messages::EXPECT_PAREN_AFTER_ARGUMENTS,
+        )?;
 
         Ok(Expr::Call {
             callee: Box::new(callee),
@@ -375,12 +910,80 @@ impl Parser {
         })
     }
 
+    fn spreadable(&mut self) -> Result<Expr, LoxError> {
+        if self.match_(&[TokenType::Ellipsis]) {
+            let operator = self.previous();
+            let expression = Box::new(self.expression()?);
+            Ok(Expr::Spread {
+                operator,
+                expression,
+            })
+        } else {
+            self.expression()
+        }
+    }
+
+    fn list(&mut self) -> Result<Expr, LoxError> {
+        let mut elements = Vec::new();
+
+        if !self.check(TokenType::RightBracket) {
+            loop {
+                elements.push(self.spreadable()?);
+
+                if !self.match_(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightBracket, messages::EXPECT_BRACKET_AFTER_LIST)?;
+
+        Ok(Expr::List { elements })
+    }
+
+    fn map(&mut self) -> Result<Expr, LoxError> {
+        let brace = self.previous();
+        let mut entries = Vec::new();
+
+        if !self.check(TokenType::RightBrace) {
+            loop {
+                let key = self.expression()?;
+                self.consume(TokenType::Colon, messages::EXPECT_COLON_AFTER_MAP_KEY)?;
+                entries.push((key, self.expression()?));
+
+                if !self.match_(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightBrace, messages::EXPECT_BRACE_AFTER_MAP)?;
+
+        Ok(Expr::Map { brace, entries })
+    }
+
     fn call(&mut self) -> Result<Expr, LoxError> {
         let mut expr = self.primary()?;
 
         loop {
             if self.match_(&[TokenType::LeftParen]) {
                 expr = self.finish_call(expr)?;
+            } else if self.match_(&[TokenType::Dot]) {
+                let name = self.consume(TokenType::Identifier, messages::EXPECT_PROPERTY_NAME)?;
+                expr = Expr::Get {
+                    object: Box::new(expr),
+                    name,
+                    cache: InlineCache::default(),
+                };
+            } else if self.match_(&[TokenType::LeftBracket]) {
+                let index = self.expression()?;
+                let bracket = self.consume(
+                    TokenType::RightBracket,
+                    messages::EXPECT_BRACKET_AFTER_INDEX,
+                )?;
+                expr = Expr::Index {
+                    object: Box::new(expr),
+                    bracket,
+                    index: Box::new(index),
+                };
             } else {
                 break;
             }
@@ -409,20 +1012,44 @@ impl Parser {
         } else if self.match_(&[TokenType::Identifier]) {
             Ok(Expr::Variable {
                 name: self.previous(),
+                depth: None,
+            })
+        } else if self.match_(&[TokenType::Print]) {
+            // Outside statement position `print` refers to the native function.
+            Ok(Expr::Variable {
+                name: self.previous(),
+                depth: None,
             })
+        } else if self.match_(&[TokenType::This]) {
+            let keyword = self.previous();
+            if self.classes.is_empty() {
+                return Err(ParserError::new(&keyword, messages::THIS_OUTSIDE_CLASS, &[]).into());
+            }
+            Ok(Expr::This { keyword })
+        } else if self.match_(&[TokenType::Super]) {
+            self.super_expression()
+        } else if self.match_(&[TokenType::Fun]) {
+            self.lambda()
+        } else if self.match_(&[TokenType::LeftBracket]) {
+            self.list()
+        } else if self.match_(&[TokenType::LeftBrace]) {
+            self.map()
         } else if self.match_(&[TokenType::LeftParen]) {
             let expression = Box::new(self.expression()?);
-            self.consume(TokenType::RightParen, "Expect ')' after expression.")?;
+            self.consume(
+                TokenType::RightParen,
+                messages::EXPECT_PAREN_AFTER_EXPRESSION,
+            )?;
             Ok(Expr::Grouping { expression })
         } else {
-            Err(ParserError::new(&self.peek(), "Expect expression.").into())
+            Err(ParserError::new(&self.peek(), messages::EXPECT_EXPRESSION, &[]).into())
         }
     }
 
     fn factor(&mut self) -> Result<Expr, LoxError> {
         let mut expr = self.unary()?;
 
-        while self.match_(&[TokenType::Slash, TokenType::Star]) {
+        while self.match_(&[TokenType::Slash, TokenType::Star, TokenType::Percent]) {
             let operator = self.previous();
             let right = Box::new(self.unary()?);
             expr = Expr::Binary {
@@ -453,7 +1080,15 @@ impl Parser {
         }
     }
 
+    fn check_next(&self, type_: TokenType) -> bool {
+        match self.tokens.get(self.current + 1) {
+            Some(token) => token.type_ == type_,
+            None => false,
+        }
+    }
+
     fn advance(&mut self) -> Token {
+        crash::set_line(Some(self.tokens[self.current].line));
         if !self.is_at_end() {
             self.current += 1;
         }
@@ -461,19 +1096,39 @@ impl Parser {
         self.previous()
     }
 
-    fn consume(&mut self, type_: TokenType, message: &str) -> Result<Token, LoxError> {
+    fn consume(&mut self, type_: TokenType, message: Message) -> Result<Token, LoxError> {
+        self.consume_with(type_, message, &[])
+    }
+
+    /// Like `consume`, with arguments for the placeholders in `message`.
+    fn consume_with(
+        &mut self,
+        type_: TokenType,
+        message: Message,
+        args: &[&dyn Display],
+    ) -> Result<Token, LoxError> {
         if self.check(type_) {
             Ok(self.advance())
         } else {
-            Err(ParserError::new(&self.peek(), message).into())
+            Err(ParserError::new(&self.peek(), message, args).into())
         }
     }
 
-    #[allow(dead_code)]
+    /// Skips to the start of the next statement. Blocks opened along the way
+    /// are skipped whole, so their contents don't cause further errors.
     fn synchronize(&mut self) {
-        self.advance();
+        let mut depth = 0usize;
 
         while !self.is_at_end() {
+            match self.advance().type_ {
+                TokenType::LeftBrace => depth += 1,
+                TokenType::RightBrace => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            if depth > 0 {
+                continue;
+            }
+
             if self.previous().type_ == TokenType::Semicolon {
                 return;
             }
@@ -486,13 +1141,14 @@ impl Parser {
                 | TokenType::If
                 | TokenType::While
                 | TokenType::Print
-                | TokenType::Return => {
+                | TokenType::Return
+                | TokenType::Throw
+                | TokenType::Try
+                | TokenType::RightBrace => {
                     return;
                 }
                 _ => {}
             }
-
-            self.advance();
         }
     }
 
@@ -508,3 +1164,96 @@ impl Parser {
         self.tokens[self.current - 1].clone()
     }
 }
+
+/// Turns `target = value`, or with `operator` a compound assignment like
+/// `target += value`, into the assignment for that kind of target.
+fn assign(
+    target: Expr,
+    equals: &Token,
+    operator: Option<Token>,
+    value: Box<Expr>,
+) -> Result<Expr, LoxError> {
+    match target {
+        // Reading a variable has no side effects, so `x += 1` can
+        // simply become `x = x + 1`
+        Expr::Variable { name, .. } => {
+            let value = match operator {
+                Some(operator) => Box::new(Expr::Binary {
+                    left: Box::new(Expr::Variable {
+                        name: name.clone(),
+                        depth: None,
+                    }),
+                    operator,
+                    right: value,
+                }),
+                None => value,
+            };
+            Ok(Expr::Assign {
+                name,
+                value,
+                depth: None,
+            })
+        }
+        Expr::Get { object, name, .. } => Ok(Expr::Set {
+            object,
+            name,
+            operator,
+            value,
+        }),
+        Expr::Index {
+            object,
+            bracket,
+            index,
+        } => Ok(Expr::SetIndex {
+            object,
+            bracket,
+            index,
+            operator,
+            value,
+        }),
+        _ => Err(ParserError::new(equals, messages::INVALID_ASSIGNMENT_TARGET, &[]).into()),
+    }
+}
+
+/// Checks that `++` or `--` is applied to something that can be assigned.
+fn increment_target(operator: &Token, target: Expr) -> Result<Expr, LoxError> {
+    match target {
+        Expr::Variable { .. } | Expr::Get { .. } | Expr::Index { .. } => Ok(target),
+        _ => Err(ParserError::new(
+            operator,
+            messages::INVALID_INCREMENT_TARGET,
+            &[&operator.lexeme],
+        )
+        .into()),
+    }
+}
+
+/// The binary operator `++` or `--` applies to its operand.
+pub fn increment_operator(operator: &Token) -> Token {
+    let (type_, lexeme) = match operator.type_ {
+        TokenType::PlusPlus => (TokenType::Plus, "+"),
+        _ => (TokenType::Minus, "-"),
+    };
+    Token {
+        column: operator.column,
+        offset: operator.offset,
+        ..Token::new(type_, lexeme, None, operator.line)
+    }
+}
+
+/// The binary operator a compound assignment like `+=` applies, or `None`
+/// for a plain `=`.
+fn compound_operator(equals: &Token) -> Option<Token> {
+    let (type_, lexeme) = match equals.type_ {
+        TokenType::PlusEqual => (TokenType::Plus, "+"),
+        TokenType::MinusEqual => (TokenType::Minus, "-"),
+        TokenType::StarEqual => (TokenType::Star, "*"),
+        TokenType::SlashEqual => (TokenType::Slash, "/"),
+        _ => return None,
+    };
+    Some(Token {
+        column: equals.column,
+        offset: equals.offset,
+        ..Token::new(type_, lexeme, None, equals.line)
+    })
+}
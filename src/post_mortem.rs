@@ -0,0 +1,144 @@
+//! Post-mortem inspection of a runtime error: the variables of the scope it
+//! was raised in, and the calls it unwound through.
+
+use std::io::{self, BufRead, Write};
+
+use crate::environment::Environment;
+use crate::lox_error::{LoxError, RuntimeError};
+use crate::token::{Literal, Token};
+use crate::token_type::TokenType;
+use crate::value;
+
+const HELP: &str = "\
+print NAME    show the value of a variable
+locals        show the variables of the scope the error was raised in
+env           show every scope from the globals in, with their variables
+stack         show the calls the error unwound through
+quit          leave the debugger";
+
+/// A runtime error together with the scope it was raised in.
+pub struct Failure {
+    pub error: RuntimeError,
+    pub scope: Environment,
+}
+
+pub fn print_variable(environment: &Environment, name: &str) {
+    let token = Token::new(TokenType::Identifier, name, None, 0);
+    match environment.get(&token) {
+        Ok(value) => println!("{} = {}", name, value),
+        Err(LoxError::Runtime(e)) => println!("{}", e.message()),
+        Err(e) => println!("{}", e),
+    }
+}
+
+pub fn print_locals(environment: &Environment) {
+    let locals = environment.locals();
+    if locals.is_empty() {
+        println!("No variables in this scope.");
+    }
+    for (name, value) in locals {
+        println!("{} = {}", name, value);
+    }
+}
+
+/// How many characters of a value `print_scopes` shows.
+const PREVIEW_LENGTH: usize = 40;
+
+/// A one-line rendering of `value`, cut short if it is long.
+fn preview(value: &Literal) -> String {
+    let text = match value {
+        Literal::String(text) => format!("{:?}", text),
+        _ => value.to_string(),
+    };
+    let text = text.lines().next().unwrap_or_default();
+    if text.chars().count() > PREVIEW_LENGTH {
+        let cut: String = text.chars().take(PREVIEW_LENGTH - 3).collect();
+        format!("{}...", cut)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Prints the chain of scopes around `environment` as a tree, from the
+/// natives in to the scope itself, with each scope's bindings.
+pub fn print_scopes(environment: &Environment) {
+    let mut scopes: Vec<_> = environment.scopes().collect();
+    scopes.reverse();
+
+    // The outermost scope only holds the natives, which are looked up on use
+    println!("natives");
+    let mut indent = String::new();
+    for (i, scope) in scopes.iter().enumerate().skip(1) {
+        let label = match i {
+            1 => "globals",
+            _ if i == scopes.len() - 1 => "current scope",
+            _ => "enclosing scope",
+        };
+        println!("{}└─ {}", indent, label);
+        indent.push_str("   ");
+
+        let locals = scope.locals();
+        let last_scope = i == scopes.len() - 1;
+        if last_scope && locals.is_empty() {
+            println!("{}   (empty)", indent);
+        }
+        for (j, (name, value)) in locals.iter().enumerate() {
+            let branch = if last_scope && j == locals.len() - 1 {
+                "└─"
+            } else {
+                "├─"
+            };
+            println!(
+                "{}{} {}: {} = {}",
+                indent,
+                branch,
+                name,
+                value::type_name(value),
+                preview(value)
+            );
+        }
+    }
+}
+
+impl Failure {
+    fn print_stack(&self) {
+        println!("{} [line {}]", self.error.message(), self.error.line());
+        for line in self.error.trace_lines() {
+            println!("  {}", line);
+        }
+    }
+
+    /// Lets the user query the failure until they quit or `input` ends.
+    pub fn inspect(&self, input: impl BufRead) {
+        println!("Type 'help' for a list of commands.");
+        let mut stdout = io::stdout();
+        print!("(debug) ");
+        stdout.flush().unwrap();
+
+        for line in input.lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            let mut words = line.split_whitespace();
+            let command = words.next().unwrap_or_default();
+
+            match command {
+                "" => {}
+                "p" | "print" => match words.next() {
+                    Some(name) => print_variable(&self.scope, name),
+                    None => println!("Usage: print NAME"),
+                },
+                "l" | "locals" => print_locals(&self.scope),
+                "e" | "env" => print_scopes(&self.scope),
+                "s" | "stack" => self.print_stack(),
+                "q" | "quit" => break,
+                "h" | "help" => println!("{}", HELP),
+                _ => println!("Unknown command '{}'. Type 'help' for a list.", command),
+            }
+
+            print!("(debug) ");
+            stdout.flush().unwrap();
+        }
+        println!();
+    }
+}
@@ -0,0 +1,56 @@
+//! The generator behind `random` and `randomInt`. It is SplitMix64, kept
+//! here rather than taken from a crate so a seed gives the same sequence
+//! in every version of the interpreter.
+
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number from 0 up to, but not including, 1.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// An integer from `min` to `max`, inclusive.
+    pub fn range(&mut self, min: i64, max: i64) -> i64 {
+        let span = max.abs_diff(min) as u128 + 1;
+        let offset = (self.next_u64() as u128 * span) >> 64;
+        min.wrapping_add(offset as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_sequences_repeat() {
+        let mut a = Random::new(42);
+        let mut b = Random::new(42);
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+
+        for _ in 0..1000 {
+            let n = a.next_f64();
+            assert!((0.0..1.0).contains(&n));
+            assert!((-2..=3).contains(&a.range(-2, 3)));
+        }
+        assert_eq!(a.range(7, 7), 7);
+        // The full range doesn't overflow
+        a.range(i64::MIN, i64::MAX);
+    }
+}
@@ -0,0 +1,139 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::lox_error::{LoxError, NativeError};
+use crate::messages;
+use crate::token::Literal;
+
+/// One non-deterministic input observed while recording.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Event {
+    source: String,
+    result: Result<Value, String>,
+}
+
+/// Captures non-deterministic inputs to a trace, or feeds them back from
+/// one so a run can be reproduced exactly.
+#[derive(Default)]
+pub enum Tape {
+    #[default]
+    Off,
+    Record(Vec<Event>),
+    Replay(VecDeque<Event>),
+}
+
+impl Tape {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let events = fs::read_to_string(path)?
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?;
+        Ok(Tape::Replay(events))
+    }
+
+    /// A tape that replays what this one recorded.
+    pub fn rewound(&self) -> Tape {
+        match self {
+            Tape::Record(events) => Tape::Replay(events.iter().cloned().collect()),
+            Tape::Replay(_) | Tape::Off => Tape::Off,
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let Tape::Record(events) = self else {
+            return Ok(());
+        };
+
+        let mut trace = String::new();
+        for event in events {
+            trace.push_str(&serde_json::to_string(event)?);
+            trace.push('\n');
+        }
+        fs::write(path, trace)
+    }
+
+    /// Records the result of `produce`, or when replaying returns the
+    /// recorded result without calling it.
+    pub fn observe(
+        &mut self,
+        source: &str,
+        produce: impl FnOnce() -> Result<Literal, LoxError>,
+    ) -> Result<Literal, LoxError> {
+        match self {
+            Tape::Off => produce(),
+            Tape::Record(events) => {
+                let result = produce();
+                let recorded = match &result {
+                    Ok(value) => Ok(to_json(value)),
+                    Err(LoxError::Native(e)) => Err(e.to_string()),
+                    Err(_) => return result,
+                };
+                events.push(Event {
+                    source: source.to_string(),
+                    result: recorded,
+                });
+                result
+            }
+            Tape::Replay(events) => {
+                let Some(event) = events.pop_front() else {
+                    return Err(NativeError::from_message(messages::TRACE_ENDED, &[]).into());
+                };
+                if event.source != source {
+                    let message = messages::REPLAY_DIVERGED;
+                    return Err(
+                        NativeError::from_message(message, &[&event.source, &source]).into(),
+                    );
+                }
+                match event.result {
+                    Ok(value) => Ok(from_json(value)),
+                    Err(message) => Err(NativeError::new(&message).into()),
+                }
+            }
+        }
+    }
+}
+
+fn to_json(value: &Literal) -> Value {
+    match value {
+        Literal::None => Value::Null,
+        Literal::Bool(b) => Value::Bool(*b),
+        Literal::Number(n) => serde_json::Number::from_f64(*n).map_or(Value::Null, Value::Number),
+        Literal::String(s) => Value::String(s.clone()),
+        Literal::List(elements) => Value::Array(elements.borrow().iter().map(to_json).collect()),
+        Literal::Map(entries) => Value::Object(
+            entries
+                .borrow()
+                .iter()
+                .map(|(k, v)| (k.clone(), to_json(v)))
+                .collect(),
+        ),
+        Literal::Callable(_) | Literal::Foreign(_) | Literal::Instance(_) => {
+            unreachable!("Non-deterministic inputs are plain data")
+        }
+    }
+}
+
+fn from_json(value: Value) -> Literal {
+    match value {
+        Value::Null => Literal::None,
+        Value::Bool(b) => Literal::Bool(b),
+        Value::Number(n) => Literal::Number(n.as_f64().unwrap_or(f64::NAN)),
+        Value::String(s) => Literal::String(s),
+        Value::Array(elements) => Literal::List(Rc::new(RefCell::new(
+            elements.into_iter().map(from_json).collect(),
+        ))),
+        Value::Object(entries) => Literal::Map(Rc::new(RefCell::new(
+            entries
+                .into_iter()
+                .map(|(k, v)| (k, from_json(v)))
+                .collect(),
+        ))),
+    }
+}
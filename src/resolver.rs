@@ -0,0 +1,366 @@
+//! Works out, before a program runs, how many scopes out each variable it
+//! reads or assigns is declared, so the interpreter can go straight to that
+//! scope. A variable that isn't declared in any enclosing local scope is a
+//! global, looked up when it is used.
+//!
+//! The scopes pushed here must match the environments the interpreter
+//! creates: one per call, one for `this` in a bound method, one for `super`
+//! in a subclass, one per block that declares something, and two for a
+//! `try` statement.
+//!
+//! The same walk also produces the table `lox analyze --definitions` prints:
+//! every use of a variable together with where it was declared.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::ast::{Expr, Stmt};
+use crate::interpreter::declares;
+use crate::token::{Span, Token};
+
+/// Fills in the `depth` of every local variable in `statements`.
+/// `capture_by_iteration` must match the interpreter's setting, since it
+/// gives `for` loops a scope of their own.
+pub fn resolve(statements: &mut [Stmt], capture_by_iteration: bool) {
+    Resolver::new(capture_by_iteration, false).statements(statements);
+}
+
+/// A use of a variable and the declaration it refers to, which is `None`
+/// for natives and names that are never declared.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct Definition {
+    pub name: String,
+    pub span: Span,
+    pub declaration: Option<Span>,
+}
+
+/// Resolves `statements` like [`resolve`] and returns every variable use in
+/// them, in source order.
+pub fn definitions(statements: &mut [Stmt], capture_by_iteration: bool) -> Vec<Definition> {
+    let mut resolver = Resolver::new(capture_by_iteration, true);
+    resolver.statements(statements);
+    let globals = resolver.globals;
+    let mut definitions = resolver.definitions.unwrap_or_default();
+    // Globals are looked up when they are used, so a function can refer to
+    // one declared further down
+    for definition in &mut definitions {
+        if definition.declaration.is_none() {
+            definition.declaration = globals.get(&definition.name).copied();
+        }
+    }
+    definitions.sort_by_key(|definition| definition.span.start);
+    definitions
+}
+
+struct Resolver {
+    /// The names declared so far in each enclosing local scope, innermost
+    /// last, with where they were declared. Empty at the top level, where
+    /// everything is global.
+    scopes: Vec<HashMap<String, Span>>,
+    /// The first declaration of each global.
+    globals: HashMap<String, Span>,
+    /// Every variable use seen so far, when they are being collected.
+    definitions: Option<Vec<Definition>>,
+    capture_by_iteration: bool,
+}
+
+impl Resolver {
+    fn new(capture_by_iteration: bool, collect_definitions: bool) -> Self {
+        Self {
+            scopes: Vec::new(),
+            globals: HashMap::new(),
+            definitions: collect_definitions.then(Vec::new),
+            capture_by_iteration,
+        }
+    }
+
+    fn statements(&mut self, statements: &mut [Stmt]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn begin_scope<'a>(&mut self, names: impl IntoIterator<Item = &'a Token>) {
+        self.scopes.push(
+            names
+                .into_iter()
+                .map(|name| (name.lexeme.clone(), name.span()))
+                .collect(),
+        );
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Token) {
+        let span = name.span();
+        match self.scopes.last_mut() {
+            Some(scope) => {
+                scope.insert(name.lexeme.clone(), span);
+            }
+            None => {
+                self.globals.entry(name.lexeme.clone()).or_insert(span);
+            }
+        }
+    }
+
+    /// How many scopes out `name` is declared, or `None` for a global.
+    fn depth(&mut self, name: &Token) -> Option<usize> {
+        let found = self
+            .scopes
+            .iter()
+            .rev()
+            .enumerate()
+            .find_map(|(depth, scope)| scope.get(&name.lexeme).map(|span| (depth, *span)));
+        if let Some(definitions) = &mut self.definitions {
+            definitions.push(Definition {
+                name: name.lexeme.clone(),
+                span: name.span(),
+                declaration: found.map(|(_, span)| span),
+            });
+        }
+        found.map(|(depth, _)| depth)
+    }
+
+    fn function(&mut self, params: &[Token], body: &mut [Stmt]) {
+        self.begin_scope(params);
+        self.statements(body);
+        self.end_scope();
+    }
+
+    fn statement(&mut self, statement: &mut Stmt) {
+        match statement {
+            Stmt::Block { statements } => {
+                // Like the interpreter, only give a block a scope if it needs one
+                if statements.iter().any(declares) {
+                    self.begin_scope([]);
+                    self.statements(statements);
+                    self.end_scope();
+                } else {
+                    self.statements(statements);
+                }
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                ..
+            } => {
+                if let Some(superclass) = superclass {
+                    self.expression(superclass);
+                }
+                self.declare(name);
+
+                // `this` and `super` are never looked up by name here, so
+                // their scopes can start out empty
+                if superclass.is_some() {
+                    self.begin_scope([]);
+                }
+                for method in methods {
+                    let Stmt::Function { params, body, .. } = method else {
+                        unreachable!("Class bodies only hold methods");
+                    };
+                    self.begin_scope([]);
+                    self.function(params, body);
+                    self.end_scope();
+                }
+                if superclass.is_some() {
+                    self.end_scope();
+                }
+            }
+            Stmt::Break { .. } | Stmt::Continue { .. } => {}
+            Stmt::Expression { expression }
+            | Stmt::Print { expression, .. }
+            | Stmt::Throw {
+                value: expression, ..
+            } => self.expression(expression),
+            Stmt::Function {
+                name, params, body, ..
+            } => {
+                // Declared first, so the function can call itself
+                self.declare(name);
+                self.function(params, body);
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                self.expression(condition);
+                self.statement(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch);
+                }
+            }
+            Stmt::Return { value, .. } => {
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+            }
+            Stmt::Try {
+                body,
+                name,
+                handler,
+            } => {
+                self.begin_scope([]);
+                self.statements(body);
+                self.end_scope();
+
+                self.begin_scope([&**name]);
+                self.statements(handler);
+                self.end_scope();
+            }
+            Stmt::Var {
+                name, initializer, ..
+            } => {
+                // The initializer can't see the variable it initializes
+                if let Some(initializer) = initializer {
+                    self.expression(initializer);
+                }
+                self.declare(name);
+            }
+            Stmt::While {
+                condition,
+                body,
+                increment,
+                bindings,
+                ..
+            } => {
+                let per_iteration = self.capture_by_iteration && !bindings.is_empty();
+                if per_iteration {
+                    self.begin_scope(bindings.iter());
+                }
+                self.expression(condition);
+                self.statement(body);
+                if let Some(increment) = increment {
+                    self.expression(increment);
+                }
+                if per_iteration {
+                    self.end_scope();
+                }
+            }
+        }
+    }
+
+    fn expression(&mut self, expression: &mut Expr) {
+        match expression {
+            Expr::Assign { name, value, depth } => {
+                self.expression(value);
+                *depth = self.depth(name);
+            }
+            Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+                self.expression(left);
+                self.expression(right);
+            }
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                self.expression(callee);
+                for argument in arguments {
+                    self.expression(argument);
+                }
+            }
+            Expr::Get { object, .. } => self.expression(object),
+            Expr::Grouping { expression }
+            | Expr::Postfix {
+                target: expression, ..
+            }
+            | Expr::Spread { expression, .. }
+            | Expr::Unary {
+                right: expression, ..
+            } => self.expression(expression),
+            Expr::Index { object, index, .. } => {
+                self.expression(object);
+                self.expression(index);
+            }
+            Expr::Lambda { params, body, .. } => self.function(params, body),
+            Expr::List { elements } => {
+                for element in elements {
+                    self.expression(element);
+                }
+            }
+            Expr::Map { entries, .. } => {
+                for (key, value) in entries {
+                    self.expression(key);
+                    self.expression(value);
+                }
+            }
+            Expr::Ternary {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expression(condition);
+                self.expression(then_branch);
+                self.expression(else_branch);
+            }
+            Expr::Set { object, value, .. } => {
+                self.expression(object);
+                self.expression(value);
+            }
+            Expr::SetIndex {
+                object,
+                index,
+                value,
+                ..
+            } => {
+                self.expression(object);
+                self.expression(index);
+                self.expression(value);
+            }
+            // `this` and `super` are found by walking out from the current scope
+            Expr::Literal { .. } | Expr::Super { .. } | Expr::This { .. } => {}
+            Expr::Variable { name, depth } => *depth = self.depth(name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn resolved(source: &str) -> Vec<Stmt> {
+        let mut scanner = Scanner::new(source);
+        let mut statements = Parser::new(scanner.scan_tokens().unwrap()).parse().unwrap();
+        resolve(&mut statements, false);
+        statements
+    }
+
+    fn returned_depth(function: &Stmt) -> Option<usize> {
+        let Stmt::Function { body, .. } = function else {
+            panic!("Expected a function");
+        };
+        match body.last() {
+            Some(Stmt::Return {
+                value: Some(value), ..
+            }) => match **value {
+                Expr::Variable { depth, .. } => depth,
+                _ => panic!("Expected a variable"),
+            },
+            _ => panic!("Expected a return"),
+        }
+    }
+
+    #[test]
+    fn test_resolves_locals_and_leaves_globals() {
+        let statements = resolved(
+            "var g = 1;
+            fun outer(a) {
+              fun local() { return a; }
+              fun global() { return g; }
+              fun param(b) { var c = b; return c; }
+            }",
+        );
+        let Stmt::Function { body, .. } = &statements[1] else {
+            panic!("Expected a function");
+        };
+        assert_eq!(returned_depth(&body[0]), Some(1));
+        assert_eq!(returned_depth(&body[1]), None);
+        assert_eq!(returned_depth(&body[2]), Some(0));
+    }
+}
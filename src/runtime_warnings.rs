@@ -0,0 +1,53 @@
+//! Warnings about suspicious things a program does while it runs, for
+//! `--runtime-warnings`: comparing values of different types with `==`,
+//! using a function as a condition, and doing arithmetic with the nil a
+//! function returned by falling off its end. Each is reported once per line,
+//! however often the line runs.
+
+use std::collections::HashSet;
+
+use crate::lint::{Level, Lint, LintConfig, Warning};
+use crate::lox_error::{LoxError, RuntimeError};
+
+pub struct RuntimeWarnings {
+    config: LintConfig,
+    /// The lints already reported on each line.
+    reported: HashSet<(Lint, usize)>,
+    /// Warnings found since they were last taken.
+    pending: Vec<Warning>,
+}
+
+impl RuntimeWarnings {
+    pub fn new(config: LintConfig) -> Self {
+        Self {
+            config,
+            reported: HashSet::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Reports `lint` on `line` unless it already was. A lint set to deny
+    /// stops the program with a runtime error instead.
+    pub fn warn(
+        &mut self,
+        lint: Lint,
+        line: usize,
+        message: String,
+        help: String,
+    ) -> Result<(), LoxError> {
+        let Some(warning) = Warning::at_line(&self.config, lint, line, message, help) else {
+            return Ok(());
+        };
+        if warning.level == Level::Deny {
+            return Err(RuntimeError::at_line(line, warning.message()).into());
+        }
+        if self.reported.insert((lint, line)) {
+            self.pending.push(warning);
+        }
+        Ok(())
+    }
+
+    pub fn take(&mut self) -> Vec<Warning> {
+        std::mem::take(&mut self.pending)
+    }
+}
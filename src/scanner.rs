@@ -1,55 +1,92 @@
 use crate::lox_error::{LoxError, ScannerError};
-use crate::token::{Literal, Token};
+use crate::messages;
+use crate::token::{Literal, Span, Token};
 use crate::token_type::TokenType;
 use std::collections::HashMap;
 
+/// Hints that `condition` is almost always true. The hint only reaches
+/// the compiler with the `nightly` feature on a nightly toolchain.
+#[inline(always)]
+fn likely(condition: bool) -> bool {
+    #[cfg(nightly)]
+    return std::hint::likely(condition);
+    #[cfg(not(nightly))]
+    condition
+}
+
 #[derive(Default)]
 pub struct Scanner {
-    source: Vec<char>,
+    source: String,
     tokens: Vec<Token>,
     keywords: HashMap<String, TokenType>,
 
     start: usize,
     current: usize,
     line: usize,
+    /// The line the token being scanned starts on.
+    start_line: usize,
 }
 
 impl Scanner {
     pub fn new(source: &str) -> Self {
-        Self {
-            source: source.chars().collect(),
-            line: 1,
+        let mut scanner = Self {
             keywords: HashMap::from([
                 ("and".to_string(), TokenType::And),
+                ("break".to_string(), TokenType::Break),
+                ("catch".to_string(), TokenType::Catch),
                 ("class".to_string(), TokenType::Class),
+                ("continue".to_string(), TokenType::Continue),
                 ("else".to_string(), TokenType::Else),
                 ("false".to_string(), TokenType::False),
                 ("for".to_string(), TokenType::For),
                 ("fun".to_string(), TokenType::Fun),
                 ("if".to_string(), TokenType::If),
+                ("in".to_string(), TokenType::In),
                 ("nil".to_string(), TokenType::Nil),
                 ("or".to_string(), TokenType::Or),
                 ("print".to_string(), TokenType::Print),
                 ("return".to_string(), TokenType::Return),
                 ("super".to_string(), TokenType::Super),
                 ("this".to_string(), TokenType::This),
+                ("throw".to_string(), TokenType::Throw),
                 ("true".to_string(), TokenType::True),
+                ("try".to_string(), TokenType::Try),
                 ("var".to_string(), TokenType::Var),
                 ("while".to_string(), TokenType::While),
             ]),
             ..Default::default()
-        }
+        };
+        scanner.reset(source);
+        scanner
+    }
+
+    pub fn keywords(&self) -> impl Iterator<Item = &str> {
+        self.keywords.keys().map(String::as_str)
+    }
+
+    /// Prepares the scanner for a new source, keeping its keyword table and
+    /// buffers.
+    pub fn reset(&mut self, source: &str) {
+        self.source.clear();
+        self.source.push_str(source);
+        self.tokens.clear();
+        self.start = 0;
+        self.current = 0;
+        self.line = 1;
+        self.start_line = 1;
     }
 
-    pub fn scan_tokens(&mut self) -> Result<Vec<Token>, LoxError> {
+    pub fn scan_tokens(&mut self) -> Result<&[Token], LoxError> {
         while !self.is_at_end() {
             self.start = self.current;
+            self.start_line = self.line;
             self.scan_token()?;
         }
 
-        self.tokens
-            .push(Token::new(TokenType::Eof, "", None, self.line));
-        Ok(self.tokens.clone())
+        self.start = self.current;
+        self.start_line = self.line;
+        self.add_token(TokenType::Eof, None)?;
+        Ok(&self.tokens)
     }
 
     fn scan_token(&mut self) -> Result<(), LoxError> {
@@ -61,12 +98,54 @@ impl Scanner {
             ')' => self.add_token(TokenType::RightParen, None),
             '{' => self.add_token(TokenType::LeftBrace, None),
             '}' => self.add_token(TokenType::RightBrace, None),
+            '[' => self.add_token(TokenType::LeftBracket, None),
+            ']' => self.add_token(TokenType::RightBracket, None),
+            ':' => self.add_token(TokenType::Colon, None),
+            '?' => self.add_token(TokenType::Question, None),
             ',' => self.add_token(TokenType::Comma, None),
-            '.' => self.add_token(TokenType::Dot, None),
-            '-' => self.add_token(TokenType::Minus, None),
-            '+' => self.add_token(TokenType::Plus, None),
+            '.' => {
+                if self.peek() == Some('.') && self.peek_next() == Some('.') {
+                    self.advance();
+                    self.advance();
+                    self.add_token(TokenType::Ellipsis, None)
+                } else {
+                    self.add_token(TokenType::Dot, None)
+                }
+            }
+            '-' => {
+                let token_type = if self.match_next('=') {
+                    TokenType::MinusEqual
+                } else if self.match_next('-') {
+                    TokenType::MinusMinus
+                } else if self.match_next('>') {
+                    TokenType::ThinArrow
+                } else {
+                    TokenType::Minus
+                };
+                self.add_token(token_type, None)
+            }
+            '+' => {
+                let token_type = if self.match_next('=') {
+                    TokenType::PlusEqual
+                } else if self.match_next('+') {
+                    TokenType::PlusPlus
+                } else {
+                    TokenType::Plus
+                };
+                self.add_token(token_type, None)
+            }
             ';' => self.add_token(TokenType::Semicolon, None),
-            '*' => self.add_token(TokenType::Star, None),
+            '%' => self.add_token(TokenType::Percent, None),
+            '*' => {
+                let token_type = if self.match_next('=') {
+                    TokenType::StarEqual
+                } else if self.match_next('*') {
+                    TokenType::StarStar
+                } else {
+                    TokenType::Star
+                };
+                self.add_token(token_type, None)
+            }
             '!' => {
                 let token_type = if self.match_next('=') {
                     TokenType::BangEqual
@@ -78,6 +157,8 @@ impl Scanner {
             '=' => {
                 let token_type = if self.match_next('=') {
                     TokenType::EqualEqual
+                } else if self.match_next('>') {
+                    TokenType::Arrow
                 } else {
                     TokenType::Equal
                 };
@@ -99,15 +180,24 @@ impl Scanner {
                 };
                 self.add_token(token_type, None)
             }
+            '|' => {
+                if self.match_next('>') {
+                    self.add_token(TokenType::Pipe, None)
+                } else {
+                    Err(ScannerError::new(self.span(), messages::UNEXPECTED_CHARACTER).into())
+                }
+            }
 
             // Longer Lexemes
             '/' => {
-                if self.match_next('/') {
+                if self.peek() == Some('/') && self.peek_next() == Some('/') {
+                    self.doc_comment()
+                } else if self.match_next('/') {
                     // Comment, ignore rest of line
-                    while self.peek() != Some('\n') && !self.is_at_end() {
-                        self.advance();
-                    }
+                    self.advance_while(|c| c != '\n');
                     Ok(())
+                } else if self.match_next('=') {
+                    self.add_token(TokenType::SlashEqual, None)
                 } else {
                     self.add_token(TokenType::Slash, None)
                 }
@@ -133,56 +223,103 @@ impl Scanner {
                 } else if c.is_ascii_alphabetic() {
                     self.identifier()
                 } else {
-                    Err(ScannerError::new(self.line, "Unexpected character.").into())
+                    Err(ScannerError::new(self.span(), messages::UNEXPECTED_CHARACTER).into())
                 }
             }
         }
     }
 
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
     fn advance(&mut self) -> char {
-        let c = self.source[self.current];
-        self.current += 1;
+        let c = self.peek().expect("Not at end of source");
+        self.current += c.len_utf8();
         c
     }
 
     fn add_token(&mut self, type_: TokenType, literal: Option<Literal>) -> Result<(), LoxError> {
-        let text = String::from_iter(&self.source[self.start..self.current]);
-        self.tokens
-            .push(Token::new(type_, &text, literal, self.line));
+        let span = self.span();
+        self.tokens.push(Token {
+            line: span.line,
+            column: span.column,
+            offset: span.start,
+            ..Token::new(type_, &self.source[span.start..span.end], literal, 0)
+        });
         Ok(())
     }
 
+    /// Where the token being scanned is.
+    fn span(&self) -> Span {
+        let line_start = self.source[..self.start].rfind('\n').map_or(0, |i| i + 1);
+        Span {
+            line: self.start_line,
+            column: self.source[line_start..self.start].chars().count() + 1,
+            start: self.start,
+            end: self.current,
+        }
+    }
+
     fn is_at_end(&self) -> bool {
         self.current >= self.source.len()
     }
 
     fn match_next(&mut self, expected: char) -> bool {
-        if self.is_at_end() {
-            return false;
-        }
-        if self.source[self.current] != expected {
+        if self.peek() != Some(expected) {
             return false;
         }
-        self.current += 1;
+        self.current += expected.len_utf8();
         true
     }
 
-    fn peek(&self) -> Option<char> {
-        if self.is_at_end() {
-            None
+    /// The character at byte `offset`, decoding UTF-8 only when it isn't
+    /// ASCII, which almost all source is.
+    fn char_at(&self, offset: usize) -> Option<char> {
+        let byte = *self.source.as_bytes().get(offset)?;
+        if likely(byte.is_ascii()) {
+            Some(byte as char)
         } else {
-            Some(self.source[self.current])
+            self.source[offset..].chars().next()
         }
     }
 
+    fn peek(&self) -> Option<char> {
+        self.char_at(self.current)
+    }
+
     fn peek_next(&self) -> Option<char> {
-        if self.current + 1 >= self.source.len() {
-            None
-        } else {
-            Some(self.source[self.current + 1])
+        let next = self.current + self.peek()?.len_utf8();
+        self.char_at(next)
+    }
+
+    /// Consumes characters for as long as `test` holds for them.
+    fn advance_while(&mut self, test: impl Fn(char) -> bool) {
+        while self.peek().is_some_and(&test) {
+            self.advance();
         }
     }
 
+    /// A `///` comment documents the declaration after it. Four or more
+    /// slashes are an ordinary comment, e.g. for separator lines.
+    fn doc_comment(&mut self) -> Result<(), LoxError> {
+        self.current += 2;
+        if self.peek() == Some('/') {
+            self.advance_while(|c| c != '\n');
+            return Ok(());
+        }
+
+        let text_start = self.current;
+        self.advance_while(|c| c != '\n');
+        let text = &self.source[text_start..self.current];
+        let text = text
+            .strip_prefix(' ')
+            .unwrap_or(text)
+            .trim_end()
+            .to_string();
+        self.add_token(TokenType::DocComment, Some(Literal::String(text)))
+    }
+
     fn string(&mut self) -> Result<(), LoxError> {
         while self.peek() != Some('"') && !self.is_at_end() {
             if self.peek() == Some('\n') {
@@ -192,44 +329,36 @@ impl Scanner {
         }
 
         if self.is_at_end() {
-            return Err(ScannerError::new(self.line, "Unterminated string.").into());
+            return Err(ScannerError::new(self.span(), messages::UNTERMINATED_STRING).into());
         }
 
         // Eat the closing "
         self.advance();
 
         // Extract string
-        let val = String::from_iter(&self.source[self.start + 1..self.current - 1]);
+        let val = self.source[self.start + 1..self.current - 1].to_string();
 
         self.add_token(TokenType::String, Some(Literal::String(val)))
     }
 
     fn number(&mut self) -> Result<(), LoxError> {
-        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
-            self.advance();
-        }
+        self.advance_while(|c| c.is_ascii_digit());
 
         // Consume part after decimal separator
         if self.peek() == Some('.') && self.peek_next().is_some_and(|c| c.is_ascii_digit()) {
             self.advance();
-
-            while self.peek().is_some_and(|c| c.is_ascii_digit()) {
-                self.advance();
-            }
+            self.advance_while(|c| c.is_ascii_digit());
         }
-        let val = String::from_iter(&self.source[self.start..self.current]);
-        let val: f64 = val.parse().unwrap();
+        let val: f64 = self.source[self.start..self.current].parse().unwrap();
 
         self.add_token(TokenType::Number, Some(Literal::Number(val)))
     }
 
     fn identifier(&mut self) -> Result<(), LoxError> {
-        while self.peek().is_some_and(|c| c.is_ascii_alphanumeric()) {
-            self.advance();
-        }
+        self.advance_while(|c| c.is_ascii_alphanumeric());
 
-        let val = String::from_iter(&self.source[self.start..self.current]);
-        if let Some(keyword) = self.keywords.get(&val) {
+        let val = &self.source[self.start..self.current];
+        if let Some(keyword) = self.keywords.get(val) {
             self.add_token(keyword.clone(), None)
         } else {
             self.add_token(TokenType::Identifier, None)
@@ -0,0 +1,201 @@
+use std::error::Error;
+use std::io::Read;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tiny_http::{Header, Method, Request, Response, Server};
+use tracing::{debug, info, warn};
+
+use crate::interpreter::Interpreter;
+use crate::lox::Lox;
+use crate::lox_error::LoxError;
+use crate::output::SharedBuffer;
+
+const MAX_CALL_DEPTH: usize = 256;
+const STACK_SIZE: usize = 64 * 1024 * 1024;
+const MAX_WALL_TIME: Duration = Duration::from_secs(5);
+const MAX_OUTPUT: usize = 1024 * 1024;
+/// The largest request body accepted, in bytes.
+const MAX_BODY: u64 = 64 * 1024;
+/// How many programs run at once. Further requests wait their turn.
+const WORKERS: usize = 4;
+
+#[derive(Deserialize)]
+struct RunRequest {
+    source: String,
+}
+
+#[derive(Serialize)]
+struct Diagnostic {
+    kind: &'static str,
+    message: String,
+}
+
+#[derive(Serialize)]
+struct RunResponse {
+    status: &'static str,
+    output: String,
+    diagnostics: Vec<Diagnostic>,
+}
+
+fn run_source(source: &str, fuel: usize) -> RunResponse {
+    let output = SharedBuffer::default();
+    let mut interpreter = Interpreter::with_writer(output.clone());
+    interpreter.set_limits(Some(fuel), Some(MAX_WALL_TIME));
+    interpreter.set_max_call_depth(Some(MAX_CALL_DEPTH));
+    interpreter.set_max_output(Some(MAX_OUTPUT));
+    interpreter.set_sandboxed(true);
+
+    let mut diagnostics = Vec::new();
+    match Lox::with_interpreter(interpreter).run(source) {
+        Ok(()) | Err(LoxError::Return(_)) => {}
+        Err(e) => {
+            let kind = match e {
+                LoxError::Scanner(_) => "scanner",
+                LoxError::Parser(_) => "parser",
+                LoxError::Limit(_) => "limit",
+                _ => "runtime",
+            };
+            diagnostics.push(Diagnostic {
+                kind,
+                message: e.to_string(),
+            });
+        }
+    }
+
+    RunResponse {
+        status: if diagnostics.is_empty() {
+            "ok"
+        } else {
+            "error"
+        },
+        output: output.take(),
+        diagnostics,
+    }
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name, value).expect("Header is valid ASCII")
+}
+
+fn respond(request: Request, status: u16, body: String) {
+    let response = Response::from_string(body)
+        .with_status_code(status)
+        .with_header(header("Content-Type", "application/json"))
+        .with_header(header("Access-Control-Allow-Origin", "*"))
+        .with_header(header("Access-Control-Allow-Headers", "Content-Type"));
+
+    if let Err(e) = request.respond(response) {
+        warn!(error = %e, "failed to send response");
+    }
+}
+
+/// Reads a run request from `body`, or gives the status and message to
+/// reject it with.
+fn parse_request(body: impl Read) -> Result<RunRequest, (u16, String)> {
+    let mut text = String::new();
+    body.take(MAX_BODY + 1)
+        .read_to_string(&mut text)
+        .map_err(|e| (400, e.to_string()))?;
+    if text.len() as u64 > MAX_BODY {
+        return Err((413, format!("Request body exceeds {} bytes", MAX_BODY)));
+    }
+    serde_json::from_str(&text).map_err(|e| (400, e.to_string()))
+}
+
+fn handle(mut request: Request, fuel: usize) {
+    debug!(method = %request.method(), url = request.url(), "playground request");
+
+    match (request.method(), request.url()) {
+        (Method::Options, _) => respond(request, 204, String::new()),
+        (Method::Post, "/run") => match parse_request(request.as_reader()) {
+            Ok(run) => {
+                let response = run_source(&run.source, fuel);
+                let body = serde_json::to_string(&response).expect("Response serializes");
+                respond(request, 200, body)
+            }
+            Err((status, e)) => respond(
+                request,
+                status,
+                serde_json::json!({ "error": e }).to_string(),
+            ),
+        },
+        _ => respond(request, 404, r#"{"error":"not found"}"#.to_string()),
+    }
+}
+
+/// Serves `POST /run` requests carrying `{"source": "..."}`, each executed in
+/// a fresh interpreter limited to `fuel` statements, five seconds and a
+/// megabyte of output, on one of `WORKERS` threads.
+pub fn serve(host: &str, port: u16, fuel: usize) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let server = Arc::new(Server::http((host, port))?);
+    info!(host, port, "playground server listening");
+
+    let workers = (0..WORKERS)
+        .map(|_| {
+            let server = Arc::clone(&server);
+            thread::Builder::new()
+                .stack_size(STACK_SIZE)
+                .spawn(move || {
+                    for request in server.incoming_requests() {
+                        // A bug in the interpreter mustn't take a worker down
+                        let _ = panic::catch_unwind(AssertUnwindSafe(|| handle(request, fuel)));
+                    }
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for worker in workers {
+        let _ = worker.join();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_is_refused() {
+        for source in ["readLine();", "readNumber();"] {
+            let response = run_source(source, 1000);
+            assert_eq!(response.status, "error");
+            assert_eq!(response.diagnostics[0].kind, "runtime");
+            assert!(response.diagnostics[0]
+                .message
+                .contains("disabled in the sandbox"));
+        }
+    }
+
+    #[test]
+    fn test_runaway_script_is_stopped_by_the_clock() {
+        let response = run_source("while (true) {}", usize::MAX);
+        assert_eq!(response.status, "error");
+        assert_eq!(response.diagnostics[0].kind, "limit");
+        assert!(response.diagnostics[0].message.starts_with("Time limit"));
+    }
+
+    #[test]
+    fn test_oversized_body_is_rejected() {
+        let source = "print 1;".repeat(MAX_BODY as usize);
+        let body = serde_json::json!({ "source": source }).to_string();
+        let Err((status, _)) = parse_request(body.as_bytes()) else {
+            panic!("An oversized body should be rejected");
+        };
+        assert_eq!(status, 413);
+
+        let run = parse_request(r#"{"source": "print 1;"}"#.as_bytes()).unwrap();
+        assert_eq!(run.source, "print 1;");
+    }
+
+    #[test]
+    fn test_output_is_capped() {
+        let response = run_source("while (true) print \"spam\";", usize::MAX);
+        assert_eq!(response.status, "error");
+        assert!(response.output.len() <= MAX_OUTPUT);
+        assert!(response.diagnostics[0].message.starts_with("Output limit"));
+    }
+}
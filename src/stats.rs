@@ -0,0 +1,31 @@
+use std::fmt;
+
+#[derive(Debug, Default, Clone)]
+pub struct Stats {
+    pub statements: usize,
+    pub expressions: usize,
+    pub calls: usize,
+    pub environments: usize,
+    pub max_call_depth: usize,
+    pub method_cache_hits: usize,
+    pub method_cache_misses: usize,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rows = [
+            ("statements executed", self.statements),
+            ("expressions evaluated", self.expressions),
+            ("function calls", self.calls),
+            ("environments allocated", self.environments),
+            ("peak call depth", self.max_call_depth),
+            ("method cache hits", self.method_cache_hits),
+            ("method cache misses", self.method_cache_misses),
+        ];
+
+        for (name, value) in rows {
+            writeln!(f, "{:<24}{:>12}", name, value)?;
+        }
+        Ok(())
+    }
+}
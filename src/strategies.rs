@@ -0,0 +1,401 @@
+//! Proptest strategies for generating well-formed Lox programs, as source
+//! text or as syntax trees. Enabled outside this crate's own tests by the
+//! `proptest` feature.
+
+use proptest::prelude::*;
+
+use crate::ast::{Expr, Stmt};
+use crate::token::{Literal, Token};
+use crate::token_type::TokenType;
+
+/// A generated program fragment: its Lox source text and the syntax tree
+/// the parser should build from it, rendered by `ast_printer`.
+#[derive(Debug, Clone)]
+pub struct Generated {
+    pub source: String,
+    pub tree: String,
+}
+
+/// Globals that generated code may refer to. See `PRELUDE`.
+const VARIABLES: [&str; 3] = ["a", "b", "c"];
+
+/// Defines every name generated code uses, so programs get past name
+/// lookup and exercise more of the interpreter.
+pub const PRELUDE: &str = "var a = 1; var b = \"s\"; var c = nil; fun f(x, y) { return y; }";
+
+impl Generated {
+    fn new(source: String, tree: String) -> Self {
+        Self { source, tree }
+    }
+}
+
+fn literal() -> impl Strategy<Value = Generated> {
+    prop_oneof![
+        (0u32..100).prop_map(|n| Generated::new(n.to_string(), n.to_string())),
+        "[a-z ]{0,8}".prop_map(|s| Generated::new(format!("\"{}\"", s), format!("{:?}", s))),
+        Just(Generated::new("true".into(), "true".into())),
+        Just(Generated::new("false".into(), "false".into())),
+        Just(Generated::new("nil".into(), "nil".into())),
+    ]
+}
+
+fn variable() -> impl Strategy<Value = Generated> {
+    proptest::sample::select(&VARIABLES[..]).prop_map(|v| Generated::new(v.into(), v.into()))
+}
+
+/// Expressions of every kind, parenthesized in the source so precedence
+/// never changes the expected tree.
+pub fn expr() -> impl Strategy<Value = Generated> {
+    let leaf = prop_oneof![literal(), variable()];
+    leaf.prop_recursive(4, 32, 2, |inner| {
+        let binary_op = proptest::sample::select(
+            &[
+                "+", "-", "*", "/", "%", "**", "<", "<=", ">", ">=", "==", "!=", "and", "or",
+            ][..],
+        );
+        prop_oneof![
+            (inner.clone(), binary_op, inner.clone()).prop_map(|(l, op, r)| Generated::new(
+                format!("({} {} {})", l.source, op, r.source),
+                format!("(group ({} {} {}))", op, l.tree, r.tree),
+            )),
+            (proptest::sample::select(&["-", "!"][..]), inner.clone()).prop_map(|(op, e)| {
+                Generated::new(
+                    format!("({} {})", op, e.source),
+                    format!("(group ({} {}))", op, e.tree),
+                )
+            }),
+            (inner.clone(), inner.clone()).prop_map(|(x, y)| Generated::new(
+                format!("f({}, {})", x.source, y.source),
+                format!("(call f {} {})", x.tree, y.tree),
+            )),
+            (inner.clone(), inner.clone()).prop_map(|(x, y)| Generated::new(
+                format!("({} |> f({}))", x.source, y.source),
+                format!("(group (call f {} {}))", x.tree, y.tree),
+            )),
+            (inner.clone(), inner.clone(), inner.clone()).prop_map(|(c, a, b)| Generated::new(
+                format!("({} ? {} : {})", c.source, a.source, b.source),
+                format!("(group (?: {} {} {}))", c.tree, a.tree, b.tree),
+            )),
+            (variable(), proptest::sample::select(&["++", "--"][..])).prop_map(|(v, op)| {
+                Generated::new(
+                    format!("({}{})", v.source, op),
+                    format!("(group (postfix {} {}))", op, v.tree),
+                )
+            }),
+            (variable(), inner.clone()).prop_map(|(v, i)| Generated::new(
+                format!("{}[{}]", v.source, i.source),
+                format!("(index {} {})", v.tree, i.tree),
+            )),
+            // Wrapped in parentheses so a statement can't mistake it for a block
+            prop::collection::vec((variable(), inner.clone()), 0..3).prop_map(|entries| {
+                let sources: Vec<_> = entries
+                    .iter()
+                    .map(|(k, v)| format!("\"{}\": {}", k.source, v.source))
+                    .collect();
+                let trees: Vec<_> = entries
+                    .iter()
+                    .map(|(k, v)| format!(" \"{}\" {}", k.source, v.tree))
+                    .collect();
+                Generated::new(
+                    format!("({{{}}})", sources.join(", ")),
+                    format!("(group (map{}))", trees.concat()),
+                )
+            }),
+            prop::collection::vec(inner, 0..3).prop_map(|elements| {
+                let sources: Vec<_> = elements.iter().map(|e| e.source.as_str()).collect();
+                let trees: Vec<_> = elements.iter().map(|e| format!(" {}", e.tree)).collect();
+                Generated::new(
+                    format!("[{}]", sources.join(", ")),
+                    format!("(list{})", trees.concat()),
+                )
+            }),
+        ]
+    })
+}
+
+/// Statements of every kind that don't need a surrounding loop or function.
+/// Branch and loop bodies are always blocks, so they may declare variables.
+pub fn stmt() -> impl Strategy<Value = Generated> {
+    let simple = prop_oneof![
+        expr().prop_map(|e| Generated::new(
+            format!("print {};", e.source),
+            format!("(print {})", e.tree)
+        )),
+        expr().prop_map(|e| Generated::new(format!("{};", e.source), format!("(; {})", e.tree))),
+        (variable(), expr()).prop_map(|(v, e)| Generated::new(
+            format!("var {} = {};", v.source, e.source),
+            format!("(var {} {})", v.tree, e.tree),
+        )),
+        (variable(), expr()).prop_map(|(v, e)| Generated::new(
+            format!("{} = {};", v.source, e.source),
+            format!("(; (= {} {}))", v.tree, e.tree),
+        )),
+        (variable(), expr()).prop_map(|(v, e)| Generated::new(
+            format!("{} += {};", v.source, e.source),
+            format!("(; (= {} (+ {} {})))", v.tree, v.tree, e.tree),
+        )),
+        (variable(), expr(), expr()).prop_map(|(v, i, e)| Generated::new(
+            format!("{}[{}] -= {};", v.source, i.source, e.source),
+            format!("(; (-= (index {} {}) {}))", v.tree, i.tree, e.tree),
+        )),
+        expr().prop_map(|e| Generated::new(
+            format!("throw {};", e.source),
+            format!("(throw {})", e.tree)
+        )),
+    ];
+    simple.prop_recursive(3, 16, 3, |inner| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..3).prop_map(|statements| {
+                let sources: Vec<_> = statements.iter().map(|s| s.source.as_str()).collect();
+                let trees: Vec<_> = statements.iter().map(|s| format!(" {}", s.tree)).collect();
+                Generated::new(
+                    format!("{{ {} }}", sources.join(" ")),
+                    format!("(block{})", trees.concat()),
+                )
+            }),
+            (expr(), inner.clone(), inner.clone()).prop_map(|(c, t, e)| Generated::new(
+                format!(
+                    "if ({}) {{ {} }} else {{ {} }}",
+                    c.source, t.source, e.source
+                ),
+                format!("(if {} (block {}) (block {}))", c.tree, t.tree, e.tree),
+            )),
+            (expr(), inner.clone()).prop_map(|(c, body)| Generated::new(
+                format!("while ({}) {{ {} }}", c.source, body.source),
+                format!("(while {} (block {}))", c.tree, body.tree),
+            )),
+            (inner.clone(), inner.clone()).prop_map(|(body, handler)| Generated::new(
+                format!(
+                    "try {{ {} }} catch (e) {{ {} }}",
+                    body.source, handler.source
+                ),
+                format!("(try (block {}) (catch e {}))", body.tree, handler.tree),
+            )),
+            inner.prop_map(|body| Generated::new(
+                format!("class C {{ m() {{ {} }} }}", body.source),
+                format!("(class C (fun m () {}))", body.tree),
+            )),
+        ]
+    })
+}
+
+/// Whole programs, one statement per line.
+pub fn program() -> impl Strategy<Value = Vec<Generated>> {
+    prop::collection::vec(stmt(), 1..6)
+}
+
+fn token(type_: TokenType, lexeme: &str) -> Token {
+    Token::new(type_, lexeme, None, 1)
+}
+
+fn grouped(expression: Expr) -> Expr {
+    Expr::Grouping {
+        expression: Box::new(expression),
+    }
+}
+
+fn variable_node() -> impl Strategy<Value = Expr> {
+    proptest::sample::select(&VARIABLES[..]).prop_map(|v| Expr::Variable {
+        name: token(TokenType::Identifier, v),
+        depth: None,
+    })
+}
+
+fn literal_node() -> impl Strategy<Value = Expr> {
+    let literal = |value| Expr::Literal { value };
+    prop_oneof![
+        (0u32..100).prop_map(move |n| literal(Literal::Number(n.into()))),
+        "[a-z ]{0,8}".prop_map(move |s| literal(Literal::String(s))),
+        any::<bool>().prop_map(move |b| literal(Literal::Bool(b))),
+        Just(()).prop_map(move |_| literal(Literal::None)),
+    ]
+}
+
+/// Expression trees like those of `expr`, with every operator wrapped in a
+/// grouping so that printing them never needs more parentheses.
+pub fn expr_node() -> impl Strategy<Value = Expr> {
+    let leaf = prop_oneof![literal_node(), variable_node()];
+    leaf.prop_recursive(4, 32, 2, |inner| {
+        let binary_op = proptest::sample::select(
+            &[
+                (TokenType::Plus, "+"),
+                (TokenType::Minus, "-"),
+                (TokenType::Star, "*"),
+                (TokenType::Slash, "/"),
+                (TokenType::Percent, "%"),
+                (TokenType::Less, "<"),
+                (TokenType::GreaterEqual, ">="),
+                (TokenType::EqualEqual, "=="),
+                (TokenType::BangEqual, "!="),
+            ][..],
+        );
+        let logical_op =
+            proptest::sample::select(&[(TokenType::And, "and"), (TokenType::Or, "or")][..]);
+        let unary_op =
+            proptest::sample::select(&[(TokenType::Minus, "-"), (TokenType::Bang, "!")][..]);
+        prop_oneof![
+            (inner.clone(), binary_op, inner.clone()).prop_map(|(left, (type_, op), right)| {
+                grouped(Expr::Binary {
+                    left: Box::new(left),
+                    operator: token(type_, op),
+                    right: Box::new(right),
+                })
+            }),
+            (inner.clone(), logical_op, inner.clone()).prop_map(|(left, (type_, op), right)| {
+                grouped(Expr::Logical {
+                    left: Box::new(left),
+                    operator: token(type_, op),
+                    right: Box::new(right),
+                })
+            }),
+            (unary_op, inner.clone()).prop_map(|((type_, op), right)| {
+                grouped(Expr::Unary {
+                    operator: token(type_, op),
+                    right: Box::new(right),
+                })
+            }),
+            prop::collection::vec(inner.clone(), 0..3).prop_map(|arguments| Expr::Call {
+                callee: Box::new(Expr::Variable {
+                    name: token(TokenType::Identifier, "f"),
+                    depth: None,
+                }),
+                paren: token(TokenType::RightParen, ")"),
+                arguments,
+            }),
+            (inner.clone(), inner.clone(), inner.clone()).prop_map(|(c, a, b)| {
+                grouped(Expr::Ternary {
+                    condition: Box::new(c),
+                    then_branch: Box::new(a),
+                    else_branch: Box::new(b),
+                })
+            }),
+            (variable_node(), inner.clone()).prop_map(|(object, index)| Expr::Index {
+                object: Box::new(object),
+                bracket: token(TokenType::RightBracket, "]"),
+                index: Box::new(index),
+            }),
+            prop::collection::vec(inner.clone(), 0..3).prop_map(|elements| Expr::List { elements }),
+            prop::collection::vec(("[a-z]{1,3}", inner), 0..3).prop_map(|entries| {
+                grouped(Expr::Map {
+                    brace: token(TokenType::LeftBrace, "{"),
+                    entries: entries
+                        .into_iter()
+                        .map(|(key, value)| {
+                            let key = Expr::Literal {
+                                value: Literal::String(key),
+                            };
+                            (key, value)
+                        })
+                        .collect(),
+                })
+            }),
+        ]
+    })
+}
+
+fn block(statements: Vec<Stmt>) -> Box<Stmt> {
+    Box::new(Stmt::Block { statements })
+}
+
+/// Statement trees like those of `stmt`.
+pub fn stmt_node() -> impl Strategy<Value = Stmt> {
+    let simple = prop_oneof![
+        expr_node().prop_map(|e| Stmt::Print {
+            keyword: Box::new(token(TokenType::Print, "print")),
+            expression: Box::new(e),
+        }),
+        expr_node().prop_map(|e| Stmt::Expression {
+            expression: Box::new(e),
+        }),
+        (variable_node(), proptest::option::of(expr_node())).prop_map(|(v, e)| {
+            let Expr::Variable { name, .. } = v else {
+                unreachable!("Generated a variable");
+            };
+            Stmt::Var {
+                name: Box::new(name),
+                annotation: None,
+                initializer: e.map(Box::new),
+            }
+        }),
+        expr_node().prop_map(|e| Stmt::Throw {
+            keyword: Box::new(token(TokenType::Throw, "throw")),
+            value: Box::new(e),
+        }),
+    ];
+    simple.prop_recursive(3, 16, 3, |inner| {
+        let body = prop::collection::vec(inner, 0..3);
+        prop_oneof![
+            body.clone()
+                .prop_map(|statements| Stmt::Block { statements }),
+            (
+                expr_node(),
+                body.clone(),
+                proptest::option::of(body.clone())
+            )
+                .prop_map(|(condition, then_branch, else_branch)| Stmt::If {
+                    keyword: Box::new(token(TokenType::If, "if")),
+                    condition: Box::new(condition),
+                    then_branch: block(then_branch),
+                    else_branch: else_branch.map(block),
+                }),
+            (expr_node(), body.clone()).prop_map(|(condition, body)| Stmt::While {
+                keyword: Box::new(token(TokenType::While, "while")),
+                condition: Box::new(condition),
+                body: block(body),
+                increment: None,
+                bindings: Vec::new(),
+                label: None,
+            }),
+            (body.clone(), body).prop_map(|(body, handler)| Stmt::Try {
+                body,
+                name: Box::new(token(TokenType::Identifier, "e")),
+                handler,
+            }),
+        ]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast_printer::{print_stmt, source_stmt};
+    use crate::interpreter::Interpreter;
+    use crate::lox::Lox;
+    use crate::output::SharedBuffer;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    proptest! {
+        #[test]
+        fn test_parse_matches_generated_tree(stmt in stmt()) {
+            let mut scanner = Scanner::new(&stmt.source);
+            let tokens = scanner.scan_tokens().unwrap();
+            let statements = Parser::new(tokens).parse().unwrap();
+            prop_assert_eq!(statements.len(), 1);
+            prop_assert_eq!(print_stmt(&statements[0]), stmt.tree);
+        }
+
+        #[test]
+        fn test_source_of_tree_reparses_to_it(stmt in stmt_node()) {
+            let source = source_stmt(&stmt);
+            let mut scanner = Scanner::new(&source);
+            let tokens = scanner.scan_tokens().unwrap();
+            let statements = Parser::new(tokens).parse().unwrap();
+            prop_assert_eq!(statements.len(), 1);
+            prop_assert_eq!(print_stmt(&statements[0]), print_stmt(&stmt));
+        }
+
+        #[test]
+        fn test_interpreter_never_panics(program in program()) {
+            let mut interpreter = Interpreter::with_writer(SharedBuffer::default());
+            interpreter.set_limits(Some(1_000), None);
+            interpreter.set_max_call_depth(Some(64));
+            let mut lox = Lox::with_interpreter(interpreter);
+            lox.run(PRELUDE).unwrap();
+
+            let lines: Vec<_> = program.iter().map(|s| s.source.as_str()).collect();
+            // Runtime errors and running out of fuel are fine; panics aren't
+            let _ = lox.run(&lines.join("\n"));
+        }
+    }
+}
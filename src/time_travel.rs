@@ -0,0 +1,437 @@
+//! `lox debug`: after a run, walk backwards and forwards through the
+//! statements it executed and inspect the variables before each one.
+//!
+//! Scopes are shared through `Rc`s, closures and lists, and the calls in
+//! progress live on the Rust stack, so the state at a step can't be copied
+//! aside. Snapshots are processes instead: the program is replayed against
+//! the inputs recorded by the first run, and every `SNAPSHOT_INTERVAL`
+//! statements the replay forks a copy of itself that waits, paused before
+//! that statement. Moving to a step forks the nearest snapshot before it
+//! and runs the fork on from there, so no move re-runs more than
+//! `SNAPSHOT_INTERVAL` statements. The replaying processes take requests
+//! from the debugger over a Unix socket.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::ast::Stmt;
+use crate::ast_printer;
+use crate::hooks::Hook;
+use crate::interpreter::Interpreter;
+use crate::parser::Parser;
+use crate::post_mortem;
+use crate::replay::Tape;
+use crate::resolver;
+use crate::scanner::Scanner;
+
+const HELP: &str = "\
+back [N]      step back N statements (default 1)
+forward [N]   step forward N statements (default 1)
+print NAME    show the value of a variable
+locals        show the variables of the innermost scope
+quit          leave the debugger";
+
+/// How many statements apart snapshots are taken.
+const SNAPSHOT_INTERVAL: usize = 100;
+
+/// What the debugger asks of a replaying process.
+#[derive(Serialize, Deserialize)]
+enum Request {
+    /// Run on to a later step.
+    Run(usize),
+    /// Fork a copy that runs on to a step, staying paused here.
+    Fork(usize),
+    Print(String),
+    Locals,
+}
+
+/// What a replaying process tells the debugger.
+#[derive(Serialize, Deserialize)]
+enum Reply {
+    /// A snapshot of this step is connecting to the debugger.
+    Snapshot(usize),
+    /// Paused before the statement of a step.
+    At {
+        step: usize,
+        statement: String,
+    },
+    Done,
+    /// The replay couldn't do what was asked.
+    Failed(String),
+}
+
+/// One end of a connection between the debugger and a replaying process,
+/// carrying a JSON message per line.
+struct Channel {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+impl Channel {
+    fn new(stream: UnixStream) -> io::Result<Self> {
+        Ok(Self {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+        })
+    }
+
+    fn accept(listener: &UnixListener) -> io::Result<Self> {
+        Self::new(listener.accept()?.0)
+    }
+
+    fn connect(socket: &Path) -> io::Result<Self> {
+        Self::new(UnixStream::connect(socket)?)
+    }
+
+    fn send(&mut self, message: &impl Serialize) -> io::Result<()> {
+        let mut line = serde_json::to_string(message)?;
+        line.push('\n');
+        self.writer.write_all(line.as_bytes())
+    }
+
+    fn receive<T: DeserializeOwned>(&mut self) -> io::Result<T> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(serde_json::from_str(&line)?)
+    }
+}
+
+/// Ends a replaying process without running anything the debugger's
+/// process would run on its way out.
+fn exit() -> ! {
+    // Safety: `_exit` only ends the process
+    unsafe { libc::_exit(0) }
+}
+
+/// Drives a replay from inside it: forks snapshots as it passes them and
+/// serves the debugger once it reaches its target.
+struct Replayer {
+    channel: Channel,
+    socket: PathBuf,
+    target: usize,
+}
+
+impl Replayer {
+    /// Forks this process, giving the copy a connection of its own, and
+    /// returns whether this is the copy.
+    fn fork(&mut self) -> bool {
+        // Connecting first queues the copy's connection ahead of anything
+        // this process goes on to do, so the debugger accepts them in order
+        let Ok(channel) = Channel::connect(&self.socket) else {
+            exit();
+        };
+        // Safety: a replay only has the one thread, so the copy can't
+        // inherit a lock another thread holds
+        match unsafe { libc::fork() } {
+            -1 => exit(),
+            0 => {
+                self.channel = channel;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn send(&mut self, reply: &Reply) {
+        if self.channel.send(reply).is_err() {
+            exit();
+        }
+    }
+
+    /// Answers requests while paused before `statement`, returning when
+    /// this process should run on.
+    fn serve(&mut self, step: usize, statement: &Stmt, interpreter: &Interpreter) {
+        loop {
+            let Ok(request) = self.channel.receive() else {
+                exit();
+            };
+            match request {
+                Request::Run(target) => {
+                    self.target = target;
+                    return;
+                }
+                Request::Fork(target) => {
+                    if !self.fork() {
+                        self.send(&Reply::Done);
+                        continue;
+                    }
+                    self.target = target;
+                    if target != step {
+                        return;
+                    }
+                    self.arrive(step, statement);
+                }
+                Request::Print(name) => {
+                    post_mortem::print_variable(&interpreter.environment, &name);
+                    self.printed();
+                }
+                Request::Locals => {
+                    post_mortem::print_locals(&interpreter.environment);
+                    self.printed();
+                }
+            }
+        }
+    }
+
+    fn arrive(&mut self, step: usize, statement: &Stmt) {
+        let statement = ast_printer::print_stmt(statement);
+        self.send(&Reply::At { step, statement });
+    }
+
+    fn printed(&mut self) {
+        if io::stdout().flush().is_err() {
+            exit();
+        }
+        self.send(&Reply::Done);
+    }
+}
+
+impl Hook for Replayer {
+    fn on_statement(&mut self, statement: &Stmt, interpreter: &Interpreter) {
+        let step = interpreter.stats.statements;
+        if step % SNAPSHOT_INTERVAL == 1 {
+            if self.fork() {
+                self.serve(step, statement, interpreter);
+            } else {
+                self.send(&Reply::Snapshot(step));
+            }
+        }
+
+        if step == self.target {
+            self.arrive(step, statement);
+            self.serve(step, statement, interpreter);
+        }
+    }
+}
+
+/// The first replaying process: runs `statements` until told to stop.
+fn replay(mut interpreter: Interpreter, statements: Vec<Stmt>, socket: &Path, target: usize) -> ! {
+    // Nothing waits for the copies replays fork, so have them reaped
+    // Safety: ignoring a signal only changes how this process handles it
+    unsafe { libc::signal(libc::SIGCHLD, libc::SIG_IGN) };
+
+    let Ok(channel) = Channel::connect(socket) else {
+        exit();
+    };
+    let replayer = Rc::new(RefCell::new(Replayer {
+        channel,
+        socket: socket.to_path_buf(),
+        target,
+    }));
+    interpreter.add_hook(replayer.clone());
+    let _ = interpreter.interpret(statements);
+
+    // Only reached by a process whose replay ended before its target
+    let mut replayer = replayer.borrow_mut();
+    let message = format!(
+        "The replay ended after {} statements, before step {}.",
+        interpreter.stats.statements, replayer.target
+    );
+    replayer.send(&Reply::Failed(message));
+    exit()
+}
+
+/// Where a replay is paused.
+struct Position {
+    step: usize,
+    statement: String,
+}
+
+/// The debugger's side of the replaying processes.
+struct Replays {
+    listener: UnixListener,
+    /// Paused replays by the step they are paused before.
+    snapshots: BTreeMap<usize, Channel>,
+}
+
+impl Replays {
+    /// Waits for `replay` to pause, keeping the snapshots it takes on the
+    /// way.
+    fn follow(&mut self, replay: &mut Channel) -> Result<Position, Box<dyn Error>> {
+        loop {
+            match replay.receive()? {
+                Reply::Snapshot(step) => {
+                    let snapshot = Channel::accept(&self.listener)?;
+                    // A replay running past an earlier snapshot takes another
+                    self.snapshots.entry(step).or_insert(snapshot);
+                }
+                Reply::At { step, statement } => return Ok(Position { step, statement }),
+                Reply::Failed(message) => return Err(message.into()),
+                Reply::Done => return Err("Unexpected reply from the replay.".into()),
+            }
+        }
+    }
+
+    /// Forks the nearest snapshot at or before `step` and runs the copy on
+    /// to it.
+    fn fork_to(&mut self, step: usize) -> Result<(Channel, Position), Box<dyn Error>> {
+        let (_, snapshot) = self
+            .snapshots
+            .range_mut(..=step)
+            .next_back()
+            .ok_or("No snapshot to start from.")?;
+        snapshot.send(&Request::Fork(step))?;
+        match snapshot.receive()? {
+            Reply::Done => {}
+            _ => return Err("Unexpected reply from a snapshot.".into()),
+        }
+
+        let mut replay = Channel::accept(&self.listener)?;
+        let position = self.follow(&mut replay)?;
+        Ok((replay, position))
+    }
+}
+
+struct Session {
+    replays: Replays,
+    /// The replay paused at `position`.
+    current: Channel,
+    position: Position,
+    /// Statements the recorded run executed, including the one it failed in.
+    steps: usize,
+}
+
+impl Session {
+    fn move_to(&mut self, step: usize) -> Result<(), Box<dyn Error>> {
+        let step = step.clamp(1, self.steps);
+        if step > self.position.step {
+            self.current.send(&Request::Run(step))?;
+            match self.replays.follow(&mut self.current) {
+                Ok(position) => self.position = position,
+                Err(e) => {
+                    println!("{}", e);
+                    // The replay that was running on is gone
+                    (self.current, self.position) = self.replays.fork_to(self.position.step)?;
+                }
+            }
+        } else if step < self.position.step {
+            match self.replays.fork_to(step) {
+                Ok((current, position)) => (self.current, self.position) = (current, position),
+                Err(e) => println!("{}", e),
+            }
+        }
+        self.show_position();
+        Ok(())
+    }
+
+    /// Has the current replay print something, waiting until it has.
+    fn show(&mut self, request: Request) -> Result<(), Box<dyn Error>> {
+        self.current.send(&request)?;
+        match self.current.receive()? {
+            Reply::Done => Ok(()),
+            _ => Err("Unexpected reply from the replay.".into()),
+        }
+    }
+
+    fn show_position(&self) {
+        println!(
+            "[step {}/{}] {}",
+            self.position.step, self.steps, self.position.statement
+        );
+    }
+}
+
+/// Lets the user step through the `steps` statements that a run of `source`
+/// executed while recording to `tape`, starting at the last one.
+///
+/// The replays are forks of this process, so no other thread may be
+/// running while it is called.
+pub fn explore(
+    source: &str,
+    tape: &Tape,
+    steps: usize,
+    input: impl BufRead,
+) -> Result<(), Box<dyn Error>> {
+    if steps == 0 {
+        return Ok(());
+    }
+
+    let mut interpreter = Interpreter::with_writer(io::sink());
+    interpreter.tape = tape.rewound();
+    let mut scanner = Scanner::new(source);
+    let mut statements = Parser::new(scanner.scan_tokens()?).parse()?;
+    resolver::resolve(&mut statements, interpreter.capture_by_iteration());
+
+    let socket = std::env::temp_dir().join(format!("lox-debug-{}.sock", std::process::id()));
+    let _ = fs::remove_file(&socket);
+    let listener = UnixListener::bind(&socket)?;
+
+    // The replay would print anything still buffered again
+    io::stdout().flush()?;
+    // Safety: as documented, no other thread can be holding a lock the
+    // replay would inherit
+    let child = match unsafe { libc::fork() } {
+        -1 => return Err(io::Error::last_os_error().into()),
+        0 => replay(interpreter, statements, &socket, steps),
+        child => child,
+    };
+
+    let result = debug(listener, steps, input);
+    // Once the session is dropped, every replay has been told to stop
+    // Safety: `child` is this process's own child
+    unsafe { libc::waitpid(child, std::ptr::null_mut(), 0) };
+    let _ = fs::remove_file(&socket);
+    result
+}
+
+fn debug(listener: UnixListener, steps: usize, input: impl BufRead) -> Result<(), Box<dyn Error>> {
+    let mut replays = Replays {
+        listener,
+        snapshots: BTreeMap::new(),
+    };
+    let mut current = Channel::accept(&replays.listener)?;
+    let position = replays.follow(&mut current)?;
+    let mut session = Session {
+        replays,
+        current,
+        position,
+        steps,
+    };
+    println!("Type 'help' for a list of commands.");
+    session.show_position();
+
+    let mut stdout = io::stdout();
+    print!("(debug) ");
+    stdout.flush()?;
+
+    for line in input.lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or_default();
+        let argument = words.next();
+        let count = || argument.and_then(|n| n.parse().ok()).unwrap_or(1);
+
+        let step = session.position.step;
+        match command {
+            "" => {}
+            "b" | "back" => session.move_to(step.saturating_sub(count()))?,
+            "f" | "forward" => session.move_to(step.saturating_add(count()))?,
+            "p" | "print" => match argument {
+                Some(name) => session.show(Request::Print(name.to_string()))?,
+                None => println!("Usage: print NAME"),
+            },
+            "l" | "locals" => session.show(Request::Locals)?,
+            "q" | "quit" => break,
+            "h" | "help" => println!("{}", HELP),
+            _ => println!("Unknown command '{}'. Type 'help' for a list.", command),
+        }
+
+        print!("(debug) ");
+        stdout.flush()?;
+    }
+    println!();
+    Ok(())
+}
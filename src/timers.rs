@@ -0,0 +1,59 @@
+use std::time::{Duration, Instant};
+
+use crate::token::Callable;
+
+pub struct Timer {
+    pub id: usize,
+    pub deadline: Instant,
+    pub interval: Option<Duration>,
+    pub callback: Callable,
+}
+
+/// Callbacks scheduled by `setTimeout` and `setInterval`, run by
+/// `runEventLoop`.
+#[derive(Default)]
+pub struct Timers {
+    next_id: usize,
+    pending: Vec<Timer>,
+}
+
+impl Timers {
+    pub fn schedule(&mut self, callback: Callable, delay: Duration, repeat: bool) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.pending.push(Timer {
+            id,
+            deadline: Instant::now() + delay,
+            interval: repeat.then_some(delay),
+            callback,
+        });
+        id
+    }
+
+    pub fn cancel(&mut self, id: usize) -> bool {
+        let before = self.pending.len();
+        self.pending.retain(|timer| timer.id != id);
+        self.pending.len() != before
+    }
+
+    /// Removes the timer that is due first, scheduling its next run if it
+    /// repeats.
+    pub fn pop_next(&mut self) -> Option<Timer> {
+        let (index, _) = self
+            .pending
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, timer)| (timer.deadline, timer.id))?;
+        let timer = self.pending.remove(index);
+
+        if let Some(interval) = timer.interval {
+            self.pending.push(Timer {
+                id: timer.id,
+                deadline: timer.deadline + interval,
+                interval: timer.interval,
+                callback: timer.callback.clone(),
+            });
+        }
+        Some(timer)
+    }
+}
@@ -1,31 +1,137 @@
 use crate::ast::Stmt;
 use crate::environment::Environment;
 use crate::interpreter::Interpreter;
-use crate::lox_error::LoxError;
+use crate::lox_error::{LoxError, RuntimeError};
+use crate::messages;
+use crate::number_format::NumberFormat;
 use crate::token_type::TokenType;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
 use std::iter::zip;
+use std::rc::Rc;
+
+use serde::Serialize;
 
 #[derive(Clone)]
 pub enum Literal {
     None,
     Bool(bool),
     Callable(Callable),
+    Foreign(Rc<RefCell<dyn Foreign>>),
+    Instance(Rc<RefCell<LoxInstance>>),
+    List(Rc<RefCell<Vec<Literal>>>),
+    Map(Rc<RefCell<BTreeMap<String, Literal>>>),
     String(String),
     Number(f64),
 }
 
+/// A value implemented in Rust whose operations are exposed to Lox as
+/// methods, e.g. a file handle.
+pub trait Foreign {
+    fn type_name(&self) -> &'static str;
+
+    /// Returns the arity of `method`, or `None` if there is no such method.
+    fn method_arity(&self, method: &str) -> Option<usize>;
+
+    fn call_method(
+        &mut self,
+        interpreter: &mut Interpreter,
+        method: &str,
+        arguments: &[Literal],
+    ) -> Result<Literal, LoxError>;
+
+    /// Returns the value of the field `name`, read with `value.name`.
+    fn field(&self, _name: &str) -> Option<Literal> {
+        None
+    }
+
+    /// The error this value wraps, if it is a caught runtime error.
+    fn as_error(&self) -> Option<&RuntimeError> {
+        None
+    }
+}
+
+/// The pending result of an async native.
+#[cfg(feature = "async")]
+pub type NativeFuture =
+    std::pin::Pin<Box<dyn std::future::Future<Output = Result<Literal, LoxError>>>>;
+
+/// How many arguments a callable takes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    Exactly(usize),
+    AtLeast(usize),
+    /// From the first to the second, inclusive.
+    Between(usize, usize),
+}
+
+impl Arity {
+    pub fn accepts(self, count: usize) -> bool {
+        match self {
+            Arity::Exactly(n) => count == n,
+            Arity::AtLeast(min) => count >= min,
+            Arity::Between(min, max) => (min..=max).contains(&count),
+        }
+    }
+
+    /// The count with its noun, like "1 argument" or "at least 2 arguments".
+    pub fn describe(self) -> String {
+        let last = match self {
+            Arity::Exactly(n) | Arity::AtLeast(n) | Arity::Between(_, n) => n,
+        };
+        let noun = if last == 1 {
+            messages::text(messages::ARGUMENT_NOUN, &[])
+        } else {
+            messages::text(messages::ARGUMENTS_NOUN, &[])
+        };
+        match self {
+            Arity::Exactly(n) => format!("{} {}", n, noun),
+            Arity::AtLeast(min) => messages::text(messages::ARITY_AT_LEAST, &[&min, &noun]),
+            Arity::Between(min, max) => {
+                messages::text(messages::ARITY_BETWEEN, &[&min, &max, &noun])
+            }
+        }
+    }
+}
+
+impl From<usize> for Arity {
+    fn from(n: usize) -> Self {
+        Arity::Exactly(n)
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Arity::Exactly(n) => write!(f, "{}", n),
+            Arity::AtLeast(min) => write!(f, "at least {}", min),
+            Arity::Between(min, max) => write!(f, "{} to {}", min, max),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub enum Callable {
+    #[cfg(feature = "async")]
+    AsyncNativeFunction(AsyncNativeFunction),
+    BuiltinMethod(BuiltinMethod),
+    Class(Rc<LoxClass>),
+    ForeignMethod(ForeignMethod),
     Function(Function),
     NativeFunction(NativeFunction),
 }
 
 // Use trait? Breaks Clone on Literal
 impl Callable {
-    pub fn arity(&self) -> usize {
+    pub fn arity(&self) -> Arity {
         match self {
-            Callable::Function(f) => f.arity(),
+            #[cfg(feature = "async")]
+            Callable::AsyncNativeFunction(f) => f.arity,
+            Callable::BuiltinMethod(m) => Arity::Exactly(m.arity),
+            Callable::Class(c) => Arity::Exactly(c.arity()),
+            Callable::ForeignMethod(m) => Arity::Exactly(m.arity),
+            Callable::Function(f) => Arity::Exactly(f.arity()),
             Callable::NativeFunction(f) => f.arity,
         }
     }
@@ -36,16 +142,72 @@ impl Callable {
         arguments: &[Literal],
     ) -> Result<Literal, LoxError> {
         match self {
+            #[cfg(feature = "async")]
+            Callable::AsyncNativeFunction(f) => {
+                // Replays skip the work entirely, including any waiting
+                let mut tape = std::mem::take(&mut interpreter.tape);
+                let result = tape.observe("async native", || {
+                    let future = (f.closure)(interpreter, arguments)?;
+                    interpreter.block_on(future)
+                });
+                interpreter.tape = tape;
+                result
+            }
+            Callable::BuiltinMethod(m) => (m.closure)(interpreter, &m.receiver, arguments),
+            Callable::Class(c) => LoxClass::call(c, interpreter, arguments),
+            Callable::ForeignMethod(m) => m.call(interpreter, arguments),
             Callable::Function(f) => f.call(interpreter, arguments),
             Callable::NativeFunction(f) => f.call(interpreter, arguments),
         }
     }
+
+    /// The name the callable was declared with, for stack traces.
+    pub fn name(&self) -> &str {
+        match self {
+            Callable::Class(c) => &c.name,
+            Callable::ForeignMethod(m) => &m.name,
+            Callable::Function(f) => f.name.as_deref().unwrap_or("<lambda>"),
+            _ => "<native>",
+        }
+    }
+
+    /// The signature and documentation shown by `help` and `:doc`.
+    pub fn help(&self) -> String {
+        let (mut text, doc) = match self {
+            Callable::Class(c) => {
+                let params = c
+                    .initializer()
+                    .map_or(String::new(), |init| init.param_list());
+                (format!("class {}({})", c.name, params), &c.doc)
+            }
+            Callable::Function(f) => {
+                let name = f.name.as_deref().unwrap_or("");
+                (format!("fun {}({})", name, f.param_list()), &f.doc)
+            }
+            _ => return format!("Built-in function taking {}.", self.arity().describe()),
+        };
+        for line in doc.iter().flat_map(|doc| doc.lines()) {
+            text.push_str("\n    ");
+            text.push_str(line);
+        }
+        text
+    }
 }
 
+pub type NativeFn = fn(&mut Interpreter, &[Literal]) -> Result<Literal, LoxError>;
+
+/// What a native runs when called: a `NativeFn`, or a closure that may
+/// capture state of its own.
+pub type NativeClosure = Rc<dyn Fn(&mut Interpreter, &[Literal]) -> Result<Literal, LoxError>>;
+
+/// A native implementing a method of a built-in type. It is passed the
+/// value the method was read from.
+pub type MethodFn = fn(&mut Interpreter, &Literal, &[Literal]) -> Result<Literal, LoxError>;
+
 #[derive(Clone)]
 pub struct NativeFunction {
-    pub arity: usize,
-    pub closure: fn(&mut Interpreter, &[Literal]) -> Result<Literal, LoxError>,
+    pub arity: Arity,
+    pub closure: NativeClosure,
 }
 
 impl NativeFunction {
@@ -58,11 +220,53 @@ impl NativeFunction {
     }
 }
 
+/// A native that starts its work and returns a future for the result, which
+/// the interpreter drives on a tokio runtime.
+#[cfg(feature = "async")]
+#[derive(Clone)]
+pub struct AsyncNativeFunction {
+    pub arity: Arity,
+    pub closure: fn(&mut Interpreter, &[Literal]) -> Result<NativeFuture, LoxError>,
+}
+
+/// A method read from a string, number, list or map, e.g. `xs.push`.
+#[derive(Clone)]
+pub struct BuiltinMethod {
+    pub receiver: Box<Literal>,
+    pub arity: usize,
+    pub closure: MethodFn,
+}
+
+#[derive(Clone)]
+pub struct ForeignMethod {
+    pub receiver: Rc<RefCell<dyn Foreign>>,
+    pub name: String,
+    pub arity: usize,
+}
+
+impl ForeignMethod {
+    pub fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: &[Literal],
+    ) -> Result<Literal, LoxError> {
+        self.receiver
+            .borrow_mut()
+            .call_method(interpreter, &self.name, arguments)
+    }
+}
+
 #[derive(Clone)]
 pub struct Function {
+    /// `None` for lambdas.
+    pub name: Option<String>,
+    /// The `///` comment before the declaration.
+    pub doc: Option<Rc<str>>,
     pub closure: Environment,
     pub params: Vec<Token>,
     pub body: Vec<Stmt>,
+    /// Whether this is a class's `init` method, which returns `this`.
+    pub initializer: bool,
 }
 
 impl Function {
@@ -71,34 +275,130 @@ impl Function {
         interpreter: &mut Interpreter,
         arguments: &[Literal],
     ) -> Result<Literal, LoxError> {
+        interpreter.count_environment();
         let mut env = Environment::from_env(&self.closure);
         for (param, arg) in zip(&self.params, arguments) {
             env.define(param, arg)
         }
 
-        match interpreter.execute_block(&self.body, env) {
+        let result = interpreter.execute_block(&self.body, env);
+        interpreter.note_return(self, result.is_ok() && !self.initializer);
+        let result = match result {
             Err(e) => match e {
                 LoxError::Return(r) => Ok(r.value),
                 _ => Err(e),
             },
             _ => Ok(Literal::None),
+        };
+        if self.initializer {
+            result?;
+            return self.closure.get_at(0, &this_token());
         }
+        result
     }
 
     pub fn arity(&self) -> usize {
         self.params.len()
     }
+
+    /// The parameter names, comma-separated.
+    fn param_list(&self) -> String {
+        let params: Vec<&str> = self.params.iter().map(|p| p.lexeme.as_str()).collect();
+        params.join(", ")
+    }
+
+    /// This method with `this` bound to `instance`.
+    pub fn bind(&self, instance: &Rc<RefCell<LoxInstance>>) -> Function {
+        let mut closure = Environment::from_env(&self.closure);
+        closure.define(&this_token(), &Literal::Instance(Rc::clone(instance)));
+        Function {
+            closure,
+            ..self.clone()
+        }
+    }
+}
+
+fn this_token() -> Token {
+    Token::new(TokenType::This, "this", None, 0)
+}
+
+/// A class declared with `class`. Calling it creates an instance and runs
+/// the `init` method on it, if there is one.
+pub struct LoxClass {
+    pub name: String,
+    /// The `///` comment before the declaration.
+    pub doc: Option<Rc<str>>,
+    pub superclass: Option<Rc<LoxClass>>,
+    pub methods: HashMap<String, Rc<Function>>,
+}
+
+impl LoxClass {
+    /// Finds `name` among this class's methods, or else its superclasses'.
+    pub fn find_method(&self, name: &str) -> Option<&Rc<Function>> {
+        match self.methods.get(name) {
+            Some(method) => Some(method),
+            None => self.superclass.as_ref()?.find_method(name),
+        }
+    }
+
+    fn initializer(&self) -> Option<&Rc<Function>> {
+        self.find_method("init")
+    }
+
+    pub fn arity(&self) -> usize {
+        self.initializer().map_or(0, |init| init.arity())
+    }
+
+    pub fn call(
+        class: &Rc<LoxClass>,
+        interpreter: &mut Interpreter,
+        arguments: &[Literal],
+    ) -> Result<Literal, LoxError> {
+        let instance = Rc::new(RefCell::new(LoxInstance {
+            class: Rc::clone(class),
+            fields: HashMap::new(),
+        }));
+        if let Some(initializer) = class.initializer() {
+            initializer.bind(&instance).call(interpreter, arguments)?;
+        }
+        Ok(Literal::Instance(instance))
+    }
+}
+
+/// An instance of a `LoxClass`, holding its own fields.
+pub struct LoxInstance {
+    pub class: Rc<LoxClass>,
+    fields: HashMap<String, Literal>,
+}
+
+impl LoxInstance {
+    /// Reads the field `name`, or else binds the class's method `name` to
+    /// `instance`.
+    pub fn get(instance: &Rc<RefCell<LoxInstance>>, name: &str) -> Option<Literal> {
+        let this = instance.borrow();
+        if let Some(value) = this.fields.get(name) {
+            return Some(value.clone());
+        }
+        let method = this.class.find_method(name)?;
+        Some(Literal::Callable(Callable::Function(method.bind(instance))))
+    }
+
+    pub fn set(&mut self, name: &str, value: Literal) {
+        self.fields.insert(name.to_string(), value);
+    }
+
+    pub fn field(&self, name: &str) -> Option<&Literal> {
+        self.fields.get(name)
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = (&String, &Literal)> {
+        self.fields.iter()
+    }
 }
 
 impl fmt::Display for Literal {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Literal::None => write!(f, "nil"),
-            Literal::Bool(b) => write!(f, "{}", b),
-            Literal::Callable(c) => write!(f, "callable({})", c.arity()),
-            Literal::String(t) => write!(f, "{}", t),
-            Literal::Number(n) => write!(f, "{}", n),
-        }
+        write!(f, "{}", NumberFormat::DEFAULT.display(self))
     }
 }
 
@@ -108,6 +408,21 @@ pub struct Token {
     pub lexeme: String,
     pub literal: Option<Literal>,
     pub line: usize,
+    /// The column the token starts at, counting characters from 1, or 0
+    /// for tokens the parser or interpreter made up.
+    pub column: usize,
+    /// The byte offset of the token in the source.
+    pub offset: usize,
+}
+
+/// Where something is in the source: the line and column it starts at and
+/// its byte range. A column of 0 means only the line is known.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub start: usize,
+    pub end: usize,
 }
 
 impl Token {
@@ -117,6 +432,37 @@ impl Token {
             lexeme: lexeme.to_string(),
             literal,
             line,
+            column: 0,
+            offset: 0,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        Span {
+            line: self.line,
+            column: self.column,
+            start: self.offset,
+            end: self.offset + self.lexeme.len(),
+        }
+    }
+}
+
+impl Span {
+    /// A span that only knows its line.
+    pub fn line(line: usize) -> Self {
+        Self {
+            line,
+            ..Default::default()
+        }
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.column == 0 {
+            write!(f, "line {}", self.line)
+        } else {
+            write!(f, "line {}, col {}", self.line, self.column)
         }
     }
 }
@@ -7,6 +7,9 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
+    Colon,
     Comma,
     Dot,
     Minus,
@@ -14,6 +17,8 @@ pub enum TokenType {
     Semicolon,
     Slash,
     Star,
+    Percent,
+    Question,
 
     // One or two character tokens.
     Bang,
@@ -24,27 +29,45 @@ pub enum TokenType {
     GreaterEqual,
     Less,
     LessEqual,
+    Arrow,
+    ThinArrow,
+    Ellipsis,
+    Pipe,
+    StarStar,
+    PlusPlus,
+    MinusMinus,
+    MinusEqual,
+    PlusEqual,
+    SlashEqual,
+    StarEqual,
 
     // Literals.
     Identifier,
     String,
     Number,
+    DocComment,
 
     // Keywords.
     And,
+    Break,
+    Catch,
     Class,
+    Continue,
     Else,
     False,
     Fun,
     For,
     If,
+    In,
     Nil,
     Or,
     Print,
     Return,
     Super,
     This,
+    Throw,
     True,
+    Try,
     Var,
     While,
 
@@ -0,0 +1,498 @@
+//! Checks optional type annotations (`var x: number`, `fun f(a: string) ->
+//! bool`) against the types that can be told from the code without running
+//! it. Anything unannotated, or whose type depends on what happens at
+//! runtime, is `any` and matches everything, so unannotated programs never
+//! get a warning.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::ast::{Expr, Signature, Stmt};
+use crate::lint::{Lint, LintConfig, Warning};
+use crate::messages;
+use crate::token::{Literal, Token};
+use crate::token_type::TokenType;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Type {
+    Any,
+    Bool,
+    Function,
+    List,
+    Map,
+    Nil,
+    Number,
+    String,
+}
+
+impl Type {
+    const ALL: [Type; 8] = [
+        Type::Any,
+        Type::Bool,
+        Type::Function,
+        Type::List,
+        Type::Map,
+        Type::Nil,
+        Type::Number,
+        Type::String,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Type::Any => "any",
+            Type::Bool => "bool",
+            Type::Function => "function",
+            Type::List => "list",
+            Type::Map => "map",
+            Type::Nil => "nil",
+            Type::Number => "number",
+            Type::String => "string",
+        }
+    }
+
+    fn of(value: &Literal) -> Self {
+        match value {
+            Literal::None => Type::Nil,
+            Literal::Bool(_) => Type::Bool,
+            Literal::Callable(_) => Type::Function,
+            Literal::Foreign(_) | Literal::Instance(_) => Type::Any,
+            Literal::List(_) => Type::List,
+            Literal::Map(_) => Type::Map,
+            Literal::String(_) => Type::String,
+            Literal::Number(_) => Type::Number,
+        }
+    }
+
+    /// Whether a value of type `actual` may be used where `self` is expected.
+    fn accepts(self, actual: Type) -> bool {
+        self == Type::Any || actual == Type::Any || self == actual
+    }
+
+    /// The type both sides of e.g. an `or` share, if they agree.
+    fn join(self, other: Type) -> Type {
+        if self == other {
+            self
+        } else {
+            Type::Any
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// The annotated parameter and return types of a function.
+struct FunctionType {
+    params: Vec<Type>,
+    returns: Type,
+}
+
+struct Binding {
+    type_: Type,
+    /// Set for names bound by a function declaration, so calls through
+    /// them can be checked.
+    function: Option<Rc<FunctionType>>,
+}
+
+struct Checker<'a> {
+    config: &'a LintConfig,
+    scopes: Vec<HashMap<String, Binding>>,
+    /// The name and return type of each function being checked, innermost
+    /// last.
+    functions: Vec<(String, Type)>,
+    warnings: Vec<Warning>,
+}
+
+/// Reports values whose type contradicts an annotation, and annotations
+/// naming unknown types, as `type-mismatch` warnings.
+pub fn check(statements: &[Stmt], config: &LintConfig) -> Vec<Warning> {
+    let mut checker = Checker {
+        config,
+        scopes: vec![HashMap::new()],
+        functions: Vec::new(),
+        warnings: Vec::new(),
+    };
+    checker.statements(statements);
+    checker.warnings.sort_by_key(|w| w.line);
+    checker.warnings
+}
+
+impl Checker<'_> {
+    fn warn(&mut self, token: &Token, message: String, help: String) {
+        let warning = Warning::new(self.config, Lint::TypeMismatch, token, message, help);
+        self.warnings.extend(warning);
+    }
+
+    fn mismatch(&mut self, token: &Token, message: messages::Message, args: &[&dyn fmt::Display]) {
+        let message = messages::text(message, args);
+        let help = messages::text(messages::TYPE_MISMATCH_HELP, &[]);
+        self.warn(token, message, help);
+    }
+
+    fn resolve(&mut self, annotation: &Token) -> Type {
+        if annotation.type_ == TokenType::Nil {
+            return Type::Nil;
+        }
+        match Type::ALL
+            .into_iter()
+            .find(|t| t.name() == annotation.lexeme)
+        {
+            Some(type_) => type_,
+            None => {
+                let names: Vec<_> = Type::ALL.iter().map(|t| t.name()).collect();
+                let message = messages::text(messages::UNKNOWN_TYPE, &[&annotation.lexeme]);
+                let help = messages::text(messages::UNKNOWN_TYPE_HELP, &[&names.join(", ")]);
+                self.warn(annotation, message, help);
+                Type::Any
+            }
+        }
+    }
+
+    fn function_type(&mut self, signature: &Signature) -> FunctionType {
+        let params = signature
+            .params
+            .iter()
+            .map(|param| match param {
+                Some(annotation) => self.resolve(annotation),
+                None => Type::Any,
+            })
+            .collect();
+        let returns = match &signature.returns {
+            Some(annotation) => self.resolve(annotation),
+            None => Type::Any,
+        };
+        FunctionType { params, returns }
+    }
+
+    fn declare(&mut self, name: &Token, binding: Binding) {
+        let scope = self.scopes.last_mut().expect("Global scope exists");
+        scope.insert(name.lexeme.clone(), binding);
+    }
+
+    fn lookup(&self, name: &Token) -> Option<&Binding> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&name.lexeme))
+    }
+
+    fn scoped(&mut self, check: impl FnOnce(&mut Self)) {
+        self.scopes.push(HashMap::new());
+        check(self);
+        self.scopes.pop();
+    }
+
+    fn statements(&mut self, statements: &[Stmt]) {
+        // Functions can be called before the statement declaring them
+        for statement in statements {
+            if let Stmt::Function {
+                name, signature, ..
+            } = statement
+            {
+                let function = Rc::new(self.function_type(signature));
+                let binding = Binding {
+                    type_: Type::Any,
+                    function: Some(function),
+                };
+                self.declare(name, binding);
+            }
+        }
+
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn function(&mut self, name: &str, params: &[Token], function: &FunctionType, body: &[Stmt]) {
+        self.functions.push((name.to_string(), function.returns));
+        self.scoped(|checker| {
+            for (i, param) in params.iter().enumerate() {
+                let type_ = function.params.get(i).copied().unwrap_or(Type::Any);
+                let binding = Binding {
+                    type_,
+                    function: None,
+                };
+                checker.declare(param, binding);
+            }
+            checker.statements(body);
+        });
+        self.functions.pop();
+    }
+
+    fn statement(&mut self, statement: &Stmt) {
+        match statement {
+            Stmt::Block { statements } => self.scoped(|checker| checker.statements(statements)),
+            Stmt::Break { .. } | Stmt::Continue { .. } => {}
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                ..
+            } => {
+                if let Some(superclass) = superclass {
+                    self.infer(superclass);
+                }
+                let binding = Binding {
+                    type_: Type::Function,
+                    function: None,
+                };
+                self.declare(name, binding);
+                for method in methods {
+                    if let Stmt::Function {
+                        name,
+                        params,
+                        signature,
+                        body,
+                        ..
+                    } = method
+                    {
+                        let function = self.function_type(signature);
+                        self.function(&name.lexeme, params, &function, body);
+                    }
+                }
+            }
+            Stmt::Expression { expression }
+            | Stmt::Print { expression, .. }
+            | Stmt::Throw {
+                value: expression, ..
+            } => {
+                self.infer(expression);
+            }
+            Stmt::Function {
+                name, params, body, ..
+            } => {
+                let function = self
+                    .lookup(name)
+                    .and_then(|binding| binding.function.clone())
+                    .expect("Functions are declared before their scope is checked");
+                self.function(&name.lexeme, params, &function, body);
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                self.infer(condition);
+                self.statement(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch);
+                }
+            }
+            Stmt::Return { keyword, value } => {
+                let actual = match value {
+                    Some(value) => self.infer(value),
+                    None => Type::Nil,
+                };
+                if let Some((name, expected)) = self.functions.last().cloned() {
+                    if !expected.accepts(actual) {
+                        let args: [&dyn fmt::Display; 3] = [&name, &expected, &actual];
+                        self.mismatch(keyword, messages::RETURN_TYPE_MISMATCH, &args);
+                    }
+                }
+            }
+            Stmt::Try {
+                body,
+                name,
+                handler,
+            } => {
+                self.scoped(|checker| checker.statements(body));
+                self.scoped(|checker| {
+                    let binding = Binding {
+                        type_: Type::Any,
+                        function: None,
+                    };
+                    checker.declare(name, binding);
+                    checker.statements(handler);
+                });
+            }
+            Stmt::Var {
+                name,
+                annotation,
+                initializer,
+            } => {
+                let declared = match annotation {
+                    Some(annotation) => self.resolve(annotation),
+                    None => Type::Any,
+                };
+                if let Some(initializer) = initializer {
+                    let actual = self.infer(initializer);
+                    if !declared.accepts(actual) {
+                        let args: [&dyn fmt::Display; 3] = [&name.lexeme, &declared, &actual];
+                        self.mismatch(name, messages::VARIABLE_TYPE_MISMATCH, &args);
+                    }
+                }
+                let binding = Binding {
+                    type_: declared,
+                    function: None,
+                };
+                self.declare(name, binding);
+            }
+            Stmt::While {
+                condition,
+                body,
+                increment,
+                ..
+            } => {
+                self.infer(condition);
+                self.statement(body);
+                if let Some(increment) = increment {
+                    self.infer(increment);
+                }
+            }
+        }
+    }
+
+    fn call(&mut self, callee: &Expr, paren: &Token, arguments: &[Expr]) -> Type {
+        let actual: Vec<_> = arguments.iter().map(|a| self.infer(a)).collect();
+        let Expr::Variable { name, .. } = callee else {
+            self.infer(callee);
+            return Type::Any;
+        };
+        let Some(function) = self.lookup(name).and_then(|b| b.function.clone()) else {
+            return Type::Any;
+        };
+
+        // A spread shifts which argument lands in which parameter
+        if !arguments.iter().any(|a| matches!(a, Expr::Spread { .. })) {
+            for (i, (expected, actual)) in function.params.iter().zip(actual).enumerate() {
+                if !expected.accepts(actual) {
+                    let args: [&dyn fmt::Display; 4] = [&(i + 1), &name.lexeme, expected, &actual];
+                    self.mismatch(paren, messages::ARGUMENT_TYPE_MISMATCH, &args);
+                }
+            }
+        }
+        function.returns
+    }
+
+    /// Returns the type `expression` evaluates to, checking the
+    /// assignments and calls inside it along the way.
+    fn infer(&mut self, expression: &Expr) -> Type {
+        match expression {
+            Expr::Assign { name, value, .. } => {
+                let actual = self.infer(value);
+                let declared = self.lookup(name).map_or(Type::Any, |b| b.type_);
+                if !declared.accepts(actual) {
+                    let args: [&dyn fmt::Display; 3] = [&name.lexeme, &declared, &actual];
+                    self.mismatch(name, messages::VARIABLE_TYPE_MISMATCH, &args);
+                }
+                actual
+            }
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                let left = self.infer(left);
+                let right = self.infer(right);
+                match operator.type_ {
+                    TokenType::Minus
+                    | TokenType::Star
+                    | TokenType::Slash
+                    | TokenType::Percent
+                    | TokenType::StarStar => Type::Number,
+                    TokenType::Plus => match left.join(right) {
+                        type_ @ (Type::Number | Type::String) => type_,
+                        _ => Type::Any,
+                    },
+                    TokenType::Greater
+                    | TokenType::GreaterEqual
+                    | TokenType::Less
+                    | TokenType::LessEqual
+                    | TokenType::EqualEqual
+                    | TokenType::BangEqual
+                    | TokenType::In => Type::Bool,
+                    _ => Type::Any,
+                }
+            }
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+            } => self.call(callee, paren, arguments),
+            Expr::Get { object, .. } => {
+                self.infer(object);
+                Type::Any
+            }
+            Expr::Grouping { expression } => self.infer(expression),
+            Expr::Index { object, index, .. } => {
+                self.infer(object);
+                self.infer(index);
+                Type::Any
+            }
+            Expr::Lambda {
+                params,
+                signature,
+                body,
+                ..
+            } => {
+                let function = self.function_type(signature);
+                self.function("<lambda>", params, &function, body);
+                Type::Function
+            }
+            Expr::List { elements } => {
+                for element in elements {
+                    self.infer(element);
+                }
+                Type::List
+            }
+            Expr::Literal { value } => Type::of(value),
+            Expr::Map { entries, .. } => {
+                for (key, value) in entries {
+                    self.infer(key);
+                    self.infer(value);
+                }
+                Type::Map
+            }
+            Expr::Logical { left, right, .. } => {
+                let left = self.infer(left);
+                left.join(self.infer(right))
+            }
+            Expr::Ternary {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.infer(condition);
+                let then_type = self.infer(then_branch);
+                then_type.join(self.infer(else_branch))
+            }
+            Expr::Set { object, value, .. } => {
+                self.infer(object);
+                self.infer(value)
+            }
+            Expr::SetIndex {
+                object,
+                index,
+                value,
+                ..
+            } => {
+                self.infer(object);
+                self.infer(index);
+                self.infer(value)
+            }
+            Expr::Postfix { target, .. } => {
+                self.infer(target);
+                Type::Number
+            }
+            Expr::Spread { expression, .. } => {
+                self.infer(expression);
+                Type::Any
+            }
+            Expr::Unary { operator, right } => {
+                self.infer(right);
+                match operator.type_ {
+                    TokenType::Minus => Type::Number,
+                    TokenType::Bang => Type::Bool,
+                    _ => Type::Any,
+                }
+            }
+            Expr::Super { .. } | Expr::This { .. } => Type::Any,
+            Expr::Variable { name, .. } => self.lookup(name).map_or(Type::Any, |b| b.type_),
+        }
+    }
+}
@@ -0,0 +1,193 @@
+//! Operations on Lox values with the same semantics the interpreter uses,
+//! for natives that need to combine or compare their arguments.
+
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+use crate::lox_error::NativeError;
+use crate::messages;
+use crate::token::{Callable, Literal};
+
+/// `nil` and `false` are falsey, everything else is truthy.
+pub fn is_truthy(value: &Literal) -> bool {
+    match value {
+        Literal::None => false,
+        Literal::Bool(b) => *b,
+        _ => true,
+    }
+}
+
+/// The name of `value`'s type, as shown when inspecting variables.
+pub fn type_name(value: &Literal) -> &'static str {
+    match value {
+        Literal::None => "nil",
+        Literal::Bool(_) => "bool",
+        Literal::Callable(Callable::Class(_)) => "class",
+        Literal::Callable(_) => "function",
+        Literal::Foreign(o) => o.borrow().type_name(),
+        Literal::Instance(_) => "instance",
+        Literal::List(_) => "list",
+        Literal::Map(_) => "map",
+        Literal::String(_) => "string",
+        Literal::Number(_) => "number",
+    }
+}
+
+/// Lox `==`: primitives compare by value, lists, maps, classes, instances
+/// and foreign values by identity, and functions are never equal.
+pub fn lox_equals(left: &Literal, right: &Literal) -> bool {
+    match (left, right) {
+        (Literal::None, Literal::None) => true,
+        (Literal::Bool(left), Literal::Bool(right)) => left == right,
+        (Literal::Number(left), Literal::Number(right)) => left == right,
+        (Literal::String(left), Literal::String(right)) => left == right,
+        (Literal::List(left), Literal::List(right)) => Rc::ptr_eq(left, right),
+        (Literal::Map(left), Literal::Map(right)) => Rc::ptr_eq(left, right),
+        (Literal::Foreign(left), Literal::Foreign(right)) => Rc::ptr_eq(left, right),
+        (Literal::Instance(left), Literal::Instance(right)) => Rc::ptr_eq(left, right),
+        (Literal::Callable(Callable::Class(left)), Literal::Callable(Callable::Class(right))) => {
+            Rc::ptr_eq(left, right)
+        }
+        (_, _) => false,
+    }
+}
+
+impl Literal {
+    /// Structural equality: lists and maps are equal when they hold equal
+    /// elements, compared the same way, rather than only when they are the
+    /// same list or map. Everything else compares like `==`. A list or map
+    /// that contains itself is handled by assuming a pair of collections
+    /// already being compared is equal.
+    pub fn deep_equals(&self, other: &Literal) -> bool {
+        deep_equals(self, other, &mut Vec::new())
+    }
+}
+
+/// `comparing` holds the pairs of collections being compared further up.
+fn deep_equals(left: &Literal, right: &Literal, comparing: &mut Vec<(usize, usize)>) -> bool {
+    let pair = match (left, right) {
+        (Literal::List(l), Literal::List(r)) => (Rc::as_ptr(l) as usize, Rc::as_ptr(r) as usize),
+        (Literal::Map(l), Literal::Map(r)) => (Rc::as_ptr(l) as usize, Rc::as_ptr(r) as usize),
+        _ => return lox_equals(left, right),
+    };
+    if pair.0 == pair.1 || comparing.contains(&pair) {
+        return true;
+    }
+
+    comparing.push(pair);
+    let equal = match (left, right) {
+        (Literal::List(left), Literal::List(right)) => {
+            let (left, right) = (left.borrow(), right.borrow());
+            left.len() == right.len()
+                && left
+                    .iter()
+                    .zip(right.iter())
+                    .all(|(l, r)| deep_equals(l, r, comparing))
+        }
+        (Literal::Map(left), Literal::Map(right)) => {
+            let (left, right) = (left.borrow(), right.borrow());
+            left.len() == right.len()
+                && left
+                    .iter()
+                    .zip(right.iter())
+                    .all(|((lk, lv), (rk, rv))| lk == rk && deep_equals(lv, rv, comparing))
+        }
+        _ => unreachable!(),
+    };
+    comparing.pop();
+    equal
+}
+
+/// Lox `+`: adds two numbers or concatenates two strings.
+pub fn add(left: &Literal, right: &Literal) -> Result<Literal, NativeError> {
+    match (left, right) {
+        (Literal::Number(left), Literal::Number(right)) => Ok(Literal::Number(left + right)),
+        (Literal::String(left), Literal::String(right)) => {
+            Ok(Literal::String(format!("{}{}", left, right)))
+        }
+        _ => Err(NativeError::from_message(
+            messages::OPERANDS_MUST_BE_NUMBERS_OR_STRINGS,
+            &[],
+        )),
+    }
+}
+
+/// Orders two numbers as `<`, `<=`, `>` and `>=` do. Returns `None` when
+/// either is NaN, for which every comparison is false.
+pub fn compare(left: &Literal, right: &Literal) -> Result<Option<Ordering>, NativeError> {
+    match (left, right) {
+        (Literal::Number(left), Literal::Number(right)) => Ok(left.partial_cmp(right)),
+        _ => Err(NativeError::from_message(
+            messages::OPERANDS_MUST_BE_NUMBERS,
+            &[],
+        )),
+    }
+}
+
+/// The position in a list of length `len` that `index` refers to.
+fn list_position(index: &Literal, len: usize) -> Result<usize, NativeError> {
+    match index {
+        Literal::Number(n) if *n >= 0.0 && n.fract() == 0.0 => {
+            if *n < len as f64 {
+                Ok(*n as usize)
+            } else {
+                Err(NativeError::from_message(
+                    messages::LIST_INDEX_OUT_OF_RANGE,
+                    &[n, &len],
+                ))
+            }
+        }
+        _ => Err(NativeError::from_message(
+            messages::LIST_INDEX_NOT_INTEGER,
+            &[],
+        )),
+    }
+}
+
+/// Looks up `object[index]` in a list or map.
+pub fn index(object: &Literal, index: &Literal) -> Result<Literal, NativeError> {
+    match (object, index) {
+        (Literal::List(list), index) => {
+            let list = list.borrow();
+            Ok(list[list_position(index, list.len())?].clone())
+        }
+        (Literal::Map(map), Literal::String(key)) => map
+            .borrow()
+            .get(key)
+            .cloned()
+            .ok_or_else(|| NativeError::from_message(messages::UNDEFINED_KEY, &[key])),
+        (Literal::Map(_), _) => Err(NativeError::from_message(
+            messages::MAP_KEYS_MUST_BE_STRINGS,
+            &[],
+        )),
+        _ => Err(NativeError::from_message(
+            messages::ONLY_INDEX_COLLECTIONS,
+            &[],
+        )),
+    }
+}
+
+/// Stores `value` at `object[index]`. Lists only allow replacing existing
+/// elements, while maps gain a key if it is new.
+pub fn set_index(object: &Literal, index: &Literal, value: Literal) -> Result<(), NativeError> {
+    match (object, index) {
+        (Literal::List(list), index) => {
+            let mut list = list.borrow_mut();
+            let position = list_position(index, list.len())?;
+            list[position] = value;
+            Ok(())
+        }
+        (Literal::Map(map), Literal::String(key)) => {
+            map.borrow_mut().insert(key.clone(), value);
+            Ok(())
+        }
+        (Literal::Map(_), _) => Err(NativeError::from_message(
+            messages::MAP_KEYS_MUST_BE_STRINGS,
+            &[],
+        )),
+        _ => Err(NativeError::from_message(
+            messages::ONLY_INDEX_COLLECTIONS,
+            &[],
+        )),
+    }
+}
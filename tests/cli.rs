@@ -0,0 +1,27 @@
+//! Command line behavior the golden programs can't cover, because it needs
+//! arguments other than a script that exists.
+
+use std::process::{Command, Output};
+
+fn lox(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_lox"))
+        .args(args)
+        .output()
+        .expect("Failed to run lox")
+}
+
+#[test]
+fn missing_script() {
+    for args in [
+        &["/nonexistent/missing.lox"][..],
+        &["run", "/nonexistent/missing.lox"],
+    ] {
+        let output = lox(args);
+        assert_eq!(output.status.code(), Some(66));
+        assert_eq!(
+            String::from_utf8_lossy(&output.stderr),
+            "Could not read file '/nonexistent/missing.lox': No such file or directory (os error 2).\n"
+        );
+        assert!(output.stdout.is_empty());
+    }
+}
@@ -0,0 +1,217 @@
+//! Golden-file tests.
+//!
+//! Every `tests/programs/**/*.lox` script is run as `lox <script>` would run
+//! it and its stdout, stderr and exit code are compared against the
+//! `.expected` file next to it. Run with `LOX_BLESS=1 cargo test --test golden`
+//! to regenerate the expected files after an intentional change in behavior.
+//!
+//! A program with a leading `// lox-args: <flags>` line is run through the
+//! `lox` binary with those extra command line flags, and one with a
+//! `// lox-script-args: <args>` line is given those arguments after `--`.
+//! Everything else runs in this process. One with a `// lox-features: <names>`
+//! line is skipped unless all of those cargo features are enabled. Each
+//! `// lox-stdin: <text>` line is fed to the program as a line of input.
+//! Scripts under a `support` directory are only used by other programs and
+//! aren't run.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+
+use lox::interpreter::STACK_SIZE;
+use lox::lint::Level;
+use lox::lox::IsolationMode;
+use lox::{Interpreter, Lox, LoxError, SharedBuffer};
+
+fn collect_programs(dir: &Path, programs: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).expect("Failed to read program directory") {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            if !path.ends_with("support") {
+                collect_programs(&path, programs);
+            }
+        } else if path.extension().is_some_and(|ext| ext == "lox") {
+            programs.push(path);
+        }
+    }
+}
+
+/// Returns the values of every `// <name>:` header among the leading comments.
+fn headers(path: &Path, name: &str) -> Vec<String> {
+    let source = fs::read_to_string(path).expect("Failed to read program");
+    let prefix = format!("// {}:", name);
+    source
+        .lines()
+        .take_while(|line| line.starts_with("// lox-"))
+        .filter_map(|line| line.strip_prefix(&prefix).map(String::from))
+        .collect()
+}
+
+fn header(path: &Path, name: &str) -> Option<String> {
+    headers(path, name).into_iter().next()
+}
+
+fn stdin(path: &Path) -> String {
+    headers(path, "lox-stdin")
+        .iter()
+        .map(|line| format!("{}\n", line.trim_start()))
+        .collect()
+}
+
+fn extra_args(path: &Path, name: &str) -> Vec<String> {
+    match header(path, name) {
+        Some(args) => args.split_whitespace().map(String::from).collect(),
+        None => Vec::new(),
+    }
+}
+
+fn feature_enabled(name: &str) -> bool {
+    (name == "async" && cfg!(feature = "async"))
+        || (name == "config-formats" && cfg!(feature = "config-formats"))
+        || (name == "crypto" && cfg!(feature = "crypto"))
+        || (name == "jupyter" && cfg!(feature = "jupyter"))
+}
+
+fn required_features_enabled(path: &Path) -> bool {
+    match header(path, "lox-features") {
+        Some(features) => features.split_whitespace().all(feature_enabled),
+        None => true,
+    }
+}
+
+fn report(stdout: &str, stderr: &str, exit: &str) -> String {
+    format!(
+        "--- stdout\n{}--- stderr\n{}--- exit\n{}\n",
+        stdout, stderr, exit
+    )
+}
+
+/// Whether `path` has to run through the binary, because it asks for
+/// command line flags or script arguments.
+fn needs_binary(path: &Path) -> bool {
+    header(path, "lox-args").is_some() || header(path, "lox-script-args").is_some()
+}
+
+fn run_binary(path: &Path) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lox"))
+        .args(extra_args(path, "lox-args"))
+        .arg(path)
+        .arg("--")
+        .args(extra_args(path, "lox-script-args"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to run lox");
+    // A program that doesn't read its input may exit before it is written
+    let mut input = child.stdin.take().expect("Stdin is piped");
+    let _ = input.write_all(stdin(path).as_bytes());
+    drop(input);
+    let output = child.wait_with_output().expect("Failed to run lox");
+
+    let exit = match output.status.code() {
+        Some(code) => code.to_string(),
+        None => "signal".to_string(),
+    };
+
+    report(
+        &String::from_utf8_lossy(&output.stdout),
+        &String::from_utf8_lossy(&output.stderr),
+        &exit,
+    )
+}
+
+/// The status `lox` exits with after reporting `error`.
+fn exit_code(error: &LoxError) -> u8 {
+    match error {
+        LoxError::Scanner(_) | LoxError::Parser(_) | LoxError::Type(_) => 65,
+        LoxError::Io(_) => 66,
+        LoxError::Exit(e) => e.code,
+        _ => 70,
+    }
+}
+
+/// Runs `path` in an interpreter of our own, set up as the binary sets up
+/// one for a plain `lox <script>`.
+fn run_in_process(path: &Path) -> String {
+    let path = path.to_path_buf();
+    let run = move || {
+        let stdout = SharedBuffer::default();
+        let stderr = SharedBuffer::default();
+        let mut interpreter = Interpreter::with_writer(stdout.clone());
+        interpreter.set_input(io::Cursor::new(stdin(&path)));
+        let mut lox = Lox::with_interpreter(interpreter);
+        lox.set_error_writer(stderr.clone());
+        lox.set_type_checking(Some(Level::Warn));
+
+        let exit = match lox.run_files(&[path], IsolationMode::SharedGlobals) {
+            Ok(()) | Err(LoxError::Return(_)) => 0,
+            Err(e @ LoxError::Exit(_)) => exit_code(&e),
+            Err(e) => {
+                lox.report(&e);
+                exit_code(&e)
+            }
+        };
+        report(&stdout.take(), &stderr.take(), &exit.to_string())
+    };
+
+    // Deep recursion needs the stack the binary runs scripts on
+    thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(run)
+        .expect("Failed to start the interpreter thread")
+        .join()
+        .expect("The interpreter doesn't panic")
+}
+
+fn run_program(path: &Path) -> String {
+    if needs_binary(path) {
+        run_binary(path)
+    } else {
+        run_in_process(path)
+    }
+}
+
+#[test]
+fn golden_programs() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs");
+    let bless = std::env::var_os("LOX_BLESS").is_some();
+
+    let mut programs = Vec::new();
+    collect_programs(&root, &mut programs);
+    programs.sort();
+
+    let mut failures = Vec::new();
+    for program in &programs {
+        if !required_features_enabled(program) {
+            continue;
+        }
+
+        let actual = run_program(program);
+        let expected_path = program.with_extension("expected");
+
+        if bless {
+            fs::write(&expected_path, &actual).expect("Failed to write expected output");
+            continue;
+        }
+
+        let expected = fs::read_to_string(&expected_path).unwrap_or_default();
+        if expected != actual {
+            let name = program.strip_prefix(&root).unwrap().display();
+            failures.push(format!(
+                "{}\n=== expected\n{}=== actual\n{}",
+                name, expected, actual
+            ));
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} of {} golden programs failed (rerun with LOX_BLESS=1 to update):\n\n{}",
+        failures.len(),
+        programs.len(),
+        failures.join("\n")
+    );
+}
@@ -0,0 +1,4 @@
+#!/bin/sh
+# Stands in for a reference interpreter that disagrees on the second line.
+echo 1
+echo 3